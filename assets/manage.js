@@ -0,0 +1,52 @@
+(function () {
+  var manage = document.querySelector(".manage");
+  if (!manage) {
+    return;
+  }
+
+  function send(fields) {
+    fetch(manage.dataset.endpoint, {
+      method: "POST",
+      body: new URLSearchParams(fields),
+    })
+      .then(function (response) {
+        if (response.ok) {
+          window.location.reload();
+          return;
+        }
+
+        return response.text().then(function (reason) {
+          window.alert(manage.dataset.failed + ": " + (reason || response.status));
+        });
+      })
+      .catch(function () {
+        window.alert(manage.dataset.failed);
+      });
+  }
+
+  document.addEventListener("click", function (event) {
+    var button = event.target.closest("[data-action]");
+    if (!button) {
+      return;
+    }
+
+    var action = button.dataset.action;
+    var name = button.dataset.name || "";
+
+    if (action === "delete") {
+      if (window.confirm(manage.dataset.confirmDelete + " " + name + "?")) {
+        send({ action: "delete", path: button.dataset.path });
+      }
+    } else if (action === "rename") {
+      var newName = window.prompt(manage.dataset.newName, name);
+      if (newName && newName !== name) {
+        send({ action: "rename", path: button.dataset.path, name: newName });
+      }
+    } else if (action === "mkdir") {
+      var folder = window.prompt(manage.dataset.newName);
+      if (folder) {
+        send({ action: "mkdir", path: manage.dataset.dir, name: folder });
+      }
+    }
+  });
+})();
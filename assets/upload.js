@@ -0,0 +1,76 @@
+(function () {
+  var form = document.querySelector(".upload");
+  if (!form) {
+    return;
+  }
+
+  var input = form.querySelector("input[type=file]");
+  var progress = form.querySelector(".upload__progress");
+  var status = form.querySelector(".upload__status");
+
+  function upload(files) {
+    if (!files || files.length === 0) {
+      return;
+    }
+
+    var data = new FormData();
+    for (var i = 0; i !== files.length; i++) {
+      data.append("file", files[i], files[i].name);
+    }
+
+    var xhr = new XMLHttpRequest();
+    xhr.open("POST", window.location.pathname);
+    xhr.setRequestHeader("Accept", "application/json");
+
+    xhr.upload.onprogress = function (event) {
+      if (event.lengthComputable) {
+        progress.value = event.loaded / event.total;
+      }
+    };
+
+    xhr.onload = function () {
+      progress.hidden = true;
+      if (xhr.status === 201) {
+        window.location.reload();
+      } else {
+        status.textContent = form.dataset.failed + ": " + (xhr.responseText || xhr.status);
+      }
+    };
+
+    xhr.onerror = function () {
+      progress.hidden = true;
+      status.textContent = form.dataset.failed;
+    };
+
+    status.textContent = "";
+    progress.value = 0;
+    progress.hidden = false;
+    xhr.send(data);
+  }
+
+  form.addEventListener("submit", function (event) {
+    event.preventDefault();
+    upload(input.files);
+  });
+
+  input.addEventListener("change", function () {
+    upload(input.files);
+  });
+
+  document.addEventListener("dragover", function (event) {
+    event.preventDefault();
+    form.classList.add("upload--over");
+  });
+
+  document.addEventListener("dragleave", function (event) {
+    if (event.relatedTarget === null) {
+      form.classList.remove("upload--over");
+    }
+  });
+
+  document.addEventListener("drop", function (event) {
+    event.preventDefault();
+    form.classList.remove("upload--over");
+    upload(event.dataTransfer.files);
+  });
+})();
@@ -0,0 +1,215 @@
+//! Performance of the request parser, the static files and the listings
+//!
+//! Every benchmark runs twice, timed and counting the allocations, compare
+//! them with a saved baseline to catch regressions:
+//!
+//! ```sh
+//! cargo bench --bench perf -- --save-baseline main
+//! cargo bench --bench perf -- --baseline main
+//! ```
+
+use std::{fs, path::PathBuf};
+
+use criterion::{
+    criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter, WallTime},
+    BenchmarkId, Criterion, Throughput,
+};
+use httpr::{
+    http::HttpHandler,
+    static_server::StaticFileHandler,
+    testing::{allocations, parse_request, CountingAllocator, Load, LoadReport, LoopbackServer},
+};
+use tokio::runtime::{Builder, Runtime};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Clients sending requests at the same time in the loopback benchmarks
+const CLIENTS: u64 = 8;
+
+const FILE_SIZES: [usize; 2] = [1024, 256 * 1024];
+const LISTING_ENTRIES: usize = 500;
+
+const BROWSER_REQUEST: &[u8] = b"GET /assets/app.js?v=3 HTTP/1.1\r\n\
+    Host: localhost:4444\r\n\
+    User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+    Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+    Accept-Language: en-US,en;q=0.5\r\n\
+    Accept-Encoding: gzip, deflate, br\r\n\
+    Connection: keep-alive\r\n\
+    If-None-Match: \"5d41402abc4b2a76b9719d911017c592\"\r\n\r\n";
+
+/// Allocations done by the benchmarked code, see [CountingAllocator]
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        allocations()
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        allocations() - start
+    }
+
+    fn add(&self, v1: &u64, v2: &u64) -> u64 {
+        v1 + v2
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (n, unit) = match *throughput {
+            Throughput::Bytes(n) | Throughput::BytesDecimal(n) => (n, "allocs/B"),
+            Throughput::Elements(n) => (n, "allocs/elem"),
+        };
+        values.iter_mut().for_each(|v| *v /= n as f64);
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+/// Measurement named in the benchmark ids, to keep apart their baselines
+trait Measured: Measurement {
+    const NAME: &'static str;
+
+    /// Value of a loopback load in the measured unit
+    fn measured(report: &LoadReport) -> Self::Value;
+}
+
+impl Measured for WallTime {
+    const NAME: &'static str = "time";
+
+    fn measured(report: &LoadReport) -> Self::Value {
+        report.elapsed
+    }
+}
+
+impl Measured for Allocations {
+    const NAME: &'static str = "allocs";
+
+    fn measured(report: &LoadReport) -> Self::Value {
+        report.allocations
+    }
+}
+
+/// Files served by the benchmarks, created once in the temporary directory
+fn fixtures() -> PathBuf {
+    let root = std::env::temp_dir().join("httpr-bench");
+
+    for size in FILE_SIZES {
+        let path = root.join(format!("file-{size}.bin"));
+        if fs::metadata(&path).map_or(true, |m| m.len() != size as u64) {
+            fs::create_dir_all(&root).unwrap();
+            fs::write(&path, vec![b'x'; size]).unwrap();
+        }
+    }
+
+    let listing = root.join("listing");
+    fs::create_dir_all(&listing).unwrap();
+    for i in 0..LISTING_ENTRIES {
+        let path = listing.join(format!("entry-{i:04}.txt"));
+        if !path.exists() {
+            fs::write(path, i.to_string()).unwrap();
+        }
+    }
+
+    root
+}
+
+fn runtime() -> Runtime {
+    Builder::new_multi_thread().enable_all().build().unwrap()
+}
+
+fn parser<M: Measured>(c: &mut Criterion<M>) {
+    let runtime = Builder::new_current_thread().build().unwrap();
+
+    let mut group = c.benchmark_group(format!("parser/{}", M::NAME));
+    group.throughput(Throughput::Bytes(BROWSER_REQUEST.len() as u64));
+    group.bench_function("browser_get", |b| {
+        b.iter(|| runtime.block_on(parse_request(BROWSER_REQUEST)).unwrap())
+    });
+    group.finish();
+}
+
+fn listing<M: Measured>(c: &mut Criterion<M>) {
+    let runtime = runtime();
+    let handler = StaticFileHandler::new(fixtures(), true).unwrap();
+    let request = runtime
+        .block_on(parse_request(
+            b"GET /listing/ HTTP/1.1\r\nHost: localhost\r\nAccept: text/html\r\n\r\n",
+        ))
+        .unwrap();
+
+    let mut group = c.benchmark_group(format!("listing/{}", M::NAME));
+    group.throughput(Throughput::Elements(LISTING_ENTRIES as u64));
+    group.bench_function("render", |b| {
+        b.iter(|| runtime.block_on(handler.solve_request(&request)).unwrap())
+    });
+    group.finish();
+}
+
+fn loopback<M: Measured>(c: &mut Criterion<M>) {
+    let runtime = runtime();
+    let handler = StaticFileHandler::new(fixtures(), true).unwrap();
+    let server = runtime.block_on(LoopbackServer::start(handler)).unwrap();
+
+    let mut group = c.benchmark_group(format!("loopback/{}", M::NAME));
+    group.throughput(Throughput::Elements(CLIENTS));
+
+    let mut loads = FILE_SIZES
+        .map(|size| (format!("file/{size}"), format!("/file-{size}.bin")))
+        .to_vec();
+    loads.push((String::from("listing"), String::from("/listing/")));
+
+    // Each iteration is a request of every client
+    for (name, uri) in loads {
+        let load = Load::get(&uri).with_clients(CLIENTS as usize);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter_custom(|iters| {
+                let load = load.clone().with_requests(iters as usize);
+                let report = runtime.block_on(load.run(server.addr())).unwrap();
+                assert_eq!(report.failures, 0, "Failed requests to {uri}");
+                M::measured(&report)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(time, parser, listing, loopback);
+criterion_group! {
+    name = allocs;
+    config = Criterion::default().with_measurement(Allocations);
+    targets = parser, listing, loopback
+}
+criterion_main!(time, allocs);
@@ -13,10 +13,7 @@ impl HttpHandler for DummyHandler {
     ) -> Result<httpr::http::Response, &'static str> {
         info!("request: {request:?}");
 
-        let mut response = Response::new(httpr::http::HttpStatus::Ok);
-        response.add_body(b"Hello, world :)");
-
-        Ok(response)
+        Ok(Response::text("Hello, world :)"))
     }
 }
 
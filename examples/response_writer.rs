@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use httpr::http::{
+    HttpStatus, Named, Request, Response, ResponseWriter, Server, WriterAdapter, WriterHandler,
+};
+use tokio::{io, time::sleep};
+
+struct CountdownHandler {}
+impl Named for CountdownHandler {}
+
+#[async_trait]
+impl WriterHandler for CountdownHandler {
+    async fn write_response(
+        &self,
+        _request: Request,
+        mut writer: ResponseWriter,
+    ) -> io::Result<()> {
+        let mut head = Response::new(HttpStatus::Ok);
+        head.add_header(("Content-Type", "text/plain; charset=utf-8"));
+        writer.send_head(head)?;
+
+        for n in (1..=10).rev() {
+            writer.write(format!("{n}...\n").as_bytes()).await?;
+            sleep(Duration::from_millis(500)).await;
+        }
+
+        writer.write(b"Liftoff :)\n").await?;
+        writer.finish().await
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let bind = "127.0.0.1:4444";
+
+    let log_env = env_logger::Env::default().default_filter_or("debug");
+    env_logger::init_from_env(log_env);
+
+    Server::new(bind.to_string(), WriterAdapter::new(CountdownHandler {}))
+        .run()
+        .await
+        .unwrap();
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use httpr::{
+    http::{HttpHandler, HttpStatus, Named, Request, Response, Server},
+    static_server::{SendfileResInterceptor, StaticFileHandler},
+};
+use log::info;
+
+/// Serves the files of the current directory to the requests with the token only
+struct TokenHandler {}
+impl Named for TokenHandler {}
+
+#[async_trait]
+impl HttpHandler for TokenHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let url = request.url();
+        let authorized = url
+            .query_pairs()
+            .any(|(k, v)| k == "token" && v == "secret");
+
+        if !authorized {
+            info!("Rejected {}", url.path());
+            return Ok(Response::new(HttpStatus::Forbidden));
+        }
+
+        Ok(Response::sendfile(url.path()))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let bind = "127.0.0.1:4444";
+
+    let log_env = env_logger::Env::default().default_filter_or("debug");
+    env_logger::init_from_env(log_env);
+
+    let files = StaticFileHandler::new(".", false).unwrap();
+
+    Server::new(bind.to_string(), TokenHandler {})
+        .push_res_inter(Arc::new(SendfileResInterceptor::new(files)))
+        .run()
+        .await
+        .unwrap();
+}
@@ -1,25 +1,16 @@
-use std::sync::Arc;
-
-use httpr::{
-    http::Server,
-    static_server::{
-        NoBodyOnHeadResInterceptor, NotFoundRenderResInterceptor, OnlyGetReqInterceptor,
-        StaticFileHandler,
-    },
-};
+use httpr::{http::Server, static_server::StaticFileHandler};
 
 #[tokio::main]
 async fn main() {
     let bind = "127.0.0.1:4444".to_string();
     let handler = StaticFileHandler::new(".", true).expect("Failed creating handler");
+    let stack = handler.site_stack();
 
     let log_env = env_logger::Env::default().default_filter_or("debug");
     env_logger::init_from_env(log_env);
 
     Server::new(bind, handler)
-        .push_req_inter(Arc::new(OnlyGetReqInterceptor))
-        .push_res_inter(Arc::new(NoBodyOnHeadResInterceptor))
-        .push_res_inter(Arc::new(NotFoundRenderResInterceptor))
+        .push_stack(&stack)
         .run()
         .await
         .unwrap()
@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use httpr::http::{HttpHandler, HttpStatus, Named, Request, Response, Server};
+use log::{debug, warn};
+use tokio::io;
+
+/// Echoes every byte back once the client asks to `Upgrade: echo`
+struct EchoHandler {}
+impl Named for EchoHandler {}
+
+#[async_trait]
+impl HttpHandler for EchoHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        if request.header("upgrade") != Some("echo") {
+            return Ok(Response::new(HttpStatus::UpgradeRequired));
+        }
+
+        let Some(on_upgrade) = request.on_upgrade() else {
+            return Ok(Response::new(HttpStatus::BadRequest));
+        };
+
+        tokio::spawn(async move {
+            let upgraded = match on_upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(e) => return warn!("{e}"),
+            };
+
+            let (mut reader, mut writer) = upgraded.into_parts();
+            match io::copy(&mut reader, &mut writer).await {
+                Ok(echoed) => debug!("Echoed {echoed} bytes"),
+                Err(e) => warn!("{e}"),
+            }
+        });
+
+        Ok(Response::switching_protocols("echo"))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let bind = "127.0.0.1:4444";
+
+    let log_env = env_logger::Env::default().default_filter_or("debug");
+    env_logger::init_from_env(log_env);
+
+    Server::new(bind.to_string(), EchoHandler {})
+        .run()
+        .await
+        .unwrap();
+}
@@ -0,0 +1,508 @@
+//! HTTP/1.1 checks run against a live server, to catch protocol regressions
+//!
+//! The checks cover the happy path of RFC 9112: the framing of the responses,
+//! the line endings and header names accepted, `Expect: 100-continue`,
+//! pipelined requests and the persistence of the connections. Servers may
+//! close the connection after each response as long as they say so. Behind
+//! the `testing` feature.
+//!
+//! ```no_run
+//! use httpr::{conformance::Conformance, static_server::StaticFileHandler, testing::LoopbackServer};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let handler = StaticFileHandler::new(".", true).unwrap();
+//! let server = LoopbackServer::start(handler).await?;
+//!
+//! let report = Conformance::new(server.addr()).with_path("/").run().await;
+//! assert!(report.passed(), "{report}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{fmt, future::Future, io, net::SocketAddr, time::Duration};
+
+use tokio::{
+    io::{copy, sink, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// Time given to each read and write of the checks
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a client waits for `100 Continue` before sending the body anyway
+const EXPECT_CONTINUE_WAIT: Duration = Duration::from_secs(1);
+
+/// Body sent with `Expect: 100-continue`
+const EXPECTED_BODY: &[u8] = b"hello";
+
+/// Suite of checks against the server listening at an address
+///
+/// The path must answer `GET` with a `2xx` or `3xx` response.
+#[derive(Debug, Clone)]
+pub struct Conformance {
+    addr: SocketAddr,
+    host: String,
+    path: String,
+    timeout: Duration,
+}
+
+impl Conformance {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            host: addr.to_string(),
+            path: String::from("/"),
+            timeout: DEFAULT_CHECK_TIMEOUT,
+        }
+    }
+
+    /// Request `path` in the checks, `/` unless set
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// `Host` header of the requests, the address unless set
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    /// Time given to each read and write, [DEFAULT_CHECK_TIMEOUT] unless set
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run every check one after another
+    pub async fn run(&self) -> ConformanceReport {
+        let checks = vec![
+            Check::new("status-line", self.status_line().await),
+            Check::new("crlf-line-endings", self.crlf_line_endings().await),
+            Check::new("content-length", self.content_length().await),
+            Check::new("head-without-body", self.head_without_body().await),
+            Check::new(
+                "case-insensitive-headers",
+                self.case_insensitive_headers().await,
+            ),
+            Check::new("bare-lf-request", self.bare_lf_request().await),
+            Check::new("expect-continue", self.expect_continue().await),
+            Check::new("pipelining", self.pipelining().await),
+            Check::new("keep-alive", self.keep_alive().await),
+            Check::new("connection-close", self.connection_close().await),
+        ];
+
+        ConformanceReport { checks }
+    }
+
+    /// `HTTP/1.1 <code> <reason>`, a success for the path
+    async fn status_line(&self) -> Result<(), String> {
+        let response = self.exchange(&self.get(&[])).await?;
+
+        let line = String::from_utf8_lossy(&response.head);
+        let line = line.lines().next().unwrap_or_default();
+        let mut parts = line.splitn(3, ' ');
+        let (version, code) = (parts.next(), parts.next());
+        let is_code = |c: &str| c.len() == 3 && c.bytes().all(|b| b.is_ascii_digit());
+        if version != Some("HTTP/1.1") || !code.is_some_and(is_code) {
+            return Err(format!("Malformed status line {line:?}"));
+        }
+
+        match response.status {
+            200..=399 => Ok(()),
+            status => Err(format!("{} answered {status}", self.path)),
+        }
+    }
+
+    /// Every line of the head ends with CRLF
+    async fn crlf_line_endings(&self) -> Result<(), String> {
+        let response = self.exchange(&self.get(&[])).await?;
+
+        let bare_lf = response
+            .head
+            .iter()
+            .enumerate()
+            .any(|(i, b)| *b == b'\n' && (i == 0 || response.head[i - 1] != b'\r'));
+        match bare_lf {
+            true => Err(String::from("Head line ending with a bare LF")),
+            false => Ok(()),
+        }
+    }
+
+    /// A single `Content-Length` telling the exact bytes of the body
+    async fn content_length(&self) -> Result<(), String> {
+        let mut connection = self.connect().await?;
+        connection
+            .send(&self.get(&[("Connection", "close")]))
+            .await?;
+        let response = connection.response(false).await?;
+
+        let lengths = response.headers_named("content-length").count();
+        let chunked = response.header("transfer-encoding").is_some();
+        match (lengths, chunked) {
+            (0, true) => return Ok(()),
+            (0, false) if matches!(response.status, 204 | 304) => return Ok(()),
+            (1, false) => {}
+            (0, false) => return Err(String::from("Neither Content-Length nor Transfer-Encoding")),
+            (1, true) => return Err(String::from("Both Content-Length and Transfer-Encoding")),
+            (n, _) => return Err(format!("{n} Content-Length headers")),
+        }
+
+        // Extra bytes would be read as the next response
+        match connection.trailing().await? {
+            0 => Ok(()),
+            n => Err(format!("{n} bytes past the Content-Length")),
+        }
+    }
+
+    /// `HEAD` answered as `GET`, without the body
+    async fn head_without_body(&self) -> Result<(), String> {
+        let get = self.exchange(&self.get(&[])).await?;
+
+        let mut connection = self.connect().await?;
+        connection
+            .send(&self.request("HEAD", &[("Connection", "close")]))
+            .await?;
+        let head = connection.response(true).await?;
+
+        if head.status != get.status {
+            return Err(format!("HEAD answered {}, GET {}", head.status, get.status));
+        }
+        if head.header("content-length") != get.header("content-length") {
+            return Err(String::from("HEAD announced another Content-Length"));
+        }
+        match connection.trailing().await? {
+            0 => Ok(()),
+            n => Err(format!("HEAD answered with {n} bytes of body")),
+        }
+    }
+
+    /// Header names in any case
+    async fn case_insensitive_headers(&self) -> Result<(), String> {
+        let expected = self.exchange(&self.get(&[])).await?.status;
+
+        let request = format!(
+            "GET {} HTTP/1.1\r\nhOsT: {}\r\nUSER-AGENT: httpr-conformance\r\naccept: */*\r\n\r\n",
+            self.path, self.host
+        );
+        let status = self.exchange(request.as_bytes()).await?.status;
+        match status == expected {
+            true => Ok(()),
+            false => Err(format!("Answered {status} instead of {expected}")),
+        }
+    }
+
+    /// Request lines ending with LF only, RFC 9112 section 2.2
+    async fn bare_lf_request(&self) -> Result<(), String> {
+        let expected = self.exchange(&self.get(&[])).await?.status;
+
+        let request = format!("GET {} HTTP/1.1\nHost: {}\n\n", self.path, self.host);
+        let status = self.exchange(request.as_bytes()).await?.status;
+        match status == expected {
+            true => Ok(()),
+            false => Err(format!("Answered {status} instead of {expected}")),
+        }
+    }
+
+    /// `100 Continue` or the final response, never stuck waiting for the body
+    async fn expect_continue(&self) -> Result<(), String> {
+        let len = EXPECTED_BODY.len().to_string();
+        let head = self.request(
+            "POST",
+            &[("Expect", "100-continue"), ("Content-Length", &len)],
+        );
+
+        let mut connection = self.connect().await?;
+        connection.send(&head).await?;
+
+        // Clients send the body anyway when no answer comes soon
+        let early = timeout(EXPECT_CONTINUE_WAIT, connection.response(false)).await;
+        let response = match early {
+            Ok(Ok(response)) if response.status == 100 => {
+                connection.send(EXPECTED_BODY).await?;
+                connection.response(false).await?
+            }
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => return Err(e),
+            Err(_) => {
+                connection.send(EXPECTED_BODY).await?;
+                connection.response(false).await?
+            }
+        };
+
+        match response.status {
+            100..=199 => Err(format!(
+                "Interim {} instead of a final response",
+                response.status
+            )),
+            417 => Err(String::from("Expectation refused")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Requests sent together answered in order, or the first one closing
+    async fn pipelining(&self) -> Result<(), String> {
+        let expected = self.exchange(&self.get(&[])).await?.status;
+
+        let mut requests = self.get(&[]);
+        requests.extend(self.request("HEAD", &[]));
+
+        let mut connection = self.connect().await?;
+        connection.send(&requests).await?;
+
+        let first = connection.response(false).await?;
+        if first.status != expected {
+            return Err(format!(
+                "First answered {} instead of {expected}",
+                first.status
+            ));
+        }
+        if first.closes() {
+            return match connection.trailing().await? {
+                0 => Ok(()),
+                n => Err(format!("{n} bytes after the closing response")),
+            };
+        }
+
+        let second = connection.response(true).await?;
+        match second.status == expected {
+            true => Ok(()),
+            false => Err(format!(
+                "Second answered {} instead of {expected}",
+                second.status
+            )),
+        }
+    }
+
+    /// Connection open for another request unless the response closes it
+    async fn keep_alive(&self) -> Result<(), String> {
+        let mut connection = self.connect().await?;
+        connection.send(&self.get(&[])).await?;
+        let response = connection.response(false).await?;
+
+        if response.closes() {
+            return match connection.trailing().await? {
+                0 => Ok(()),
+                n => Err(format!("{n} bytes after the closing response")),
+            };
+        }
+
+        connection.send(&self.get(&[])).await?;
+        connection.response(false).await.map(|_| ())
+    }
+
+    /// Connection closed after a request with `Connection: close`
+    async fn connection_close(&self) -> Result<(), String> {
+        let mut connection = self.connect().await?;
+        connection
+            .send(&self.get(&[("Connection", "close")]))
+            .await?;
+        connection.response(false).await?;
+
+        match connection.trailing().await? {
+            0 => Ok(()),
+            n => Err(format!("{n} bytes after the last response")),
+        }
+    }
+
+    fn get(&self, headers: &[(&str, &str)]) -> Vec<u8> {
+        self.request("GET", headers)
+    }
+
+    fn request(&self, method: &str, headers: &[(&str, &str)]) -> Vec<u8> {
+        let mut request = format!("{method} {} HTTP/1.1\r\nHost: {}\r\n", self.path, self.host);
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        request.push_str("\r\n");
+
+        request.into_bytes()
+    }
+
+    /// Response to `request` on a new connection
+    async fn exchange(&self, request: &[u8]) -> Result<RawResponse, String> {
+        let mut connection = self.connect().await?;
+        connection.send(request).await?;
+        connection.response(false).await
+    }
+
+    async fn connect(&self) -> Result<Connection, String> {
+        let stream = limit(self.timeout, TcpStream::connect(self.addr)).await?;
+
+        Ok(Connection {
+            stream: BufReader::new(stream),
+            timeout: self.timeout,
+        })
+    }
+}
+
+/// Result of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    /// Why it failed, `None` if it passed
+    pub failure: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Result<(), String>) -> Self {
+        Self {
+            name,
+            failure: outcome.err(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Checks run by [Conformance::run], one line each when displayed
+#[derive(Debug, Clone)]
+pub struct ConformanceReport {
+    pub checks: Vec<Check>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(Check::passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed())
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.failure {
+                None => writeln!(f, "ok   {}", check.name)?,
+                Some(failure) => writeln!(f, "FAIL {}: {failure}", check.name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Response as read from the wire
+struct RawResponse {
+    /// Status line and headers, line endings included
+    head: Vec<u8>,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl RawResponse {
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.headers
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn header<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        self.headers_named(name).next()
+    }
+
+    /// Whether the server closes the connection after it
+    fn closes(&self) -> bool {
+        self.headers_named("connection")
+            .flat_map(|v| v.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("close"))
+    }
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    timeout: Duration,
+}
+
+impl Connection {
+    async fn send(&mut self, bytes: &[u8]) -> Result<(), String> {
+        limit(self.timeout, self.stream.get_mut().write_all(bytes)).await
+    }
+
+    /// Next response, its body read as framed
+    async fn response(&mut self, is_head: bool) -> Result<RawResponse, String> {
+        let timeout = self.timeout;
+        limit(timeout, read_response(&mut self.stream, is_head)).await
+    }
+
+    /// Bytes sent until the server closes the connection
+    async fn trailing(&mut self) -> Result<usize, String> {
+        let mut rest = Vec::new();
+        match timeout(self.timeout, self.stream.read_to_end(&mut rest)).await {
+            Ok(Ok(_)) => Ok(rest.len()),
+            // Closed with unread data on its side
+            Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionReset => Ok(rest.len()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(String::from("Connection left open")),
+        }
+    }
+}
+
+/// `future` failing after `duration`
+async fn limit<T, F>(duration: Duration, future: F) -> Result<T, String>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    match timeout(duration, future).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(String::from("Timed out")),
+    }
+}
+
+async fn read_response<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    is_head: bool,
+) -> io::Result<RawResponse> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        if reader.read_until(b'\n', &mut head).await? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        if matches!(&head[start..], b"\r\n" | b"\n") {
+            break;
+        }
+    }
+
+    let text = String::from_utf8_lossy(&head).into_owned();
+    let mut lines = text.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("Invalid status line"))?;
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.to_string(), v.trim().to_string()))
+        .collect::<Vec<_>>();
+
+    let response = RawResponse {
+        head,
+        status,
+        headers,
+    };
+
+    // The body of the unframed ones ends with the connection, read by the caller
+    let len = response
+        .header("content-length")
+        .and_then(|l| l.parse().ok());
+    match len {
+        Some(len) if !is_head && !matches!(status, 100..=199 | 204 | 304) => {
+            let read = copy(&mut (&mut *reader).take(len), &mut sink()).await?;
+            if read < len {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(response)
+}
@@ -1,22 +1,87 @@
 //! Essential tools to build a http server
 
-use std::any::type_name;
+use std::any::{type_name, Any};
 use std::collections::HashSet;
 use std::fmt;
+use std::future::Future;
 use std::ops::ControlFlow;
 use std::path::Path;
 use std::string::FromUtf8Error;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 
 use async_trait::async_trait;
-use log::{debug, error, info, log_enabled};
+use log::{debug, error, info, log_enabled, warn, LevelFilter};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
 use strum_macros::{Display, EnumString};
 use tokio::{
-    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Error},
-    net::{tcp::OwnedReadHalf, TcpListener},
+    fs::File,
+    io::{
+        self, AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader, Error, ErrorKind,
+    },
+    net::{tcp::OwnedReadHalf, TcpStream},
+    sync::watch,
+    task::JoinSet,
+    time::{timeout, Duration},
 };
 use url::Url;
 
+use admin::{LOG_LEVEL_KEY, MAX_CLIENT_CONNECTIONS_KEY};
+use builder::ListenerOptions;
+use clients::ClientTracker;
+use cors::CorsOptions;
+use proxies::TrustedProxies;
+use scoped::{PathPattern, ScopedReqInterceptor, ScopedResInterceptor};
+use stats::ServerStats;
+
+use crate::static_server::{templates, SENDFILE_HEADER};
+
+pub use admin::ConfigRegistry;
+pub use body::{Body, BodyStream};
+pub use builder::{ServerBuilder, DEFAULT_BACKLOG};
+pub use errors::{ErrorHandler, ProblemDetailsErrorHandler, ServerError};
+pub use extensions::Extensions;
+pub use request_log::{LogSink, MatchedRoute, RequestLog};
+pub use router::{PathParams, Router};
+pub use stack::InterceptorStack;
+pub use upgrade::{OnUpgrade, Upgraded};
+pub use writer::{ResponseWriter, WriterAdapter, WriterHandler, DEFAULT_WRITER_BUFFER};
+
+pub mod access_log;
+pub mod admin;
+pub mod auth;
+mod body;
+mod builder;
+mod clients;
+pub mod compression;
+pub mod conditional;
+pub mod cors;
+pub mod delay;
+mod errors;
+mod extensions;
+pub mod fault;
+pub mod method_filter;
+pub mod metrics;
+pub mod multipart;
+pub mod negotiation;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod proxies;
+pub mod proxy;
+pub mod range;
+pub mod recording;
+mod request_log;
+pub mod router;
+pub mod scoped;
+mod stack;
+pub mod stats;
+mod upgrade;
+pub mod vhost;
+mod writer;
+
 macro_rules! define_status {
     ($($name:ident = ($code:expr, $desc:expr)),*) => {
         #[derive(Debug, Clone, Copy, PartialEq)]
@@ -24,22 +89,37 @@ macro_rules! define_status {
             $(
                 $name,
             )*
+            /// Code without a built-in description, see [HttpStatus::custom]
+            Other(u16),
         }
 
         impl HttpStatus {
-            fn code(&self) -> u16 {
+            /// Status for a numeric code, `None` for unknown codes
+            pub fn from_code(code: u16) -> Option<Self> {
+                match code {
+                    $(
+                        $code => Some(HttpStatus::$name),
+                    )*
+                    _ => None,
+                }
+            }
+
+            pub fn code(&self) -> u16 {
                 match *self {
                     $(
                         HttpStatus::$name => $code,
                     )*
+                    HttpStatus::Other(code) => code,
                 }
             }
 
-            fn description(&self) -> &'static str {
+            /// Standard reason phrase, empty for [HttpStatus::Other] codes
+            pub fn description(&self) -> &'static str {
                 match *self {
                     $(
                         HttpStatus::$name => $desc,
                     )*
+                    HttpStatus::Other(_) => "",
                 }
             }
         }
@@ -120,6 +200,36 @@ define_status! {
     NetworkAuthenticationRequired = (511, "Network Authentication Required")
 }
 
+impl HttpStatus {
+    /// Status for any three digit code, built-in ones included
+    ///
+    /// ```
+    /// use httpr::http::HttpStatus;
+    ///
+    /// assert_eq!(HttpStatus::custom(404), Some(HttpStatus::NotFound));
+    /// assert_eq!(HttpStatus::custom(299), Some(HttpStatus::Other(299)));
+    /// assert_eq!(HttpStatus::custom(1000), None);
+    /// ```
+    pub fn custom(code: u16) -> Option<Self> {
+        match code {
+            100..=999 => Some(Self::from_code(code).unwrap_or(Self::Other(code))),
+            _ => None,
+        }
+    }
+
+    /// Statuses sending the client to a `Location`
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            HttpStatus::MovedPermanently
+                | HttpStatus::Found
+                | HttpStatus::SeeOther
+                | HttpStatus::TemporaryRedirect
+                | HttpStatus::PermanentRedirect
+        )
+    }
+}
+
 pub trait Named {
     fn name(&self) -> &str {
         type_name::<Self>().split("::").last().unwrap()
@@ -150,6 +260,36 @@ pub trait AsyncTryFrom<T>: Sized {
 
 const HTTP_VERSION: &str = "HTTP/1.1";
 
+/// Time allowed to receive the request line and the headers
+pub const DEFAULT_HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// Slowest average upload accepted for request bodies, in bytes per second
+pub const DEFAULT_MIN_BODY_RATE: u64 = 1024;
+/// Largest request body read, larger ones get `413 Payload Too Large`
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
+/// Longest request target accepted, longer ones get `414 URI Too Long`
+pub const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
+/// Room for the method and the protocol around the target in the request line
+const REQUEST_LINE_SLACK: usize = 64;
+/// Time allowed to the in-flight requests to finish on shutdown
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+/// `Retry-After` seconds of the requests answered with `503`
+const RETRY_AFTER_SECS: u64 = 5;
+
+/// Everything but the RFC 5987 `attr-char` set
+const RFC5987_ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
 #[derive(Default, Debug, Clone, Copy, EnumString, Display, Eq, PartialEq, Hash)]
 #[strum(serialize_all = "UPPERCASE")]
 pub enum Method {
@@ -165,7 +305,7 @@ pub enum Method {
     Patch,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 #[allow(dead_code)]
 pub struct Request {
     method: Method,
@@ -173,6 +313,13 @@ pub struct Request {
     version: String,
     headers: HashMap<String, String>,
     body: Vec<u8>,
+    extensions: Extensions,
+    peer_addr: Option<SocketAddr>,
+    remote_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    /// Unread body, shared by the copies of the request
+    body_stream: Option<Arc<std::sync::Mutex<Option<BodyStream>>>>,
+    upgrade: Option<Arc<std::sync::Mutex<Option<OnUpgrade>>>>,
 }
 
 impl Request {
@@ -190,80 +337,382 @@ impl Request {
         self.method
     }
 
+    /// Header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    /// Lowercase names and values of the headers
+    pub(crate) fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Best media type from `available` according to the `Accept` header
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        negotiation::negotiate_media(self.header("accept"), available)
+    }
+
+    /// Client address, the one told by the trusted proxies for their connections
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Address at the other end of the connection, a proxy one when proxied
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Address of the server the connection was accepted on
+    ///
+    /// Tells the interface when listening on all of them, like `0.0.0.0`.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Values attached to the request by the server or the interceptors
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Shared state registered with [Server::with_state]
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.extensions.get_arc::<T>()
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Take the body still in the connection, only the first call gets it
+    ///
+    /// `None` when the body was read before the handler, see
+    /// [Server::with_streamed_body].
+    pub fn body_stream(&self) -> Option<BodyStream> {
+        self.body_stream.as_ref()?.lock().unwrap().take()
+    }
+
+    /// Take the connection once answered with `101 Switching Protocols`, for
+    /// WebSockets, tunnels or other protocols
+    ///
+    /// Only the first call gets it, `None` as well when the body is being streamed.
+    pub fn on_upgrade(&self) -> Option<OnUpgrade> {
+        self.upgrade.as_ref()?.lock().unwrap().take()
+    }
+
+    /// Whether the client keeps the connection for another request, by default
+    /// with HTTP/1.1 and when asked with HTTP/1.0
+    pub fn keeps_alive(&self) -> bool {
+        let connection = self.header("connection").unwrap_or_default();
+        match self.version.as_str() {
+            "http/1.0" => has_option(connection, "keep-alive"),
+            _ => !has_option(connection, "close"),
+        }
+    }
+
     pub fn body_string(&self) -> Result<String, FromUtf8Error> {
         String::from_utf8(self.body.to_vec())
     }
 
-    /// ```
-    /// let mut request = Request::new(Method::Get, String::from("/"), HTTP_VERSION);
-    /// request.
+    /// Replace the body with its decoded `body`, dropping its `Content-Encoding`
+    pub(crate) fn set_decoded_body(&mut self, body: Vec<u8>) {
+        self.headers.remove("content-encoding");
+        self.headers
+            .insert(String::from("content-length"), body.len().to_string());
+        self.body = body;
+    }
+
+    /// Full request url built from the `host` header and the request uri
     ///
-    /// assert_eq!(request.method.url(), request.method());
-    /// ```
+    /// The server refuses the requests without a valid one, the others, like
+    /// the ones built by hand, get `localhost` and the root at worst.
     pub fn url(&self) -> Url {
-        let host = self.headers.get("host").unwrap();
-        Url::parse(&format!("http://{host}{}", self.uri)).unwrap()
+        if self.uri.contains("://") {
+            if let Ok(url) = Url::parse(&self.uri) {
+                return url;
+            }
+        }
+
+        let host = self.headers.get("host").map_or("localhost", String::as_str);
+        let path = match self.uri.starts_with('/') {
+            true => self.uri.as_str(),
+            false => "/",
+        };
+
+        Url::parse(&format!("http://{host}{path}"))
+            .or_else(|_| Url::parse(&format!("http://localhost{path}")))
+            .unwrap_or_else(|_| Url::parse("http://localhost/").expect("Valid url"))
     }
 }
 
+/// Whether `host` is a `host[:port]` authority, RFC 9110 section 7.2
+fn is_valid_host(host: &str) -> bool {
+    let Ok(url) = Url::parse(&format!("http://{host}/")) else {
+        return false;
+    };
+
+    !host.is_empty()
+        && url.host_str().is_some()
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none()
+}
+
+/// Whether `byte` can be part of a method name, a `tchar` of RFC 9110
+pub(crate) fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
 #[async_trait]
 impl AsyncTryFrom<BufReader<OwnedReadHalf>> for Request {
     type Error = Error;
 
     async fn try_from(value: BufReader<OwnedReadHalf>) -> Result<Self, Self::Error> {
-        let reader = BufReader::new(value);
-        let mut lines = reader.lines();
+        let mut reader = BufReader::new(value);
+
+        let mut request = Request::read_head(&mut reader, DEFAULT_MAX_URI_LENGTH).await?;
+        request.read_body(&mut reader).await?;
+
+        Ok(request)
+    }
+}
+
+impl Request {
+    /// Read the request line and the headers
+    ///
+    /// Targets over `max_uri_length` and unknown methods fail with an
+    /// `InvalidData` error wrapping their [ServerError].
+    pub(crate) async fn read_head<R: AsyncBufRead + Unpin + Send>(
+        reader: &mut R,
+        max_uri_length: usize,
+    ) -> io::Result<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg);
+        let refused = |error: ServerError| Error::new(ErrorKind::InvalidData, error);
+
+        // The line is read up to the limit, never whole into memory
+        let limit = max_uri_length + REQUEST_LINE_SLACK;
+        let mut first_line = String::new();
+        let read = (&mut *reader)
+            .take(limit as u64)
+            .read_line(&mut first_line)
+            .await?;
+        if read == 0 {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+        if !first_line.ends_with('\n') && read >= limit {
+            return Err(refused(ServerError::UriTooLong));
+        }
 
-        let first_line = lines.next_line().await.unwrap().unwrap();
         let mut parts = first_line.split_whitespace();
+        let verb = parts.next().ok_or_else(|| invalid("verb"))?;
+        let method = match verb.to_uppercase().parse::<Method>() {
+            Ok(method) => method,
+            Err(_) if verb.bytes().all(is_token_byte) => {
+                return Err(refused(ServerError::NotImplemented(format!(
+                    "Method {verb}"
+                ))));
+            }
+            Err(_) => return Err(invalid("Invalid method")),
+        };
 
-        let (verb, uri, protocol) = (
-            parts
-                .next()
-                .expect("verb")
-                .to_uppercase()
-                .parse::<Method>()
-                .expect("Not allowed method!"),
-            parts.next().expect("path").to_string(),
-            parts.next().expect("protocol").to_lowercase(),
-        );
+        let uri = parts.next().ok_or_else(|| invalid("path"))?.to_string();
+        if uri.len() > max_uri_length {
+            return Err(refused(ServerError::UriTooLong));
+        }
 
-        let mut request = Request::new(verb, uri, protocol);
+        let protocol = parts
+            .next()
+            .ok_or_else(|| invalid("protocol"))?
+            .to_lowercase();
 
-        while let Some(line) = lines.next_line().await? {
+        let mut request = Request::new(method, uri, protocol);
+        let mut content_lengths = Vec::new();
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+
+            let line = line.trim_end_matches(['\r', '\n']);
             if line.is_empty() {
                 break;
             }
 
-            if let Some((key, value)) = line.split_once(": ") {
-                request.headers.insert(key.to_lowercase(), value.into());
+            // Folded lines are obsolete, RFC 9112 section 5.2
+            if line.starts_with([' ', '\t']) {
+                return Err(invalid("Folded header line"));
+            }
+
+            // Optional whitespace around the value, RFC 9112 section 5
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("Header without colon"))?;
+            let value = value.trim_matches([' ', '\t']);
+
+            // Proxies may read a header with spaces in its name differently
+            if key.is_empty() || !key.bytes().all(is_token_byte) {
+                return Err(invalid("Invalid header name"));
             }
-        }
 
-        if let Some(len) = request.headers.get("content-length") {
-            let len = len.parse().unwrap_or(0usize);
-            request.body.resize(len, 0);
+            let key = key.to_lowercase();
+            if key == "content-length" {
+                content_lengths.extend(value.split(',').map(|len| len.trim().to_string()));
+            }
 
-            lines.get_mut().read_exact(&mut request.body).await?;
+            // Repeated fields are one list, but for the single host
+            match request.headers.get_mut(&key) {
+                Some(_) if key == "host" => return Err(invalid("Repeated Host")),
+                Some(values) => {
+                    values.push_str(", ");
+                    values.push_str(value);
+                }
+                None => {
+                    request.headers.insert(key, value.into());
+                }
+            }
         }
 
+        request.check_framing(&content_lengths)?;
+        request.check_host()?;
+
         Ok(request)
     }
+
+    /// Reject the requests whose url can't be built, RFC 9112 section 3.2
+    ///
+    /// HTTP/1.1 requires a `host[:port]` `Host`, the target must be a path,
+    /// `*` or an absolute URL.
+    fn check_host(&self) -> io::Result<()> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg);
+
+        match self.headers.get("host") {
+            Some(host) if !is_valid_host(host) => return Err(invalid("Invalid Host")),
+            None if self.version == "http/1.1" => return Err(invalid("Missing Host")),
+            _ => {}
+        }
+
+        let is_target = self.uri.starts_with('/') || self.uri == "*" || self.uri.contains("://");
+        if !is_target {
+            return Err(invalid("Invalid request target"));
+        }
+
+        Ok(())
+    }
+
+    /// Reject the requests whose body length is ambiguous, RFC 9112 section 6.3
+    ///
+    /// A server and a proxy in front of it could split them in different requests.
+    /// Bodies with a transfer coding fail wrapping a [ServerError::NotImplemented].
+    fn check_framing(&mut self, content_lengths: &[String]) -> io::Result<()> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg);
+
+        let coding = self.headers.get("transfer-encoding");
+        let Some(first) = content_lengths.first() else {
+            // No transfer coding is decoded, the body would be left unread
+            return match coding {
+                Some(coding) => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    ServerError::NotImplemented(format!("Transfer-Encoding {coding}")),
+                )),
+                None => Ok(()),
+            };
+        };
+
+        if coding.is_some() {
+            return Err(invalid("Both Content-Length and Transfer-Encoding"));
+        }
+        if content_lengths.iter().any(|len| len != first) {
+            return Err(invalid("Conflicting Content-Length values"));
+        }
+        if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid("Invalid Content-Length"));
+        }
+        first
+            .parse::<u64>()
+            .map_err(|_| invalid("Invalid Content-Length"))?;
+
+        // Repeated equal values are read as one
+        self.headers
+            .insert(String::from("content-length"), first.clone());
+
+        Ok(())
+    }
+
+    /// Read the `Content-Length` bytes of body
+    pub(crate) async fn read_body<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        let len = self.content_length();
+        if len > 0 {
+            self.body.resize(len as usize, 0);
+            reader.read_exact(&mut self.body).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy of the request for another `uri` of the server, reading it only
+    pub(crate) fn internal(&self, uri: &str) -> Request {
+        let method = match self.method {
+            Method::Head => Method::Head,
+            _ => Method::Get,
+        };
+
+        Request {
+            method,
+            uri: uri.to_string(),
+            ..self.head()
+        }
+    }
+
+    /// Copy of the request without body nor extensions
+    fn head(&self) -> Request {
+        Request {
+            headers: self.headers.clone(),
+            peer_addr: self.peer_addr,
+            remote_addr: self.remote_addr,
+            local_addr: self.local_addr,
+            ..Request::new(self.method, self.uri.clone(), self.version.clone())
+        }
+    }
+
+    fn content_length(&self) -> u64 {
+        self.header("content-length")
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Debug)]
 pub struct Response {
     status: HttpStatus,
-    headers: HashMap<String, String>,
-    body: Vec<u8>,
+    reason: Option<String>,
+    /// Lowercase names, in the order they were added, repeated ones included
+    headers: Vec<(String, String)>,
+    body: Body,
+    /// Send no `Content-Length` at all
+    omit_content_length: bool,
+    /// Reset the connection instead of writing the response
+    reset: bool,
 }
 
 impl Response {
     pub fn new(status: HttpStatus) -> Self {
         Self {
             status,
-            headers: HashMap::new(),
-            body: Vec::new(),
+            reason: None,
+            headers: Vec::new(),
+            body: Body::default(),
+            omit_content_length: false,
+            reset: false,
         }
     }
 
@@ -271,16 +720,156 @@ impl Response {
         Self::new(HttpStatus::NotFound)
     }
 
+    pub fn no_content() -> Self {
+        Self::new(HttpStatus::NoContent)
+    }
+
+    /// `200` with an HTML `body`
+    pub fn html(body: &str) -> Self {
+        Self::with_body("text/html; charset=utf-8", body.as_bytes())
+    }
+
+    /// `200` with a plain text `body`
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// let response = Response::text("Hello, world :)");
+    /// assert_eq!(response.status(), HttpStatus::Ok);
+    /// assert_eq!(response.header("content-type"), Some("text/plain; charset=utf-8"));
+    /// ```
+    pub fn text(body: &str) -> Self {
+        Self::with_body("text/plain; charset=utf-8", body.as_bytes())
+    }
+
+    /// `200` with `value` as JSON, a `500` when it can't be serialized
+    ///
+    /// ```
+    /// use httpr::http::Response;
+    ///
+    /// let response = Response::json(&serde_json::json!({ "files": ["a.txt"] }));
+    /// assert_eq!(response.header("content-type"), Some("application/json"));
+    /// ```
+    pub fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self::with_body("application/json", &body),
+            Err(e) => {
+                error!("Can't serialize the JSON response: {e}");
+                Self::new(HttpStatus::InternalServerError)
+            }
+        }
+    }
+
+    fn with_body(content_type: &str, body: &[u8]) -> Self {
+        let mut response = Self::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", content_type));
+        response.add_body(body);
+        response
+    }
+
+    /// `200` streaming the file at `path`, typed by its extension
+    ///
+    /// Unknown extensions are sent as `application/octet-stream`.
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let response = Response::from_file("Cargo.toml").await?;
+    /// assert_eq!(response.status(), HttpStatus::Ok);
+    /// assert_eq!(response.header("content-type"), Some("text/x-toml"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).await?;
+        let metadata = file.metadata().await?;
+
+        if !metadata.is_file() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Not a file"));
+        }
+
+        let content_type = mime_guess::from_path(path).first_or_octet_stream();
+
+        let mut response = Self::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", content_type.as_ref()));
+        response.stream_body(file, metadata.len());
+
+        Ok(response)
+    }
+
+    /// `101` switching the connection to `protocol`, see [Request::on_upgrade]
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// let response = Response::switching_protocols("websocket");
+    /// assert_eq!(response.status(), HttpStatus::SwitchingProtocols);
+    /// assert_eq!(response.header("upgrade"), Some("websocket"));
+    /// ```
+    pub fn switching_protocols(protocol: &str) -> Self {
+        let mut response = Self::new(HttpStatus::SwitchingProtocols);
+        response.add_header(("Connection", "upgrade"));
+        response.add_header(("Upgrade", protocol));
+        response
+    }
+
+    /// `301` to `path`, cached by the browsers, see [Response::redirect_with]
     pub fn redirect<P: AsRef<Path>>(path: P) -> Self {
-        let mut response = Self::new(HttpStatus::MovedPermanently);
-        response.headers.insert(
-            "Location".to_string(),
-            path.as_ref().to_str().unwrap().to_string(),
-        );
+        Self::redirect_with(HttpStatus::MovedPermanently, path)
+    }
+
+    /// Redirect to `location` with a redirect `status`
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// let response = Response::redirect_with(HttpStatus::TemporaryRedirect, "/docs/");
+    /// assert_eq!(response.status(), HttpStatus::TemporaryRedirect);
+    /// assert_eq!(response.header("location"), Some("/docs/"));
+    /// ```
+    pub fn redirect_with<P: AsRef<Path>>(status: HttpStatus, location: P) -> Self {
+        let mut response = Self::new(status);
+        response.add_header(("Location", &location.as_ref().to_string_lossy()));
+
+        response
+    }
+
+    /// Let a [SendfileResInterceptor](crate::static_server::SendfileResInterceptor) serve
+    /// the file at `path` of its root
+    ///
+    /// ```
+    /// use httpr::http::Response;
+    ///
+    /// let response = Response::sendfile("/reports/2024.pdf");
+    /// assert_eq!(response.header("x-sendfile"), Some("/reports/2024.pdf"));
+    /// ```
+    pub fn sendfile(path: &str) -> Self {
+        let mut response = Self::new(HttpStatus::Ok);
+        response.add_header((SENDFILE_HEADER, path));
+        response
+    }
 
+    /// Close the connection with a TCP reset instead of answering, to try the clients
+    ///
+    /// ```
+    /// use httpr::http::Response;
+    ///
+    /// assert!(Response::reset().is_reset());
+    /// ```
+    pub fn reset() -> Self {
+        let mut response = Self::new(HttpStatus::InternalServerError);
+        response.reset = true;
         response
     }
 
+    pub fn is_reset(&self) -> bool {
+        self.reset
+    }
+
+    #[deprecated(note = "sends a non-standard `Allowed` header, use `Response::options`")]
     pub fn allowed(methods: HashSet<Method>) -> Self {
         let methods_string = methods
             .into_iter()
@@ -288,52 +877,301 @@ impl Response {
             .collect::<Vec<_>>()
             .join(",");
 
-        let mut headers = HashMap::with_capacity(1);
-        headers.insert("Allowed".to_string(), methods_string);
+        let headers = vec![("Allowed".to_string(), methods_string)];
 
         Self {
             status: HttpStatus::NoContent,
+            reason: None,
             headers,
-            body: Vec::new(),
+            body: Body::default(),
+            omit_content_length: false,
+            reset: false,
         }
     }
 
+    /// `405` listing the `allow`ed methods in the `Allow` header
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Method, Response};
+    ///
+    /// let response = Response::method_not_allowed(&[Method::Get, Method::Head]);
+    /// assert_eq!(response.status(), HttpStatus::MethodNotAllowed);
+    /// assert_eq!(response.header("allow"), Some("GET, HEAD"));
+    /// ```
+    pub fn method_not_allowed(allow: &[Method]) -> Self {
+        let mut response = Self::new(HttpStatus::MethodNotAllowed);
+        response.add_header(("Allow", &allow_header(allow)));
+        response
+    }
+
+    /// `204` answer of an `OPTIONS` request, with the CORS preflight headers when given
+    ///
+    /// Change the status with [Response::set_status] for clients expecting a `200`.
+    ///
+    /// ```
+    /// use httpr::http::{cors::CorsOptions, Method, Response};
+    ///
+    /// let cors = CorsOptions::new("*");
+    /// let response = Response::options(&[Method::Get, Method::Post], Some(&cors));
+    /// assert_eq!(response.header("allow"), Some("GET, POST"));
+    /// assert_eq!(response.header("access-control-allow-methods"), Some("GET, POST"));
+    /// assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+    /// ```
+    pub fn options(allow: &[Method], cors: Option<&CorsOptions>) -> Self {
+        let allow = allow_header(allow);
+
+        let mut response = Self::new(HttpStatus::NoContent);
+        response.add_header(("Allow", &allow));
+
+        if let Some(cors) = cors {
+            response.add_header(("Access-Control-Allow-Methods", &allow));
+            for (name, value) in cors.headers() {
+                response.add_header((name, &value));
+            }
+        }
+
+        response
+    }
+
     pub fn status(&self) -> HttpStatus {
         self.status
     }
 
+    pub fn set_status(&mut self, status: HttpStatus) {
+        self.status = status;
+    }
+
+    /// Reason phrase of the status line, the standard one unless replaced
+    pub fn reason(&self) -> &str {
+        self.reason
+            .as_deref()
+            .unwrap_or_else(|| self.status.description())
+    }
+
+    /// Replace the reason phrase of the status line, control characters are dropped
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// let mut response = Response::new(HttpStatus::custom(299).unwrap());
+    /// response.set_reason("Mostly Fine\r\n");
+    /// assert!(response.as_bytes().starts_with(b"HTTP/1.1 299 Mostly Fine\r\n"));
+    /// ```
+    pub fn set_reason(&mut self, reason: &str) {
+        self.reason = Some(reason.chars().filter(|c| !c.is_control()).collect());
+    }
+
+    /// Header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set a header, replacing the values it had
     pub fn add_header(&mut self, (k, value): (&str, &str)) {
-        self.headers.insert(k.to_lowercase(), value.to_string());
+        self.remove_header(k);
+        self.append_header((k, value));
+    }
+
+    /// Add a value to a header, keeping the ones it had
+    ///
+    /// For the headers sent once per value, like `Set-Cookie`.
+    ///
+    /// ```
+    /// use httpr::http::{HttpStatus, Response};
+    ///
+    /// let mut response = Response::new(HttpStatus::Ok);
+    /// response.append_header(("Set-Cookie", "a=1"));
+    /// response.append_header(("Set-Cookie", "b=2"));
+    /// let head = String::from_utf8(response.as_bytes()).unwrap();
+    /// assert!(head.contains("set-cookie: a=1\r\nset-cookie: b=2\r\n"));
+    /// ```
+    pub fn append_header(&mut self, (k, value): (&str, &str)) {
+        self.headers.push((k.to_lowercase(), value.to_string()));
+    }
+
+    /// Lowercase names and values of the headers
+    pub(crate) fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Remove a header by case-insensitive name, returning its first value
+    pub fn remove_header(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.headers.retain_mut(|(k, v)| {
+            let is_removed = k.eq_ignore_ascii_case(name);
+            if is_removed && removed.is_none() {
+                removed = Some(std::mem::take(v));
+            }
+            !is_removed
+        });
+        removed
+    }
+
+    /// Ask the client to download the body as `file_name` instead of showing it
+    ///
+    /// Non-ASCII names are sent with the RFC 5987 encoding next to an ASCII fallback.
+    pub fn add_attachment(&mut self, file_name: &str) {
+        let fallback = file_name
+            .chars()
+            .map(|c| match c {
+                ' '..='~' if c != '"' && c != '\\' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+
+        let mut value = format!("attachment; filename=\"{fallback}\"");
+        if fallback != file_name {
+            let encoded = utf8_percent_encode(file_name, RFC5987_ATTR_CHAR);
+            value.push_str(&format!("; filename*=UTF-8''{encoded}"));
+        }
+
+        self.add_header(("Content-Disposition", &value));
     }
 
     pub fn add_body(&mut self, body: &[u8]) {
-        self.body = body.to_vec();
+        self.set_body(Body::Bytes(body.to_vec()));
+    }
+
+    /// Render the registered template `name` with `context` as an HTML body
+    ///
+    /// See [templates] to register them.
+    pub fn render<T: Serialize>(&mut self, name: &str, context: &T) -> Result<(), &'static str> {
+        let body = templates::render(name, context)?;
+
+        self.add_header(("Content-Type", "text/html; charset=utf-8"));
+        self.add_body(body.as_bytes());
+        Ok(())
+    }
+
+    /// Stream `len` bytes from `reader` as body instead of keeping them in memory
+    pub fn stream_body<R: AsyncRead + Send + 'static>(&mut self, reader: R, len: u64) {
+        self.set_body(Body::stream(reader, len));
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
     }
 
     pub fn clean_body(&mut self) {
-        self.body.clear();
+        self.set_body(Body::default());
+    }
+
+    /// Move the body out, leaving an empty one
+    pub fn take_body(&mut self) -> Body {
+        std::mem::take(&mut self.body)
+    }
+
+    /// Replace the body, in memory or streamed
+    ///
+    /// A length set for the previous body, by header or
+    /// [Response::set_content_length], is dropped.
+    pub fn set_body(&mut self, body: Body) {
+        self.body = body;
+        self.remove_header("content-length");
+    }
+
+    /// Announce `len` bytes of body instead of the length of the body
+    ///
+    /// For bodies whose size is known but not to the [Body], like an unbounded
+    /// reader of a file, or the answers to `HEAD` without body. The body must
+    /// then be `len` bytes long, set it before since replacing it drops `len`.
+    ///
+    /// ```
+    /// use httpr::http::{Body, Response};
+    ///
+    /// let mut response = Response::text("");
+    /// response.set_body(Body::unbounded(&b"hello"[..]));
+    /// response.set_content_length(5);
+    /// assert_eq!(response.content_length(), Some(5));
+    /// ```
+    pub fn set_content_length(&mut self, len: u64) {
+        self.add_header(("Content-Length", &len.to_string()));
+    }
+
+    /// Send no `Content-Length`, the body ends with the connection
+    ///
+    /// For bodies framed by other means, like a `Transfer-Encoding` set by the
+    /// handler. Replacing the body keeps it omitted.
+    pub fn omit_content_length(&mut self) {
+        self.omit_content_length = true;
+    }
+
+    /// Length announced in the head, `None` if it isn't sent
+    ///
+    /// `1xx` and `204` responses never have one. Otherwise it is the one set
+    /// with [Response::set_content_length] or the `Content-Length` header, else
+    /// the length of the body, but for `304`, unbounded bodies and responses
+    /// with a `Transfer-Encoding`.
+    pub fn content_length(&self) -> Option<u64> {
+        if self.omit_content_length || matches!(self.status.code(), 100..=199 | 204) {
+            return None;
+        }
+
+        let header = self.header("content-length");
+        match header.and_then(|len| len.trim().parse().ok()) {
+            Some(len) => Some(len),
+            None if self.status.code() == 304
+                || !self.body.is_sized()
+                || self.header("transfer-encoding").is_some() =>
+            {
+                None
+            }
+            None => Some(self.body.len()),
+        }
     }
 
+    /// Serialized response, streamed bodies aren't included
     pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.head_bytes();
+
+        if let Body::Bytes(body) = &self.body {
+            bytes.extend_from_slice(body);
+        }
+
+        bytes
+    }
+
+    /// Write the serialized response, streaming the body if needed
+    pub async fn write_to<W: AsyncWrite + Unpin>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.head_bytes()).await?;
+        self.body.write_to(writer).await?;
+        writer.flush().await
+    }
+
+    /// Write the status line and headers only, as an answer to `HEAD`
+    ///
+    /// `Content-Length` still announces the body, which is never read.
+    pub async fn write_head_to<W: AsyncWrite + Unpin>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.head_bytes()).await?;
+        writer.flush().await
+    }
+
+    fn head_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
         let status_line = format!(
             "{} {} {}\r\n",
             HTTP_VERSION,
             self.status.code(),
-            self.status.description()
+            self.reason()
         );
         bytes.extend_from_slice(status_line.as_bytes());
 
-        for (k, v) in &self.headers {
+        // The length is written once, the one announced
+        for (k, v) in self.headers.iter().filter(|(k, _)| *k != "content-length") {
             let line = format!("{k}: {v}\r\n");
             bytes.extend_from_slice(line.as_bytes());
         }
 
-        let len_line = format!("Content-Length: {}\r\n\r\n", self.body.len());
-        bytes.extend_from_slice(len_line.as_bytes());
+        if let Some(len) = self.content_length() {
+            let len_line = format!("Content-Length: {len}\r\n");
+            bytes.extend_from_slice(len_line.as_bytes());
+        }
 
-        bytes.extend_from_slice(&self.body);
+        bytes.extend_from_slice(b"\r\n");
 
         bytes
     }
@@ -344,6 +1182,24 @@ pub struct Server<H> {
     handler: Arc<H>,
     interceptors_req: Vec<Arc<dyn InterceptorReq>>,
     interceptors_res: Vec<Arc<dyn InterceptorRes>>,
+    /// Path scoped, run around the global ones
+    scoped_req: Vec<Arc<dyn InterceptorReq>>,
+    scoped_res: Vec<Arc<dyn InterceptorRes>>,
+    state: Extensions,
+    head_timeout: Duration,
+    max_uri_length: usize,
+    min_body_rate: Option<u64>,
+    max_body_size: Option<u64>,
+    streamed_body: Option<u64>,
+    request_timeout: Option<Duration>,
+    shutdown_grace: Duration,
+    clients: Arc<ClientTracker>,
+    log_sinks: Vec<Arc<dyn LogSink>>,
+    error_handler: Option<Arc<dyn ErrorHandler>>,
+    stats: Arc<ServerStats>,
+    config: Arc<ConfigRegistry>,
+    trusted_proxies: Arc<TrustedProxies>,
+    listener: ListenerOptions,
 }
 
 impl<H: HttpHandler> Server<H> {
@@ -353,9 +1209,136 @@ impl<H: HttpHandler> Server<H> {
             handler: Arc::new(handler),
             interceptors_req: Vec::new(),
             interceptors_res: Vec::new(),
+            scoped_req: Vec::new(),
+            scoped_res: Vec::new(),
+            state: Extensions::new(),
+            head_timeout: DEFAULT_HEAD_TIMEOUT,
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+            min_body_rate: Some(DEFAULT_MIN_BODY_RATE),
+            max_body_size: Some(DEFAULT_MAX_BODY_SIZE),
+            streamed_body: None,
+            request_timeout: None,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            clients: Arc::new(ClientTracker::new(None)),
+            log_sinks: Vec::new(),
+            error_handler: None,
+            stats: Arc::new(ServerStats::new()),
+            config: Arc::new(ConfigRegistry::new()),
+            trusted_proxies: Arc::new(TrustedProxies::default()),
+            listener: ListenerOptions::default(),
         }
     }
 
+    /// [ServerBuilder] to tune the listening socket
+    pub fn builder(bind: &str, handler: H) -> ServerBuilder<H> {
+        ServerBuilder::new(bind, handler)
+    }
+
+    /// Share `state` with handlers and interceptors through [Request::state]
+    ///
+    /// Only one state value per type is kept, registering the same type twice replaces it.
+    pub fn with_state<T: Send + Sync + 'static>(&mut self, state: Arc<T>) -> &mut Self {
+        self.state.insert_arc(state);
+        self
+    }
+
+    /// Deadline to receive the request head, slower clients get `408 Request Timeout`
+    ///
+    /// Kept-alive connections are also closed once idle that long.
+    pub fn with_head_timeout(&mut self, head_timeout: Duration) -> &mut Self {
+        self.head_timeout = head_timeout;
+        self
+    }
+
+    /// Longest request target accepted, longer ones get `414 URI Too Long`
+    pub fn with_max_uri_length(&mut self, max_uri_length: usize) -> &mut Self {
+        self.max_uri_length = max_uri_length;
+        self
+    }
+
+    /// Slowest average rate, in bytes per second, to receive the request body
+    ///
+    /// The body deadline is the head timeout plus the time to upload it at this rate,
+    /// `None` waits for the body forever.
+    pub fn with_min_body_rate(&mut self, min_body_rate: Option<u64>) -> &mut Self {
+        self.min_body_rate = min_body_rate;
+        self
+    }
+
+    /// Largest request body read, larger ones get `413 Payload Too Large` before reading it
+    ///
+    /// Streamed bodies aren't read by the server and aren't limited, `None` reads any body.
+    pub fn with_max_body_size(&mut self, max_body_size: Option<u64>) -> &mut Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Leave the request bodies of `min_size` bytes or more in the connection
+    ///
+    /// The handler reads them as it goes with [Request::body_stream] and [Request::body]
+    /// is empty. The min body rate doesn't apply to them, `None` reads every body first.
+    pub fn with_streamed_body(&mut self, min_size: Option<u64>) -> &mut Self {
+        self.streamed_body = min_size;
+        self
+    }
+
+    /// Time allowed to interceptors and handler to answer a request
+    ///
+    /// Late requests are cancelled and answered with `503 Service Unavailable`.
+    pub fn with_request_timeout(&mut self, request_timeout: Option<Duration>) -> &mut Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Time allowed to the in-flight requests to finish once shutdown starts
+    ///
+    /// The connections still open afterwards are closed.
+    pub fn with_shutdown_grace(&mut self, shutdown_grace: Duration) -> &mut Self {
+        self.shutdown_grace = shutdown_grace;
+        self
+    }
+
+    /// Take the client address from the forwarding headers of these proxies
+    ///
+    /// Their connections are capped per client once the request is read. Handlers
+    /// read them with [Request::state], like [ProxyHandler](proxy::ProxyHandler) to
+    /// keep only their forwarding headers.
+    pub fn with_trusted_proxies(&mut self, trusted_proxies: TrustedProxies) -> &mut Self {
+        self.trusted_proxies = Arc::new(trusted_proxies);
+        self.state.insert_arc(self.trusted_proxies.clone());
+        self
+    }
+
+    /// Cap the simultaneous connections of each client address
+    ///
+    /// The excess is answered with `429 Too Many Requests`, `None` removes the cap.
+    pub fn with_max_client_connections(&mut self, max: Option<usize>) -> &mut Self {
+        self.clients.set_max_per_client(max);
+        self
+    }
+
+    /// Send the [RequestLog] of every answered request to `log_sink`
+    pub fn push_log_sink(&mut self, log_sink: Arc<dyn LogSink>) -> &mut Self {
+        self.log_sinks.push(log_sink);
+        self
+    }
+
+    /// Live counters of the connections and requests
+    pub fn stats(&self) -> Arc<ServerStats> {
+        self.stats.clone()
+    }
+
+    /// Settings changeable while running, like the log level
+    pub fn config(&self) -> Arc<ConfigRegistry> {
+        self.config.clone()
+    }
+
+    /// Build the responses of the [ServerError]s with `error_handler`
+    pub fn with_error_handler(&mut self, error_handler: Arc<dyn ErrorHandler>) -> &mut Self {
+        self.error_handler = Some(error_handler);
+        self
+    }
+
     pub fn push_req_inter(&mut self, req_inter: Arc<dyn InterceptorReq>) -> &mut Self {
         self.interceptors_req.push(req_inter);
         self
@@ -366,73 +1349,679 @@ impl<H: HttpHandler> Server<H> {
         self
     }
 
+    /// Run `req_inter` only for the request paths matching `pattern`
+    ///
+    /// Scoped interceptors run before all the global ones, in push order.
+    pub fn push_scoped_req_inter(
+        &mut self,
+        pattern: PathPattern,
+        req_inter: Arc<dyn InterceptorReq>,
+    ) -> &mut Self {
+        self.scoped_req
+            .push(Arc::new(ScopedReqInterceptor::new(pattern, req_inter)));
+        self
+    }
+
+    /// Run `res_inter` only for the request paths matching `pattern`
+    ///
+    /// Scoped interceptors run after all the global ones, in push order.
+    pub fn push_scoped_res_inter(
+        &mut self,
+        pattern: PathPattern,
+        res_inter: Arc<dyn InterceptorRes>,
+    ) -> &mut Self {
+        self.scoped_res
+            .push(Arc::new(ScopedResInterceptor::new(pattern, res_inter)));
+        self
+    }
+
+    /// Push the interceptors of `stack` after the current ones
+    pub fn push_stack(&mut self, stack: &InterceptorStack) -> &mut Self {
+        self.interceptors_req
+            .extend(stack.interceptors_req().iter().cloned());
+        self.interceptors_res
+            .extend(stack.interceptors_res().iter().cloned());
+        self
+    }
+
+    /// Names of the request interceptors in chain order
+    pub fn req_inter_names(&self) -> Vec<&str> {
+        self.interceptors_req.iter().map(|i| i.name()).collect()
+    }
+
+    /// Names of the response interceptors in chain order
+    pub fn res_inter_names(&self) -> Vec<&str> {
+        self.interceptors_res.iter().map(|i| i.name()).collect()
+    }
+
+    /// Insert a request interceptor at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of request interceptors.
+    pub fn insert_req_inter(
+        &mut self,
+        index: usize,
+        req_inter: Arc<dyn InterceptorReq>,
+    ) -> &mut Self {
+        self.interceptors_req.insert(index, req_inter);
+        self
+    }
+
+    /// Insert a response interceptor at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of response interceptors.
+    pub fn insert_res_inter(
+        &mut self,
+        index: usize,
+        res_inter: Arc<dyn InterceptorRes>,
+    ) -> &mut Self {
+        self.interceptors_res.insert(index, res_inter);
+        self
+    }
+
+    /// Replace the first request interceptor called `name`, returning the old one
+    pub fn replace_req_inter(
+        &mut self,
+        name: &str,
+        req_inter: Arc<dyn InterceptorReq>,
+    ) -> Option<Arc<dyn InterceptorReq>> {
+        let index = self
+            .interceptors_req
+            .iter()
+            .position(|i| i.name() == name)?;
+        Some(std::mem::replace(
+            &mut self.interceptors_req[index],
+            req_inter,
+        ))
+    }
+
+    /// Replace the first response interceptor called `name`, returning the old one
+    pub fn replace_res_inter(
+        &mut self,
+        name: &str,
+        res_inter: Arc<dyn InterceptorRes>,
+    ) -> Option<Arc<dyn InterceptorRes>> {
+        let index = self
+            .interceptors_res
+            .iter()
+            .position(|i| i.name() == name)?;
+        Some(std::mem::replace(
+            &mut self.interceptors_res[index],
+            res_inter,
+        ))
+    }
+
+    /// Remove the first request interceptor called `name`
+    pub fn remove_req_inter(&mut self, name: &str) -> Option<Arc<dyn InterceptorReq>> {
+        let index = self
+            .interceptors_req
+            .iter()
+            .position(|i| i.name() == name)?;
+        Some(self.interceptors_req.remove(index))
+    }
+
+    /// Remove the first response interceptor called `name`
+    pub fn remove_res_inter(&mut self, name: &str) -> Option<Arc<dyn InterceptorRes>> {
+        let index = self
+            .interceptors_res
+            .iter()
+            .position(|i| i.name() == name)?;
+        Some(self.interceptors_res.remove(index))
+    }
+
     pub async fn run(&self) -> io::Result<()> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Follow the runtime settings of the server, until the tasks are dropped
+    fn subscribe_config(&self) -> JoinSet<()> {
+        let mut subscriptions = JoinSet::new();
+
+        let mut log_level = self.config.register(
+            LOG_LEVEL_KEY,
+            &log::max_level().as_str().to_lowercase(),
+            |v| v.parse::<LevelFilter>().is_ok(),
+        );
+        subscriptions.spawn(async move {
+            while log_level.changed().await.is_ok() {
+                if let Ok(level) = log_level.borrow_and_update().parse::<LevelFilter>() {
+                    log::set_max_level(level);
+                    info!("Log level -> {level}");
+                }
+            }
+        });
+
+        let max = self.clients.max_per_client();
+        let mut max_client_connections = self.config.register(
+            MAX_CLIENT_CONNECTIONS_KEY,
+            &max.map_or(String::from("none"), |m| m.to_string()),
+            |v| v == "none" || v.parse::<usize>().is_ok(),
+        );
+        let clients = self.clients.clone();
+        subscriptions.spawn(async move {
+            while max_client_connections.changed().await.is_ok() {
+                let max = max_client_connections.borrow_and_update().parse().ok();
+                clients.set_max_per_client(max);
+                info!("Max client connections -> {max:?}");
+            }
+        });
+
+        subscriptions
+    }
+
+    /// Run until `shutdown` resolves, then drain the connections
+    ///
+    /// No connection is accepted after `shutdown`, idle kept-alive ones are closed,
+    /// requests read meanwhile are answered with `503 Service Unavailable` and the
+    /// in-flight ones have the shutdown grace to finish.
+    pub async fn run_until<F: Future<Output = ()>>(&self, shutdown: F) -> io::Result<()> {
         debug!("Running in a debug mode...");
         debug!("Server chain: {self:?}");
 
         info!("bind -> {}", self.bind);
 
-        let listener = TcpListener::bind(&self.bind).await?;
+        let listener = self.listener.bind(&self.bind).await?;
+        let _subscriptions = self.subscribe_config();
+        let mut connections = JoinSet::new();
+        let (shutting_down, draining) = watch::channel(false);
+
+        tokio::pin!(shutdown);
+
         loop {
-            let (stream, socket) = listener.accept().await?;
+            let (stream, socket) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = &mut shutdown => break,
+            };
+
+            // Forget the finished connections
+            while connections.try_join_next().is_some() {}
+            self.listener.accepted(&stream);
 
             debug!("Connection from: {}:{}", socket.ip(), socket.port());
 
+            let log_sinks = self.log_sinks.clone();
+            let stats = self.stats.clone();
+            let connection = stats.connection();
+            let mut log = RequestLog::new(socket);
+            let local_addr = stream.local_addr().ok();
+
+            // Proxied clients are only known once their request is read, `Some(None)`
+            let client = if self.trusted_proxies.is_trusted(socket.ip()) {
+                Some(None)
+            } else {
+                self.clients.acquire(socket.ip()).map(Some)
+            };
+
+            let Some(client) = client else {
+                warn!("Too many connections from {}", socket.ip());
+                connections.spawn(async move {
+                    let _connection = connection;
+                    let (_, mut write_half) = stream.into_split();
+                    let mut response = Response::new(HttpStatus::TooManyRequests);
+                    response.add_header(("Connection", "close"));
+                    let _ = response.write_to(&mut write_half).await;
+
+                    log.finish(response.status(), 0);
+                    record_log(&log_sinks, &stats, &log).await;
+                });
+                continue;
+            };
+
             let handler = self.handler.clone();
-            let interceptor_req = self.interceptors_req.clone();
-            let interceptor_res = self.interceptors_res.clone();
+            let interceptor_req: Arc<[_]> = [&self.scoped_req[..], &self.interceptors_req]
+                .concat()
+                .into();
+            let interceptor_res: Arc<[_]> = [&self.interceptors_res[..], &self.scoped_res]
+                .concat()
+                .into();
+            let state = self.state.clone();
+            let head_timeout = self.head_timeout;
+            let max_uri_length = self.max_uri_length;
+            let min_body_rate = self.min_body_rate;
+            let max_body_size = self.max_body_size;
+            let streamed_body = self.streamed_body;
+            let request_timeout = self.request_timeout;
+            let error_handler = self.error_handler.clone();
+            let draining = draining.clone();
+            let clients = self.clients.clone();
+            let trusted_proxies = self.trusted_proxies.clone();
+
+            connections.spawn(async move {
+                // Hold the stats until the connection ends
+                let _connection = connection;
 
-            tokio::spawn(async move {
                 let (read_half, mut write_half) = stream.into_split();
-                let reader = BufReader::new(read_half);
+                let mut reader = BufReader::new(read_half);
+                let mut client = client;
+                let mut served = 0;
+
+                loop {
+                    // Idle until the next request, closed when draining or too long
+                    if served > 0 {
+                        let mut draining = draining.clone();
+                        let next = tokio::select! {
+                            next = timeout(head_timeout, reader.fill_buf()) => next,
+                            _ = draining.wait_for(|draining| *draining) => return,
+                        };
+                        if !matches!(next, Ok(Ok(buf)) if !buf.is_empty()) {
+                            return;
+                        }
+                        log = RequestLog::new(socket);
+                    }
+
+                    let read = async {
+                        let mut request = timeout(
+                            head_timeout,
+                            Request::read_head(&mut reader, max_uri_length),
+                        )
+                        .await??;
+
+                        if streamed_body.is_some_and(|min| request.content_length() >= min) {
+                            return Ok::<_, Error>(request);
+                        }
+
+                        if max_body_size.is_some_and(|max| request.content_length() > max) {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                ServerError::PayloadTooLarge,
+                            ));
+                        }
+
+                        match min_body_rate {
+                            Some(rate) => {
+                                let upload =
+                                    Duration::from_secs(request.content_length() / rate.max(1));
+                                timeout(head_timeout + upload, request.read_body(&mut reader))
+                                    .await??
+                            }
+                            None => request.read_body(&mut reader).await?,
+                        }
+
+                        Ok::<_, Error>(request)
+                    };
+
+                    let error = match read.await {
+                        Ok(req) => Ok(req),
+                        Err(e) if e.kind() == ErrorKind::TimedOut => {
+                            debug!("Client {socket} too slow sending the request");
+                            Err(ServerError::Timeout)
+                        }
+                        Err(e) if e.kind() == ErrorKind::InvalidData => {
+                            debug!("Client {socket} sent a malformed request: {e}");
+                            let message = e.to_string();
+                            match e.into_inner().map(|inner| inner.downcast::<ServerError>()) {
+                                Some(Ok(error)) => Err(*error),
+                                _ => Err(ServerError::BadRequest(message)),
+                            }
+                        }
+                        Err(e) => {
+                            error!("Server can't build the request! {e}");
+                            return;
+                        }
+                    };
+
+                    let mut request = match error {
+                        Ok(req) => req,
+                        Err(error) => {
+                            let mut response = Response::new(error.status());
+                            response.add_header(("Connection", "close"));
+                            let mut response =
+                                handle_error(&error_handler, None, &error, response).await;
+                            let _ = response.write_to(&mut write_half).await;
+
+                            log.finish(response.status(), response.body().len());
+                            record_log(&log_sinks, &stats, &log).await;
+                            return;
+                        }
+                    };
+
+                    let len = request.content_length();
+                    let keeps_alive = request.keeps_alive();
+                    let mut unread = Some(reader);
+                    if streamed_body.is_some_and(|min| len >= min) {
+                        let body = BodyStream::new(unread.take().unwrap(), len);
+                        request.body_stream = Some(Arc::new(std::sync::Mutex::new(Some(body))));
+                    }
 
-                let mut request: Request = match AsyncTryFrom::try_from(reader).await {
-                    Ok(req) => req,
-                    Err(_) => {
-                        error!("Server can't build the request!");
+                    // Sent the connection once the response switches protocols
+                    let upgraded = unread.as_ref().map(|_| {
+                        let (upgraded, on_upgrade) = OnUpgrade::new();
+                        request.upgrade = Some(Arc::new(std::sync::Mutex::new(Some(on_upgrade))));
+                        upgraded
+                    });
+
+                    request.extensions.extend(state.clone());
+                    let is_head = request.method == Method::Head;
+
+                    let peer = trusted_proxies.client_addr(
+                        socket,
+                        request.header("forwarded"),
+                        request.header("x-forwarded-for"),
+                    );
+                    request.peer_addr = Some(peer);
+                    request.remote_addr = Some(socket);
+                    request.local_addr = local_addr;
+                    log.peer = peer;
+
+                    log.method = Some(request.method);
+                    log.uri = Some(request.uri.clone());
+                    log.version = Some(request.version.clone());
+                    let version = request.version.clone();
+                    log.user_agent = request.header("user-agent").map(String::from);
+                    log.bytes_in = request.body.len() as u64;
+
+                    // Hold the client slot until the connection ends, the first
+                    // client of a trusted proxy connection has it
+                    if client.is_none() {
+                        client = clients.acquire(peer.ip());
+                    }
+                    if client.is_none() {
+                        warn!("Too many connections from {}", peer.ip());
+                        let mut response = Response::new(HttpStatus::TooManyRequests);
+                        response.add_header(("Connection", "close"));
+                        let _ = response.write_head_to(&mut write_half).await;
+
+                        log.finish(response.status(), 0);
+                        record_log(&log_sinks, &stats, &log).await;
                         return;
                     }
-                };
 
-                if !log_enabled!(log::Level::Debug) {
-                    info!("Request -> [{}] {}", request.method, request.uri);
-                }
+                    if *draining.borrow() {
+                        let error = ServerError::ShuttingDown;
+
+                        let mut response = Response::new(error.status());
+                        response.add_header(("Connection", "close"));
+                        response.add_header(("Retry-After", &RETRY_AFTER_SECS.to_string()));
+                        let mut response =
+                            handle_error(&error_handler, Some(&request), &error, response).await;
+
+                        let bytes_out = if is_head {
+                            let _ = response.write_head_to(&mut write_half).await;
+                            0
+                        } else {
+                            let _ = response.write_to(&mut write_half).await;
+                            response.body().len()
+                        };
+
+                        log.finish(response.status(), bytes_out);
+                        record_log(&log_sinks, &stats, &log).await;
+                        return;
+                    }
+
+                    if !log_enabled!(log::Level::Debug) {
+                        info!("Request -> [{}] {}", request.method, request.uri);
+                    }
 
-                debug!("Request -> {request:?}");
+                    debug!("Request -> {request:?}");
+
+                    #[cfg(feature = "otel")]
+                    let otel_cx = otel::request_context(&request, socket);
+
+                    // Interceptors take the request, keep its head to report their errors
+                    let head = error_handler.as_ref().map(|_| request.head());
+
+                    let solve = {
+                        let handler = handler.clone();
+                        let interceptor_req = interceptor_req.clone();
+                        let interceptor_res = interceptor_res.clone();
+                        let error_handler = error_handler.clone();
+                        let head = head.as_ref().map(Request::head);
+
+                        async move {
+                            // Run interceptors_req
+                            for interceptor in interceptor_req.iter() {
+                                match interceptor.chain_req(request).await {
+                                    ControlFlow::Continue(r) => request = r,
+                                    ControlFlow::Break(res) if res.status().code() >= 400 => {
+                                        let error = ServerError::Status(res.status());
+                                        let res = handle_error(
+                                            &error_handler,
+                                            head.as_ref(),
+                                            &error,
+                                            res,
+                                        )
+                                        .await;
+                                        return (res, None);
+                                    }
+                                    ControlFlow::Break(res) => return (res, None),
+                                }
+                            }
+
+                            // Run handler
+                            let mut response = match handler.solve_request(&request).await {
+                                Ok(res) => res,
+                                Err(msg) => {
+                                    error!("{msg}");
+                                    let error = ServerError::Handler(msg);
+                                    let response = Response::new(error.status());
+                                    handle_error(&error_handler, Some(&request), &error, response)
+                                        .await
+                                }
+                            };
+
+                            // Run interceptors_req
+                            for interceptor in interceptor_res.iter() {
+                                response = interceptor.chain_res(&request, response).await;
+                            }
+
+                            let route = request.extensions.get::<MatchedRoute>().cloned();
+                            (response, route)
+                        }
+                    };
+
+                    #[cfg(feature = "otel")]
+                    let solve =
+                        opentelemetry::trace::FutureExt::with_context(solve, otel_cx.clone());
 
-                // Run interceptors_req
-                for interceptor in &interceptor_req {
-                    match interceptor.chain_req(request).await {
-                        ControlFlow::Continue(r) => request = r,
-                        ControlFlow::Break(res) => {
-                            write_half.write_all(&res.as_bytes()).await.unwrap();
+                    // Own task to catch the panics of handlers and interceptors
+                    let mut solving = tokio::spawn(solve);
+
+                    let deadline = async {
+                        match request_timeout {
+                            Some(request_timeout) => tokio::time::sleep(request_timeout).await,
+                            None => std::future::pending().await,
+                        }
+                    };
+
+                    // Drop the pending work as soon as the client leaves or time runs out
+                    let solved = tokio::select! {
+                        solved = &mut solving => Some(solved),
+                        _ = deadline => {
+                            solving.abort();
+                            None
+                        }
+                        _ = disconnected(write_half.as_ref()) => {
+                            solving.abort();
+                            debug!("Client {socket} disconnected, request cancelled");
                             return;
                         }
+                    };
+
+                    let (mut response, route) = match solved {
+                        Some(Ok(solved)) => solved,
+                        None => {
+                            warn!("Request from {socket} cancelled, it took too long");
+                            let error = ServerError::HandlerTimeout;
+
+                            let mut response = Response::new(error.status());
+                            response.add_header(("Retry-After", &RETRY_AFTER_SECS.to_string()));
+                            let response =
+                                handle_error(&error_handler, head.as_ref(), &error, response).await;
+                            (response, None)
+                        }
+                        Some(Err(e)) if e.is_panic() => {
+                            let error = ServerError::Panic(panic_message(e.into_panic()));
+                            error!("Request solving panicked: {error:?}");
+
+                            let response = Response::new(error.status());
+                            let response =
+                                handle_error(&error_handler, head.as_ref(), &error, response).await;
+                            (response, None)
+                        }
+                        Some(Err(_)) => return,
+                    };
+
+                    debug!("Response -> {response:?}");
+
+                    #[cfg(feature = "otel")]
+                    otel::end_request(&otel_cx, &response);
+
+                    if response.is_reset() {
+                        debug!("Resetting the connection of {socket}");
+                        // Without the shutdown of a dropped half, closing sends the reset
+                        let _ = write_half.as_ref().set_zero_linger();
+                        write_half.forget();
+                        return;
+                    }
+
+                    // Another request can follow once the body is framed and read
+                    let is_framed = is_head
+                        || response.content_length().is_some()
+                        || matches!(response.status().code(), 100..=199 | 204 | 304);
+                    let is_closed_by_handler = response
+                        .header("connection")
+                        .is_some_and(|options| has_option(options, "close"));
+                    let keeps_alive = keeps_alive
+                        && unread.is_some()
+                        && is_framed
+                        && !is_closed_by_handler
+                        && !*draining.borrow();
+
+                    match response.status() {
+                        HttpStatus::SwitchingProtocols => {}
+                        _ if !keeps_alive => response.add_header(("Connection", "close")),
+                        _ if version == "http/1.0" => {
+                            response.add_header(("Connection", "keep-alive"))
+                        }
+                        _ => {}
                     }
-                }
 
-                // Run handler
-                let mut response = match handler.solve_request(&request).await {
-                    Ok(res) => res,
-                    Err(msg) => {
-                        error!("{msg}");
-                        Response::new(HttpStatus::InternalServerError)
+                    let written = if is_head {
+                        response.write_head_to(&mut write_half).await
+                    } else {
+                        response.write_to(&mut write_half).await
+                    };
+
+                    if let Err(e) = written {
+                        debug!("Client {socket} disconnected, response not sent: {e}");
+                        return;
+                    }
+
+                    let bytes_out = if is_head { 0 } else { response.body().len() };
+                    log.route = route.map(|r| r.0);
+                    log.finish(response.status(), bytes_out);
+                    record_log(&log_sinks, &stats, &log).await;
+                    if served > 0 {
+                        stats.kept_alive();
                     }
-                };
 
-                // Run interceptors_req
-                for interceptor in &interceptor_res {
-                    response = interceptor.chain_res(&request, response).await;
+                    match (response.status(), unread, upgraded) {
+                        (HttpStatus::SwitchingProtocols, Some(reader), Some(upgraded)) => {
+                            debug!("Connection of {socket} upgraded");
+                            let _ = upgraded.send(Upgraded::new(reader, write_half));
+                            return;
+                        }
+                        (_, Some(next), _) if keeps_alive => {
+                            reader = next;
+                            served += 1;
+                        }
+                        _ => return,
+                    }
                 }
+            });
+        }
 
-                debug!("Response -> {response:?}");
+        drop(listener);
+        shutting_down.send_replace(true);
+        info!(
+            "Shutting down, draining {} connections for {:?}",
+            connections.len(),
+            self.shutdown_grace
+        );
 
-                write_half.write_all(&response.as_bytes()).await.unwrap();
-            });
+        let drain = async { while connections.join_next().await.is_some() {} };
+        if timeout(self.shutdown_grace, drain).await.is_err() {
+            warn!("Closing {} connections after the grace", connections.len());
+            connections.shutdown().await;
         }
+
+        Ok(())
+    }
+}
+
+async fn handle_error(
+    error_handler: &Option<Arc<dyn ErrorHandler>>,
+    request: Option<&Request>,
+    error: &ServerError,
+    response: Response,
+) -> Response {
+    match error_handler {
+        Some(error_handler) => error_handler.handle_error(request, error, response).await,
+        None => response,
+    }
+}
+
+/// `Allow` header value
+fn allow_header(methods: &[Method]) -> String {
+    methods
+        .iter()
+        .map(Method::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<&'static str>() {
+        Ok(msg) => msg.to_string(),
+        Err(payload) => match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(_) => String::from("Box<dyn Any>"),
+        },
     }
 }
 
+async fn record_log(log_sinks: &[Arc<dyn LogSink>], stats: &ServerStats, log: &RequestLog) {
+    if log.method.is_some() {
+        stats.request();
+    }
+    stats.served(log.bytes_out);
+
+    for sink in log_sinks {
+        sink.record(log).await;
+    }
+}
+
+/// Whether the `Connection` header value lists `option`
+fn has_option(connection: &str, option: &str) -> bool {
+    connection
+        .split(',')
+        .any(|listed| listed.trim().eq_ignore_ascii_case(option))
+}
+
+/// Resolves once the client resets the connection
+///
+/// A closed side, read as end of file, may still wait for the response, like
+/// after `shutdown(SHUT_WR)`. Clients gone for good are caught writing it.
+async fn disconnected(stream: &TcpStream) {
+    let mut buf = [0; 1];
+
+    match stream.peek(&mut buf).await {
+        Err(e) if is_reset(&e) => {}
+        // Pipelined bytes, half-closed clients or other errors
+        _ => std::future::pending().await,
+    }
+}
+
+fn is_reset(error: &Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe
+    )
+}
+
 impl<H: Named> fmt::Debug for Server<H> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let int_req = self
@@ -451,3 +2040,303 @@ impl<H: Named> fmt::Debug for Server<H> {
         write!(f, "{int_req} -> [{}] -> {int_res}", self.handler.name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    async fn parse(raw: &str) -> io::Result<Request> {
+        Request::read_head(&mut raw.as_bytes(), DEFAULT_MAX_URI_LENGTH).await
+    }
+
+    #[tokio::test]
+    async fn refuses_missing_or_invalid_host() {
+        for raw in [
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: user@example.com\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: example.com/private\r\n\r\n",
+            "GET nowhere HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        ] {
+            let error = parse(raw).await.unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{raw:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_hosts() {
+        for raw in [
+            "GET /a?b=c HTTP/1.1\r\nHost: example.com:8080\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: [::1]:4444\r\n\r\n",
+            "OPTIONS * HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "GET / HTTP/1.0\r\n\r\n",
+        ] {
+            let request = parse(raw).await.unwrap();
+            request.url();
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_headers_with_optional_whitespace() {
+        let request = parse(
+            "POST / HTTP/1.1\r\nHost:x\r\nContent-Length:5\r\nAccept: a\r\nACCEPT:\tb \r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(request.header("host"), Some("x"));
+        assert_eq!(request.content_length(), 5);
+        assert_eq!(request.header("accept"), Some("a, b"));
+    }
+
+    #[tokio::test]
+    async fn refuses_ambiguous_headers() {
+        for raw in [
+            "GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nX-Folded: a\r\n b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nNo colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nContent-Length :5\r\n\r\n",
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length:5\r\nTransfer-Encoding:chunked\r\n\r\n",
+        ] {
+            let error = parse(raw).await.unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{raw:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_transfer_codings() {
+        let raw = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding:chunked\r\n\r\n";
+        let error = parse(raw).await.unwrap_err();
+
+        let error = error
+            .into_inner()
+            .unwrap()
+            .downcast::<ServerError>()
+            .unwrap();
+        assert_eq!(error.status(), HttpStatus::NotImplemented);
+    }
+
+    #[test]
+    fn url_without_host() {
+        let request = Request::new(Method::Get, String::from("/a b"), String::from("http/1.1"));
+        assert_eq!(request.url().as_str(), "http://localhost/a%20b");
+
+        let request = Request::new(Method::Get, String::from("*"), String::from("http/1.1"));
+        assert_eq!(request.url().as_str(), "http://localhost/");
+    }
+
+    struct Empty;
+
+    impl Named for Empty {}
+
+    #[async_trait]
+    impl HttpHandler for Empty {
+        async fn solve_request(&self, _request: &Request) -> Result<Response, &'static str> {
+            Ok(Response::new(HttpStatus::Ok))
+        }
+    }
+
+    /// Head of the next response, its body is empty
+    async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> String {
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            assert_ne!(reader.read_line(&mut head).await.unwrap(), 0, "{head}");
+        }
+        head
+    }
+
+    #[tokio::test]
+    async fn keeps_connections_alive_until_shutdown() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut server = Server::new(addr.to_string(), Empty);
+        server.with_shutdown_grace(Duration::from_secs(30));
+        let stats = server.stats();
+        let (shutdown, stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+
+        let stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let mut stream = BufReader::new(stream);
+
+        for _ in 0..2 {
+            let request = "GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+            stream
+                .get_mut()
+                .write_all(request.as_bytes())
+                .await
+                .unwrap();
+            let head = read_head(&mut stream).await;
+            assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+            assert!(!head.contains("connection: close"), "{head}");
+        }
+
+        // Idle connections are closed, not waited for during the grace
+        shutdown.send(()).unwrap();
+        let mut rest = Vec::new();
+        timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(rest.is_empty());
+        timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let stats = stats.snapshot();
+        assert_eq!((stats.total_requests, stats.keep_alive_requests), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn closes_http_1_0_connections_unless_asked() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = Server::new(addr.to_string(), Empty);
+        let task = tokio::spawn(async move { server.run().await });
+
+        let cases = [
+            ("GET / HTTP/1.0\r\n\r\n", "connection: close"),
+            (
+                "GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+                "connection: close",
+            ),
+            (
+                "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+                "connection: keep-alive",
+            ),
+        ];
+        for (request, connection) in cases {
+            let stream = loop {
+                match TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let mut stream = BufReader::new(stream);
+            stream
+                .get_mut()
+                .write_all(request.as_bytes())
+                .await
+                .unwrap();
+
+            let head = read_head(&mut stream).await;
+            assert!(head.contains(connection), "{request:?}: {head}");
+            if connection == "connection: close" {
+                let mut rest = Vec::new();
+                timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+                    .await
+                    .unwrap()
+                    .unwrap();
+            }
+        }
+
+        task.abort();
+    }
+
+    /// Answers after a while, unless cancelled
+    struct Slow(Arc<AtomicBool>);
+
+    impl Named for Slow {}
+
+    #[async_trait]
+    impl HttpHandler for Slow {
+        async fn solve_request(&self, _request: &Request) -> Result<Response, &'static str> {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            self.0.store(true, Ordering::Relaxed);
+            Ok(Response::new(HttpStatus::Ok))
+        }
+    }
+
+    #[tokio::test]
+    async fn cancels_requests_of_reset_connections_only() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let solved = Arc::new(AtomicBool::new(false));
+        let server = Server::new(addr.to_string(), Slow(solved.clone()));
+        let task = tokio::spawn(async move { server.run().await });
+
+        let connect = || async {
+            loop {
+                match TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        };
+        let request = b"GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+
+        // Half-closed, still waiting for the response
+        let mut stream = connect().await;
+        stream.write_all(request).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 200"));
+
+        // Reset, nobody to answer
+        solved.store(false, Ordering::Relaxed);
+        let mut stream = connect().await;
+        stream.write_all(request).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.set_zero_linger().unwrap();
+        drop(stream);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!solved.load(Ordering::Relaxed));
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn refuses_large_bodies_before_reading_them() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut server = Server::new(addr.to_string(), Empty);
+        server.with_max_body_size(Some(16));
+        let task = tokio::spawn(async move { server.run().await });
+
+        let mut stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+
+        // The body is never sent, the answer can't wait for it
+        let head = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 100000000000\r\n\r\n";
+        stream.write_all(head.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 413"));
+
+        task.abort();
+    }
+}
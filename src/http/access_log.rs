@@ -0,0 +1,198 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate};
+use log::warn;
+
+use super::{LogSink, Named, RequestLog};
+
+/// Rotated files kept by default
+pub const DEFAULT_ACCESS_LOG_RETENTION: usize = 7;
+
+/// When the access log file is rotated
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Never,
+    /// Once the file reaches this size in bytes
+    Size(u64),
+    /// On the first record of each day, local time
+    Daily,
+}
+
+impl FromStr for Rotation {
+    type Err = &'static str;
+
+    /// `never`, `daily` or a size like `500K`, `10M` or `1G`
+    ///
+    /// ```
+    /// use httpr::http::access_log::Rotation;
+    ///
+    /// assert_eq!("daily".parse(), Ok(Rotation::Daily));
+    /// assert_eq!("10M".parse(), Ok(Rotation::Size(10 * 1024 * 1024)));
+    /// assert_eq!("4096".parse(), Ok(Rotation::Size(4096)));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "expected never, daily or a size like 10M";
+
+        match s.to_lowercase().as_str() {
+            "never" => Ok(Rotation::Never),
+            "daily" => Ok(Rotation::Daily),
+            size => {
+                let (digits, unit) = match size.char_indices().last() {
+                    Some((i, 'k')) => (&size[..i], 1024),
+                    Some((i, 'm')) => (&size[..i], 1024 * 1024),
+                    Some((i, 'g')) => (&size[..i], 1024 * 1024 * 1024),
+                    _ => (size, 1),
+                };
+
+                match digits.parse::<u64>() {
+                    Ok(n) if n > 0 => Ok(Rotation::Size(n * unit)),
+                    _ => Err(invalid),
+                }
+            }
+        }
+    }
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+    opened: NaiveDate,
+}
+
+/// Access log in Common Log Format written to its own file
+///
+/// Rotated files get a numeric suffix, `access.log.1` being the newest, and the
+/// ones beyond the retention are deleted.
+pub struct AccessLog {
+    path: PathBuf,
+    rotation: Rotation,
+    retention: usize,
+    current: Mutex<LogFile>,
+}
+
+impl AccessLog {
+    /// Append to the file at `path`, creating it if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let current = Mutex::new(Self::open_file(&path)?);
+
+        Ok(Self {
+            path,
+            rotation: Rotation::Never,
+            retention: DEFAULT_ACCESS_LOG_RETENTION,
+            current,
+        })
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Rotated files to keep, older ones are deleted
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    fn write_line(&self, line: &str) {
+        let now = Local::now();
+        let mut current = self.current.lock().unwrap();
+
+        let rotate = match self.rotation {
+            Rotation::Never => false,
+            Rotation::Size(max) => current.size > 0 && current.size + line.len() as u64 > max,
+            Rotation::Daily => current.opened != now.date_naive(),
+        };
+
+        if rotate {
+            match self.rotate() {
+                Ok(file) => *current = file,
+                Err(e) => warn!("Failed rotating {:?}: {e}", self.path),
+            }
+        }
+
+        match current.file.write_all(line.as_bytes()) {
+            Ok(()) => current.size += line.len() as u64,
+            Err(e) => warn!("Failed writing {:?}: {e}", self.path),
+        }
+    }
+
+    /// Shift the rotated files, move the current one to `.1` and reopen it
+    fn rotate(&self) -> io::Result<LogFile> {
+        if self.retention == 0 {
+            fs::remove_file(&self.path)?;
+            return Self::open_file(&self.path);
+        }
+
+        let _ = fs::remove_file(self.rotated_path(self.retention));
+        for n in (1..self.retention).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(from, self.rotated_path(n + 1))?;
+            }
+        }
+
+        fs::rename(&self.path, self.rotated_path(1))?;
+        Self::open_file(&self.path)
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(format!(".{n}"));
+        PathBuf::from(path)
+    }
+
+    fn open_file(path: &Path) -> io::Result<LogFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+
+        let opened = metadata
+            .modified()
+            .map(|m| chrono::DateTime::<Local>::from(m).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+
+        Ok(LogFile {
+            file,
+            size: metadata.len(),
+            opened,
+        })
+    }
+}
+
+impl Named for AccessLog {}
+
+#[async_trait]
+impl LogSink for AccessLog {
+    async fn record(&self, log: &RequestLog) {
+        let line = format!(
+            "{} - - [{}] \"{}\" {} {}\n",
+            log.peer.ip(),
+            log.started.format("%d/%b/%Y:%H:%M:%S %z"),
+            log.request_line(),
+            log.status.code(),
+            log.bytes_out,
+        );
+
+        self.write_line(&line);
+    }
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("path", &self.path)
+            .field("rotation", &self.rotation)
+            .field("retention", &self.retention)
+            .finish()
+    }
+}
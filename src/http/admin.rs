@@ -0,0 +1,226 @@
+//! Runtime configuration registry and its admin endpoint
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+use super::{auth::constant_time_eq, HttpStatus, InterceptorReq, Method, Named, Request, Response};
+
+/// Log level of the server, any `log::LevelFilter` like `info` or `debug`
+///
+/// Levels above the filter of the installed logger have no effect.
+pub const LOG_LEVEL_KEY: &str = "log_level";
+/// Simultaneous connections of each client address, a number or `none`
+pub const MAX_CLIENT_CONNECTIONS_KEY: &str = "max_client_connections";
+
+struct Setting {
+    value: watch::Sender<String>,
+    validate: fn(&str) -> bool,
+}
+
+/// Settings changeable at runtime, their users subscribe to be told of the changes
+#[derive(Default)]
+pub struct ConfigRegistry {
+    settings: Mutex<HashMap<String, Setting>>,
+}
+
+impl ConfigRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the setting `key` and subscribe to it
+    ///
+    /// `validate` rejects the values it returns `false` for. A key already registered
+    /// keeps its current value and validation.
+    pub fn register(
+        &self,
+        key: &str,
+        initial: &str,
+        validate: fn(&str) -> bool,
+    ) -> watch::Receiver<String> {
+        let mut settings = self.settings.lock().unwrap();
+
+        settings
+            .entry(key.to_string())
+            .or_insert_with(|| Setting {
+                value: watch::Sender::new(initial.to_string()),
+                validate,
+            })
+            .value
+            .subscribe()
+    }
+
+    pub fn subscribe(&self, key: &str) -> Option<watch::Receiver<String>> {
+        let settings = self.settings.lock().unwrap();
+        settings.get(key).map(|s| s.value.subscribe())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let settings = self.settings.lock().unwrap();
+        settings.get(key).map(|s| s.value.borrow().clone())
+    }
+
+    /// Change `key` and notify its subscribers
+    pub fn set(&self, key: &str, value: &str) -> Result<(), &'static str> {
+        let settings = self.settings.lock().unwrap();
+        let setting = settings.get(key).ok_or("Unknown setting")?;
+
+        if !(setting.validate)(value) {
+            return Err("Invalid value");
+        }
+
+        setting.value.send_replace(value.to_string());
+        Ok(())
+    }
+
+    /// Current value of every setting
+    pub fn values(&self) -> BTreeMap<String, String> {
+        let settings = self.settings.lock().unwrap();
+        settings
+            .iter()
+            .map(|(k, s)| (k.clone(), s.value.borrow().clone()))
+            .collect()
+    }
+}
+
+impl fmt::Debug for ConfigRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.values()).finish()
+    }
+}
+
+/// Admin API of a [ConfigRegistry] on `path`, behind a bearer token
+///
+/// `GET <path>` lists the settings as JSON and `PUT <path>/<key>` changes one with
+/// the request body as value.
+pub struct AdminReqInterceptor {
+    path: String,
+    token: String,
+    registry: Arc<ConfigRegistry>,
+}
+
+impl AdminReqInterceptor {
+    /// `token` can't be empty, requests without one would be let in
+    pub fn new(
+        path: &str,
+        token: &str,
+        registry: Arc<ConfigRegistry>,
+    ) -> Result<Self, &'static str> {
+        if token.trim().is_empty() {
+            return Err("Empty admin token!");
+        }
+
+        Ok(Self {
+            path: path.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            registry,
+        })
+    }
+
+    fn is_authorized(&self, request: &Request) -> bool {
+        let token = request
+            .header("authorization")
+            .and_then(|auth| auth.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        constant_time_eq(token.as_bytes(), self.token.as_bytes())
+    }
+
+    fn solve(&self, request: &Request, key: Option<&str>) -> Response {
+        match (request.method(), key) {
+            (Method::Get, None) => {
+                let mut response = Response::json(&self.registry.values());
+                response.add_header(("Cache-Control", "no-store"));
+                response
+            }
+            (Method::Get, Some(key)) => match self.registry.get(key) {
+                Some(value) => {
+                    let mut response = Response::text(&value);
+                    response.add_header(("Cache-Control", "no-store"));
+                    response
+                }
+                None => Response::new(HttpStatus::NotFound),
+            },
+            (Method::Put, Some(key)) => {
+                let Ok(value) = request.body_string() else {
+                    return Response::new(HttpStatus::BadRequest);
+                };
+
+                match self.registry.set(key, value.trim()) {
+                    Ok(()) => Response::no_content(),
+                    Err(_) if self.registry.get(key).is_none() => {
+                        Response::new(HttpStatus::NotFound)
+                    }
+                    Err(_) => Response::new(HttpStatus::BadRequest),
+                }
+            }
+            _ => Response::new(HttpStatus::MethodNotAllowed),
+        }
+    }
+}
+
+impl Named for AdminReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for AdminReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let path = request.uri.split('?').next().unwrap_or_default();
+
+        let key = match path.strip_prefix(&self.path) {
+            Some("" | "/") => None,
+            Some(key) if key.starts_with('/') => Some(key[1..].to_string()),
+            _ => return ControlFlow::Continue(request),
+        };
+
+        if !self.is_authorized(&request) {
+            let mut response = Response::new(HttpStatus::Unauthorized);
+            response.add_header(("WWW-Authenticate", "Bearer"));
+            return ControlFlow::Break(response);
+        }
+
+        ControlFlow::Break(self.solve(&request, key.as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request {
+        let mut request = Request::new(
+            Method::Get,
+            String::from("/admin"),
+            String::from("http/1.1"),
+        );
+        if let Some(authorization) = authorization {
+            request
+                .headers
+                .insert(String::from("authorization"), authorization.to_string());
+        }
+        request
+    }
+
+    #[test]
+    fn refuses_empty_tokens() {
+        let registry = Arc::new(ConfigRegistry::new());
+        for token in ["", " "] {
+            assert!(AdminReqInterceptor::new("/admin", token, registry.clone()).is_err());
+        }
+
+        let admin = AdminReqInterceptor::new("/admin", "secret", registry).unwrap();
+        assert!(admin.is_authorized(&request(Some("Bearer secret"))));
+        for authorization in [None, Some("Bearer "), Some("Bearer"), Some("")] {
+            assert!(
+                !admin.is_authorized(&request(authorization)),
+                "{authorization:?}"
+            );
+        }
+    }
+}
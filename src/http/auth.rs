@@ -0,0 +1,363 @@
+//! HTTP Basic authentication
+
+use std::{
+    collections::HashMap,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use percent_encoding::percent_decode_str;
+use tokio::fs::try_exists;
+
+use super::{HttpStatus, InterceptorReq, Method, Named, Request, Response};
+use crate::static_server::url_path::normalize_path;
+
+pub mod api_key;
+pub mod htpasswd;
+
+/// Source of the users a [BasicAuthReqInterceptor] lets in
+pub trait Credentials: Send + Sync {
+    fn verify(&self, user: &str, password: &str) -> bool;
+}
+
+/// Plain text passwords by user
+impl Credentials for HashMap<String, String> {
+    fn verify(&self, user: &str, password: &str) -> bool {
+        self.get(user)
+            .is_some_and(|expected| constant_time_eq(password.as_bytes(), expected.as_bytes()))
+    }
+}
+
+/// User name of an authenticated request, in its extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser(pub String);
+
+/// Request paths under authentication
+///
+/// Every path is protected unless narrowed down to path prefixes or to the
+/// directories holding a marker file. Handlers listing files on their own, like
+/// archives, use it to leave the protected ones out of anonymous requests.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedPaths {
+    prefixes: Vec<String>,
+    marker: Option<(PathBuf, String)>,
+}
+
+impl ProtectedPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Protect the request paths under `prefix`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefixes.push(format!("/{}", prefix.trim_matches('/')));
+        self
+    }
+
+    /// Protect the request paths under the directories of `root` holding `file_name`
+    pub fn with_marker<P: Into<PathBuf>>(mut self, root: P, file_name: &str) -> Self {
+        self.marker = Some((root.into(), file_name.to_string()));
+        self
+    }
+
+    /// Whether the file name `name` is the marker one
+    pub fn is_marker(&self, name: &str) -> bool {
+        self.marker
+            .as_ref()
+            .is_some_and(|(_, marker)| marker == name)
+    }
+
+    /// Whether the decoded request path `path` needs authentication
+    ///
+    /// It is normalized like the served files are looked up, the invalid ones are
+    /// always protected.
+    pub async fn is_protected(&self, path: &str) -> bool {
+        let Some(path) = normalize_path(path) else {
+            return true;
+        };
+
+        if self.prefixes.is_empty() && self.marker.is_none() {
+            return true;
+        }
+
+        let in_prefix = self.prefixes.iter().any(|prefix| {
+            prefix == "/"
+                || path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+
+        if in_prefix {
+            return true;
+        }
+
+        let Some((root, marker)) = &self.marker else {
+            return false;
+        };
+
+        // The root and every directory down to the requested path
+        let mut dir = root.clone();
+        for segment in Path::new(&path).iter().skip(1) {
+            if try_exists(dir.join(marker)).await.unwrap_or(false) {
+                return true;
+            }
+            dir.push(segment);
+        }
+
+        try_exists(dir.join(marker)).await.unwrap_or(false)
+    }
+}
+
+/// Require Basic credentials of a known user
+///
+/// Every request is protected unless the protection is narrowed down to path
+/// prefixes or to the directories holding a marker file. Requests changing
+/// things, with methods other than `GET`, `HEAD` and `OPTIONS`, are always
+/// protected, and the `.ht*` files, like `.htpasswd`, are never served.
+pub struct BasicAuthReqInterceptor {
+    realm: String,
+    users: HashMap<String, String>,
+    credentials: Vec<Arc<dyn Credentials>>,
+    paths: ProtectedPaths,
+    internal_routes: Vec<String>,
+}
+
+impl BasicAuthReqInterceptor {
+    pub fn new(realm: &str) -> Self {
+        Self {
+            realm: realm.replace('"', ""),
+            users: HashMap::new(),
+            credentials: Vec::new(),
+            paths: ProtectedPaths::default(),
+            internal_routes: Vec::new(),
+        }
+    }
+
+    pub fn with_user(mut self, user: &str, password: &str) -> Self {
+        self.users.insert(user.to_string(), password.to_string());
+        self
+    }
+
+    /// Also let in the users of `credentials`, like an [Htpasswd](htpasswd::Htpasswd)
+    pub fn with_credentials(mut self, credentials: Arc<dyn Credentials>) -> Self {
+        self.credentials.push(credentials);
+        self
+    }
+
+    /// Protect the request paths under `prefix`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.paths = self.paths.with_prefix(prefix);
+        self
+    }
+
+    /// Protect the request paths under the directories of `root` holding `file_name`
+    ///
+    /// The marker files themselves are never served.
+    pub fn with_marker<P: Into<PathBuf>>(mut self, root: P, file_name: &str) -> Self {
+        self.paths = self.paths.with_marker(root, file_name);
+        self
+    }
+
+    /// Protect the request paths of `paths`, replacing the prefixes and marker set
+    pub fn with_protected_paths(mut self, paths: ProtectedPaths) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Protect the paths under `route` like the request path following it
+    ///
+    /// For the internal routes serving something of a file, like its thumbnail
+    /// at `/__internal/thumbnail/private/a.png` for `/private/a.png`.
+    pub fn with_internal_route(mut self, route: &str) -> Self {
+        self.internal_routes
+            .push(format!("/{}", route.trim_matches('/')));
+        self
+    }
+
+    pub fn protected_paths(&self) -> &ProtectedPaths {
+        &self.paths
+    }
+
+    fn authenticate(&self, request: &Request) -> Option<String> {
+        let (user, password) = basic_credentials(request)?;
+
+        let is_valid = self.users.verify(&user, &password)
+            || self.credentials.iter().any(|c| c.verify(&user, &password));
+
+        is_valid.then_some(user)
+    }
+
+    async fn is_protected(&self, request: &Request, path: &str) -> bool {
+        if !matches!(
+            request.method(),
+            Method::Get | Method::Head | Method::Options
+        ) {
+            return true;
+        }
+
+        // Internal routes are protected as the file they are about
+        let path = self
+            .internal_routes
+            .iter()
+            .find_map(|route| {
+                path.strip_prefix(route.as_str())
+                    .filter(|rest| rest.starts_with('/'))
+            })
+            .unwrap_or(path);
+
+        self.paths.is_protected(path).await
+    }
+
+    fn challenge(&self) -> Response {
+        let mut response = Response::new(HttpStatus::Unauthorized);
+        response.add_header((
+            "WWW-Authenticate",
+            &format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm),
+        ));
+        response
+    }
+}
+
+impl Named for BasicAuthReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for BasicAuthReqInterceptor {
+    async fn chain_req(&self, mut request: Request) -> ControlFlow<Response, Request> {
+        let url = request.url();
+        let decoded = percent_decode_str(url.path()).decode_utf8_lossy();
+        let path = normalize_path(&decoded).unwrap_or_else(|| decoded.into_owned());
+
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if self.paths.is_marker(name) || name.starts_with(".ht") {
+            return ControlFlow::Break(Response::new(HttpStatus::NotFound));
+        }
+
+        // Known users are recognized anywhere, like for their archives of public paths
+        if let Some(user) = self.authenticate(&request) {
+            request.extensions_mut().insert(AuthUser(user));
+            return ControlFlow::Continue(request);
+        }
+
+        match self.is_protected(&request, &path).await {
+            true => ControlFlow::Break(self.challenge()),
+            false => ControlFlow::Continue(request),
+        }
+    }
+}
+
+/// User and password of a `Basic` `Authorization` header
+pub fn basic_credentials(request: &Request) -> Option<(String, String)> {
+    let encoded = request.header("authorization")?.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+
+    Some((user.to_string(), password.to_string()))
+}
+
+/// Compare every byte to not leak how much of a secret matched
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, uri: &str) -> Request {
+        Request::new(method, uri.to_string(), String::from("http/1.1"))
+    }
+
+    fn status(flow: ControlFlow<Response, Request>) -> Option<HttpStatus> {
+        match flow {
+            ControlFlow::Break(response) => Some(response.status()),
+            ControlFlow::Continue(_) => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn anonymous_changes_need_credentials() {
+        let auth = BasicAuthReqInterceptor::new("test")
+            .with_user("user", "secret")
+            .with_prefix("private");
+
+        let flow = auth
+            .chain_req(request(Method::Delete, "/private/a.txt"))
+            .await;
+        assert_eq!(status(flow), Some(HttpStatus::Unauthorized));
+
+        let flow = auth
+            .chain_req(request(Method::Post, "/__internal/manage"))
+            .await;
+        assert_eq!(status(flow), Some(HttpStatus::Unauthorized));
+
+        let flow = auth.chain_req(request(Method::Get, "/public/a.txt")).await;
+        assert_eq!(status(flow), None);
+    }
+
+    #[tokio::test]
+    async fn internal_routes_are_protected_as_their_file() {
+        let auth = BasicAuthReqInterceptor::new("test")
+            .with_user("user", "secret")
+            .with_prefix("private")
+            .with_internal_route("/__internal/thumbnail");
+
+        let flow = auth
+            .chain_req(request(Method::Get, "/__internal/thumbnail/private/a.png"))
+            .await;
+        assert_eq!(status(flow), Some(HttpStatus::Unauthorized));
+
+        let flow = auth
+            .chain_req(request(Method::Get, "/__internal/thumbnail/public/a.png"))
+            .await;
+        assert_eq!(status(flow), None);
+    }
+
+    #[tokio::test]
+    async fn protects_unnormalized_paths() {
+        let root = std::env::temp_dir().join(format!("httpr-auth-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("marked")).unwrap();
+        std::fs::write(root.join("marked").join(".private"), "").unwrap();
+
+        let auth = BasicAuthReqInterceptor::new("test")
+            .with_user("user", "secret")
+            .with_prefix("private")
+            .with_marker(&root, ".private");
+
+        for uri in [
+            "//private/a.txt",
+            "/./private/a.txt",
+            "/%2F/private/a.txt",
+            "//marked/a.txt",
+            "/./marked//a.txt",
+        ] {
+            let flow = auth.chain_req(request(Method::Get, uri)).await;
+            assert_eq!(status(flow), Some(HttpStatus::Unauthorized), "{uri}");
+        }
+
+        let flow = auth.chain_req(request(Method::Get, "//public/a.txt")).await;
+        assert_eq!(status(flow), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn never_serves_ht_files() {
+        let auth = BasicAuthReqInterceptor::new("test")
+            .with_user("user", "secret")
+            .with_prefix("private");
+
+        for uri in [
+            "/.htpasswd",
+            "/public/.htaccess",
+            "/%2Ehtpasswd",
+            "/.htpasswd/",
+            "/.htpasswd/.",
+        ] {
+            let flow = auth.chain_req(request(Method::Get, uri)).await;
+            assert_eq!(status(flow), Some(HttpStatus::NotFound), "{uri}");
+        }
+    }
+}
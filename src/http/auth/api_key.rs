@@ -0,0 +1,239 @@
+//! API key authentication
+
+use std::{fs, future::Future, ops::ControlFlow, path::Path, str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+
+use super::constant_time_eq;
+use crate::http::{scoped::PathPattern, HttpStatus, InterceptorReq, Named, Request, Response};
+
+/// Header read by default by an [ApiKeyReqInterceptor]
+pub const DEFAULT_API_KEY_HEADER: &str = "X-API-Key";
+
+/// Owner of a valid key and what it may do, in the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyIdentity {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiKeyIdentity {
+    pub fn new(name: &str, scopes: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Source of the keys an [ApiKeyReqInterceptor] lets in
+///
+/// Async functions from the key to its identity are stores too, to look the
+/// keys up in a database or another service.
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn lookup(&self, key: &str) -> Option<ApiKeyIdentity>;
+}
+
+#[async_trait]
+impl<F, Fut> ApiKeyStore for F
+where
+    F: Fn(String) -> Fut + Send + Sync,
+    Fut: Future<Output = Option<ApiKeyIdentity>> + Send,
+{
+    async fn lookup(&self, key: &str) -> Option<ApiKeyIdentity> {
+        self(key.to_string()).await
+    }
+}
+
+/// Fixed list of keys, in memory or loaded from a file
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: Vec<(String, ApiKeyIdentity)>,
+}
+
+impl ApiKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key(mut self, key: &str, identity: ApiKeyIdentity) -> Self {
+        self.keys.push((key.to_string(), identity));
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        fs::read_to_string(path)
+            .map_err(|_| "Can't read the API keys file")?
+            .parse()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// `name:key` lines, optionally followed by `:scope,scope`
+///
+/// Blank lines and `#` comments are skipped.
+///
+/// ```
+/// use httpr::http::auth::api_key::{ApiKeyIdentity, ApiKeys};
+///
+/// let keys: ApiKeys = "# clients\nci:s3cr3t:read,write\nstatus:p4ss\n".parse().unwrap();
+/// assert!(!keys.is_empty());
+/// assert!("ci".parse::<ApiKeys>().is_err());
+/// ```
+impl FromStr for ApiKeys {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(3, ':');
+            let (Some(name), Some(key)) = (fields.next(), fields.next()) else {
+                return Err("Invalid API key entry");
+            };
+            if key.is_empty() {
+                return Err("Invalid API key entry");
+            }
+
+            let scopes = fields.next().unwrap_or_default().split(',');
+            let identity = ApiKeyIdentity {
+                name: name.to_string(),
+                scopes: scopes.filter(|s| !s.is_empty()).map(String::from).collect(),
+            };
+            keys.push((key.to_string(), identity));
+        }
+
+        Ok(Self { keys })
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for ApiKeys {
+    async fn lookup(&self, key: &str) -> Option<ApiKeyIdentity> {
+        // Every key is compared to not leak which one is close
+        self.keys
+            .iter()
+            .fold(None, |found, (expected, identity)| {
+                match constant_time_eq(key.as_bytes(), expected.as_bytes()) {
+                    true => Some(identity),
+                    false => found,
+                }
+            })
+            .cloned()
+    }
+}
+
+/// Require a key known by an [ApiKeyStore]
+///
+/// The key is read from the [DEFAULT_API_KEY_HEADER] unless other headers or
+/// query parameters are set, the first one present is used. Requests without
+/// a valid key get a `401`, the other ones carry its [ApiKeyIdentity].
+/// Scoped with [ApiKeyReqInterceptor::with_path], only the matching paths
+/// are protected.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use httpr::http::auth::api_key::{ApiKeyIdentity, ApiKeyReqInterceptor, ApiKeys};
+///
+/// let keys = ApiKeys::new().with_key("s3cr3t", ApiKeyIdentity::new("ci", &["read"]));
+/// let auth = ApiKeyReqInterceptor::new(Arc::new(keys)).with_query("api_key");
+///
+/// let lookup = ApiKeyReqInterceptor::new(Arc::new(|key: String| async move {
+///     (key == "s3cr3t").then(|| ApiKeyIdentity::new("ci", &[]))
+/// }));
+/// ```
+pub struct ApiKeyReqInterceptor {
+    store: Arc<dyn ApiKeyStore>,
+    headers: Vec<String>,
+    query_params: Vec<String>,
+    paths: Vec<PathPattern>,
+}
+
+impl ApiKeyReqInterceptor {
+    pub fn new(store: Arc<dyn ApiKeyStore>) -> Self {
+        Self {
+            store,
+            headers: Vec::new(),
+            query_params: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Read the key from the header `name`, besides the other ones set
+    pub fn with_header(mut self, name: &str) -> Self {
+        self.headers.push(name.to_lowercase());
+        self
+    }
+
+    /// Read the key from the query parameter `name`, after the headers
+    pub fn with_query(mut self, name: &str) -> Self {
+        self.query_params.push(name.to_string());
+        self
+    }
+
+    /// Protect only the requests matching `pattern` or the other ones added
+    pub fn with_path(mut self, pattern: PathPattern) -> Self {
+        self.paths.push(pattern);
+        self
+    }
+
+    fn key(&self, request: &Request) -> Option<String> {
+        let default = [DEFAULT_API_KEY_HEADER.to_lowercase()];
+        let headers = match self.headers.is_empty() && self.query_params.is_empty() {
+            true => &default[..],
+            false => &self.headers,
+        };
+
+        if let Some(key) = headers.iter().find_map(|name| request.header(name)) {
+            return Some(key.trim().to_string());
+        }
+
+        let url = request.url();
+        let mut pairs = url.query_pairs();
+        pairs
+            .find(|(name, _)| self.query_params.iter().any(|p| p == name))
+            .map(|(_, key)| key.into_owned())
+    }
+}
+
+impl Named for ApiKeyReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for ApiKeyReqInterceptor {
+    async fn chain_req(&self, mut request: Request) -> ControlFlow<Response, Request> {
+        let url = request.url();
+        if !self.paths.is_empty() && !self.paths.iter().any(|p| p.matches(url.path())) {
+            return ControlFlow::Continue(request);
+        }
+
+        let identity = match self.key(&request) {
+            Some(key) if !key.is_empty() => self.store.lookup(&key).await,
+            _ => None,
+        };
+
+        match identity {
+            Some(identity) => {
+                request.extensions_mut().insert(identity);
+                ControlFlow::Continue(request)
+            }
+            None => {
+                let mut response = Response::text("Missing or invalid API key");
+                response.set_status(HttpStatus::Unauthorized);
+                ControlFlow::Break(response)
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+use md5::{Digest, Md5};
+
+use super::Credentials;
+
+const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Credentials of an Apache `.htpasswd` file
+///
+/// Supports bcrypt (`$2y$`, `$2b$`, `$2a$`) and md5-crypt (`$apr1$`, `$1$`) entries,
+/// users with other hashes never authenticate.
+#[derive(Debug, Clone, Default)]
+pub struct Htpasswd {
+    users: HashMap<String, String>,
+}
+
+impl Htpasswd {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        fs::read_to_string(path)
+            .map_err(|_| "Can't read the htpasswd file")?
+            .parse()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+}
+
+/// `user:hash` lines, blank lines and `#` comments are skipped
+///
+/// ```
+/// use httpr::http::auth::{htpasswd::Htpasswd, Credentials};
+///
+/// let htpasswd: Htpasswd = "# team\nbob:$apr1$r31abcde$ouL8QL9v/FwrkrtBccxbL.\n".parse().unwrap();
+/// assert!(htpasswd.verify("bob", "password"));
+/// assert!(!htpasswd.verify("bob", "passw0rd"));
+/// ```
+impl FromStr for Htpasswd {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut users = HashMap::new();
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (user, hash) = line.split_once(':').ok_or("Invalid htpasswd entry")?;
+            users.insert(user.to_string(), hash.to_string());
+        }
+
+        Ok(Self { users })
+    }
+}
+
+impl Credentials for Htpasswd {
+    fn verify(&self, user: &str, password: &str) -> bool {
+        let Some(hash) = self.users.get(user) else {
+            return false;
+        };
+
+        if hash.starts_with("$2y$") || hash.starts_with("$2b$") || hash.starts_with("$2a$") {
+            return bcrypt::verify(password, hash).unwrap_or(false);
+        }
+
+        let (magic, rest) = match hash.strip_prefix("$apr1$") {
+            Some(rest) => ("$apr1$", rest),
+            None => match hash.strip_prefix("$1$") {
+                Some(rest) => ("$1$", rest),
+                None => return false,
+            },
+        };
+
+        let salt = rest.split('$').next().unwrap_or_default();
+        let expected = md5_crypt(password.as_bytes(), magic, salt);
+        super::constant_time_eq(expected.as_bytes(), hash.as_bytes())
+    }
+}
+
+/// FreeBSD md5-crypt, Apache's `$apr1$` only changes the magic
+fn md5_crypt(password: &[u8], magic: &str, salt: &str) -> String {
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let alternate = Md5::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+
+    let mut digest = Md5::new()
+        .chain_update(password)
+        .chain_update(magic)
+        .chain_update(salt);
+
+    for chunk in password.chunks(16) {
+        digest.update(&alternate[..chunk.len()]);
+    }
+
+    let mut len = password.len();
+    while len > 0 {
+        match len & 1 {
+            1 => digest.update([0]),
+            _ => digest.update(&password[..1]),
+        }
+        len >>= 1;
+    }
+
+    let mut result = digest.finalize();
+    for round in 0..1000 {
+        let mut digest = Md5::new();
+
+        match round & 1 {
+            1 => digest.update(password),
+            _ => digest.update(result),
+        }
+        if round % 3 != 0 {
+            digest.update(salt);
+        }
+        if round % 7 != 0 {
+            digest.update(password);
+        }
+        match round & 1 {
+            1 => digest.update(result),
+            _ => digest.update(password),
+        }
+
+        result = digest.finalize();
+    }
+
+    let mut encoded = String::with_capacity(22);
+    let mut push = |value: u32, chars: usize| {
+        let mut value = value;
+        for _ in 0..chars {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        push(
+            u32::from(result[a]) << 16 | u32::from(result[b]) << 8 | u32::from(result[c]),
+            4,
+        );
+    }
+    push(u32::from(result[11]), 2);
+
+    format!("{magic}{}${encoded}", String::from_utf8_lossy(salt))
+}
@@ -0,0 +1,146 @@
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// Response payload, kept in memory or streamed from a reader
+pub enum Body {
+    Bytes(Vec<u8>),
+    /// `len` bytes read from `reader` while writing the response
+    Stream {
+        reader: Pin<Box<dyn AsyncRead + Send>>,
+        len: u64,
+    },
+    /// Everything read from `reader`, the closed connection ends it
+    Unbounded {
+        reader: Pin<Box<dyn AsyncRead + Send>>,
+    },
+}
+
+impl Body {
+    pub fn stream<R: AsyncRead + Send + 'static>(reader: R, len: u64) -> Self {
+        Body::Stream {
+            reader: Box::pin(reader),
+            len,
+        }
+    }
+
+    pub fn unbounded<R: AsyncRead + Send + 'static>(reader: R) -> Self {
+        Body::Unbounded {
+            reader: Box::pin(reader),
+        }
+    }
+
+    /// Bytes of the body, `0` for the unbounded ones
+    pub fn len(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::Stream { len, .. } => *len,
+            Body::Unbounded { .. } => 0,
+        }
+    }
+
+    /// Whether the length is known before writing it
+    pub fn is_sized(&self) -> bool {
+        !matches!(self, Body::Unbounded { .. })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) async fn write_to<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        match self {
+            Body::Bytes(bytes) => writer.write_all(bytes).await,
+            Body::Stream { reader, len } => {
+                let copied = io::copy(&mut reader.take(*len), writer).await?;
+                if copied < *len {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Body stream ended before its length",
+                    ));
+                }
+
+                Ok(())
+            }
+            Body::Unbounded { reader } => io::copy(reader, writer).await.map(|_| ()),
+        }
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::Bytes(Vec::new())
+    }
+}
+
+/// Request body read from the connection while the handler consumes it
+///
+/// The client is only read as fast as the handler goes, see
+/// [Request::body_stream](super::Request::body_stream).
+pub struct BodyStream {
+    reader: Pin<Box<dyn AsyncRead + Send>>,
+    len: u64,
+}
+
+impl BodyStream {
+    pub(crate) fn new<R: AsyncRead + Send + 'static>(reader: R, len: u64) -> Self {
+        Self {
+            reader: Box::pin(reader.take(len)),
+            len,
+        }
+    }
+
+    /// `Content-Length` of the request
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Next bytes of the body, `None` once it's all read
+    pub async fn chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut chunk = vec![0; 8 * 1024];
+        match self.read(&mut chunk).await? {
+            0 => Ok(None),
+            read => {
+                chunk.truncate(read);
+                Ok(Some(chunk))
+            }
+        }
+    }
+}
+
+impl AsyncRead for BodyStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.reader.as_mut().poll_read(cx, buf)
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BodyStream({})", self.len)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => write!(f, "Bytes({})", bytes.len()),
+            Body::Stream { len, .. } => write!(f, "Stream({len})"),
+            Body::Unbounded { .. } => write!(f, "Unbounded"),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Construction of a [Server] with its socket tuned
+
+use std::io;
+
+use log::warn;
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream};
+
+use super::{HttpHandler, Server, DEFAULT_MAX_URI_LENGTH};
+
+/// Pending connections queued by the system, as with `TcpListener::bind`
+pub const DEFAULT_BACKLOG: u32 = 1024;
+
+/// Settings of the listening socket and the accepted connections
+#[derive(Debug, Clone)]
+pub(crate) struct ListenerOptions {
+    nodelay: bool,
+    reuse_address: bool,
+    reuse_port: bool,
+    backlog: u32,
+    recv_buffer_size: Option<u32>,
+    send_buffer_size: Option<u32>,
+}
+
+impl Default for ListenerOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: false,
+            reuse_address: cfg!(unix),
+            reuse_port: false,
+            backlog: DEFAULT_BACKLOG,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+        }
+    }
+}
+
+impl ListenerOptions {
+    /// Listen on the first address `bind` resolves to
+    pub(crate) async fn bind(&self, bind: &str) -> io::Result<TcpListener> {
+        let address = lookup_host(bind)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No address to bind to"))?;
+
+        let socket = match address.is_ipv4() {
+            true => TcpSocket::new_v4()?,
+            false => TcpSocket::new_v6()?,
+        };
+        socket.set_reuseaddr(self.reuse_address)?;
+        #[cfg(unix)]
+        socket.set_reuseport(self.reuse_port)?;
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        socket.bind(address)?;
+        socket.listen(self.backlog)
+    }
+
+    /// Apply the connection settings to an accepted `stream`
+    pub(crate) fn accepted(&self, stream: &TcpStream) {
+        if self.nodelay {
+            if let Err(e) = stream.set_nodelay(true) {
+                warn!("Can't set TCP_NODELAY: {e}");
+            }
+        }
+    }
+}
+
+/// [Server] with its listening socket and request limits tuned
+///
+/// The defaults are the ones of [Server::new]. The worker threads are the ones
+/// of the Tokio runtime running the server: one per core unless set with
+/// `#[tokio::main(worker_threads = 4)]` or the `TOKIO_WORKER_THREADS` variable.
+///
+/// ```
+/// use httpr::{http::ServerBuilder, static_server::StaticFileHandler};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let server = ServerBuilder::new("0.0.0.0:80", StaticFileHandler::new(".", false)?)
+///     .with_nodelay(true)
+///     .with_reuse_port(true)
+///     .with_backlog(4096)
+///     .build();
+/// # Ok(())
+/// # }
+/// ```
+pub struct ServerBuilder<H> {
+    bind: String,
+    handler: H,
+    options: ListenerOptions,
+    max_uri_length: usize,
+}
+
+impl<H: HttpHandler> ServerBuilder<H> {
+    pub fn new(bind: &str, handler: H) -> Self {
+        Self {
+            bind: bind.to_string(),
+            handler,
+            options: ListenerOptions::default(),
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+        }
+    }
+
+    /// Send the responses without waiting to fill the packets (`TCP_NODELAY`)
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.options.nodelay = nodelay;
+        self
+    }
+
+    /// Bind again while the old connections linger (`SO_REUSEADDR`), on by default on Unix
+    pub fn with_reuse_address(mut self, reuse: bool) -> Self {
+        self.options.reuse_address = reuse;
+        self
+    }
+
+    /// Share the address with other processes listening on it (`SO_REUSEPORT`)
+    ///
+    /// The system spreads the connections among them. Ignored out of Unix.
+    pub fn with_reuse_port(mut self, reuse: bool) -> Self {
+        self.options.reuse_port = reuse;
+        self
+    }
+
+    /// Connections waiting to be accepted, [DEFAULT_BACKLOG] unless set
+    ///
+    /// The system can cap it, like Linux with `net.core.somaxconn`.
+    pub fn with_backlog(mut self, backlog: u32) -> Self {
+        self.options.backlog = backlog;
+        self
+    }
+
+    /// Size of the receive buffer of the sockets (`SO_RCVBUF`), the system one unless set
+    pub fn with_recv_buffer_size(mut self, size: u32) -> Self {
+        self.options.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Size of the send buffer of the sockets (`SO_SNDBUF`), the system one unless set
+    pub fn with_send_buffer_size(mut self, size: u32) -> Self {
+        self.options.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Longest request target accepted, [DEFAULT_MAX_URI_LENGTH] unless set
+    ///
+    /// Longer ones get `414 URI Too Long`, see [Server::with_max_uri_length].
+    pub fn with_max_uri_length(mut self, max_uri_length: usize) -> Self {
+        self.max_uri_length = max_uri_length;
+        self
+    }
+
+    pub fn build(self) -> Server<H> {
+        let mut server = Server::new(self.bind, self.handler);
+        server.listener = self.options;
+        server.with_max_uri_length(self.max_uri_length);
+        server
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Open connections of every client address
+#[derive(Debug)]
+pub(crate) struct ClientTracker {
+    connections: Mutex<HashMap<IpAddr, usize>>,
+    /// `usize::MAX` when there is no cap
+    max_per_client: AtomicUsize,
+}
+
+impl ClientTracker {
+    pub(crate) fn new(max_per_client: Option<usize>) -> Self {
+        Self {
+            connections: Mutex::default(),
+            max_per_client: AtomicUsize::new(max_per_client.unwrap_or(usize::MAX)),
+        }
+    }
+
+    pub(crate) fn max_per_client(&self) -> Option<usize> {
+        match self.max_per_client.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            max => Some(max),
+        }
+    }
+
+    /// Change the cap, the clients already over it keep their connections
+    pub(crate) fn set_max_per_client(&self, max_per_client: Option<usize>) {
+        self.max_per_client
+            .store(max_per_client.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    /// Register a connection of `ip`, `None` if the client is over the cap
+    pub(crate) fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<ClientGuard> {
+        let mut connections = self.connections.lock().unwrap();
+        let count = connections.entry(ip).or_default();
+
+        if self.max_per_client().is_some_and(|max| *count >= max) {
+            return None;
+        }
+
+        *count += 1;
+
+        Some(ClientGuard {
+            tracker: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// Connection slot of a client, released on drop
+pub(crate) struct ClientGuard {
+    tracker: Arc<ClientTracker>,
+    ip: IpAddr,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        let mut connections = self.tracker.connections.lock().unwrap();
+
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Response body compression and request body decompression
+
+use std::{
+    io::{Read, Write},
+    ops::ControlFlow,
+};
+
+use async_trait::async_trait;
+use flate2::{
+    read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder},
+    write::{DeflateEncoder, GzEncoder},
+    Compression,
+};
+use log::warn;
+use tokio::io::AsyncReadExt;
+
+use super::{
+    negotiation::negotiate_encoding, Body, HttpStatus, InterceptorReq, InterceptorRes, Method,
+    Named, Request, Response,
+};
+
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// Smaller bodies barely shrink, they are sent as they are
+pub const DEFAULT_MIN_COMPRESSION_SIZE: usize = 1024;
+
+/// Bigger streamed bodies are sent as they are instead of read into memory
+pub const DEFAULT_MAX_COMPRESSION_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Largest decoded request body, bigger ones get a `413`
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Already compressed content types, `type/*` skips a whole type
+pub const DEFAULT_SKIPPED_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/avif",
+    "video/*",
+    "audio/*",
+    "font/woff",
+    "font/woff2",
+    "application/zip",
+    "application/gzip",
+    "application/zstd",
+    "application/x-7z-compressed",
+    "application/x-bzip2",
+    "application/x-rar-compressed",
+    "application/x-xz",
+    "application/pdf",
+];
+
+const ENCODINGS: [&str; 2] = ["gzip", "deflate"];
+
+/// Compress the response bodies with `gzip` or `deflate`, as the client accepts
+///
+/// Streamed bodies are read into memory to be compressed up to a max size. Partial
+/// contents and the skipped content types are left as they are.
+pub struct CompressionResInterceptor {
+    level: u32,
+    min_size: usize,
+    max_size: u64,
+    skipped_types: Vec<String>,
+}
+
+impl CompressionResInterceptor {
+    pub fn new() -> Self {
+        Self {
+            level: DEFAULT_COMPRESSION_LEVEL,
+            min_size: DEFAULT_MIN_COMPRESSION_SIZE,
+            max_size: DEFAULT_MAX_COMPRESSION_SIZE,
+            skipped_types: DEFAULT_SKIPPED_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+        }
+    }
+
+    /// From `0`, no compression, to `9`, the smallest and slowest
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Bodies under `min_size` bytes are sent uncompressed
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Streamed bodies over `max_size` bytes are sent uncompressed
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Replace the content types never compressed, like `image/png` or `video/*`
+    pub fn with_skipped_types<S: AsRef<str>>(mut self, types: &[S]) -> Self {
+        self.skipped_types = types.iter().map(|t| t.as_ref().to_lowercase()).collect();
+        self
+    }
+
+    fn is_skipped(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+
+        self.skipped_types
+            .iter()
+            .any(|skipped| match skipped.strip_suffix("/*") {
+                Some(main_type) => essence.split_once('/').is_some_and(|(t, _)| t == main_type),
+                None => *skipped == essence,
+            })
+    }
+
+    fn compress(&self, encoding: &str, data: &[u8]) -> Option<Vec<u8>> {
+        let level = Compression::new(self.level);
+
+        match encoding {
+            "gzip" => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(data).ok()?;
+                encoder.finish().ok()
+            }
+            _ => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), level);
+                encoder.write_all(data).ok()?;
+                encoder.finish().ok()
+            }
+        }
+    }
+}
+
+impl Default for CompressionResInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for CompressionResInterceptor {}
+
+#[async_trait]
+impl InterceptorRes for CompressionResInterceptor {
+    async fn chain_res(&self, request: &Request, mut response: Response) -> Response {
+        let status = response.status();
+        if !(200..300).contains(&status.code())
+            || status == HttpStatus::NoContent
+            || status == HttpStatus::PartialContent
+            || response.header("content-encoding").is_some()
+        {
+            return response;
+        }
+
+        match response.header("content-type") {
+            Some(content_type) if !self.is_skipped(content_type) => {}
+            _ => return response,
+        }
+
+        let len = response.body().len();
+        let is_stream = matches!(response.body(), Body::Stream { .. });
+        if len < self.min_size as u64
+            || (is_stream && len > self.max_size)
+            || !response.body().is_sized()
+        {
+            return response;
+        }
+
+        // The body depends on the request header from here on
+        let vary = match response.header("vary") {
+            Some(vary) if vary.to_lowercase().contains("accept-encoding") => vary.to_string(),
+            Some(vary) => format!("{vary}, Accept-Encoding"),
+            None => String::from("Accept-Encoding"),
+        };
+        response.add_header(("Vary", &vary));
+
+        // HEAD bodies may be announced only, they are never read
+        if request.method() == Method::Head {
+            return response;
+        }
+
+        let Some(encoding) = negotiate_encoding(request.header("accept-encoding"), &ENCODINGS)
+        else {
+            return response;
+        };
+
+        let data = match response.take_body() {
+            Body::Bytes(data) => data,
+            Body::Stream { reader, len } => {
+                let mut data = Vec::with_capacity(len as usize);
+                match reader.take(len).read_to_end(&mut data).await {
+                    Ok(read) if read as u64 == len => data,
+                    _ => {
+                        warn!("Failed reading the body to compress");
+                        return Response::new(HttpStatus::InternalServerError);
+                    }
+                }
+            }
+            body => {
+                response.set_body(body);
+                return response;
+            }
+        };
+
+        let compressed = match self.compress(encoding, &data) {
+            Some(compressed) if compressed.len() < data.len() => compressed,
+            _ => {
+                response.set_body(Body::Bytes(data));
+                return response;
+            }
+        };
+
+        // Same resource, different bytes
+        if let Some(etag) = response.header("etag").filter(|e| !e.starts_with("W/")) {
+            let weak = format!("W/{etag}");
+            response.add_header(("ETag", &weak));
+        }
+
+        response.add_header(("Content-Encoding", encoding));
+        response.set_body(Body::Bytes(compressed));
+
+        response
+    }
+}
+
+/// Decode the request bodies sent with `Content-Encoding: gzip` or `deflate`
+///
+/// Handlers get the decoded body, without the `Content-Encoding` header. Other
+/// encodings get a `415`, corrupt bodies a `400` and bodies decoding over the
+/// max size a `413`. Streamed request bodies are left as they are.
+pub struct DecompressionReqInterceptor {
+    max_size: u64,
+}
+
+impl DecompressionReqInterceptor {
+    pub fn new() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    /// Refuse the bodies decoding over `max_size` bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Decoded `body`, the encodings are undone from the last one applied
+    fn decode(&self, encodings: &str, body: &[u8]) -> Result<Vec<u8>, HttpStatus> {
+        let mut body = body.to_vec();
+
+        for encoding in encodings.rsplit(',').map(|e| e.trim().to_lowercase()) {
+            body = match encoding.as_str() {
+                "" | "identity" => continue,
+                "gzip" | "x-gzip" => self.read_limited(MultiGzDecoder::new(&body[..]))?,
+                // Zlib wrapped as the spec says, or raw as some clients send it
+                "deflate" => self
+                    .read_limited(ZlibDecoder::new(&body[..]))
+                    .or_else(|_| self.read_limited(DeflateDecoder::new(&body[..])))?,
+                _ => return Err(HttpStatus::UnsupportedMediaType),
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn read_limited<R: Read>(&self, decoder: R) -> Result<Vec<u8>, HttpStatus> {
+        let mut decoded = Vec::new();
+        decoder
+            .take(self.max_size + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|_| HttpStatus::BadRequest)?;
+
+        match decoded.len() as u64 > self.max_size {
+            true => Err(HttpStatus::PayloadTooLarge),
+            false => Ok(decoded),
+        }
+    }
+}
+
+impl Default for DecompressionReqInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for DecompressionReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for DecompressionReqInterceptor {
+    async fn chain_req(&self, mut request: Request) -> ControlFlow<Response, Request> {
+        let Some(encodings) = request.header("content-encoding").map(String::from) else {
+            return ControlFlow::Continue(request);
+        };
+        if request.body().is_empty() {
+            return ControlFlow::Continue(request);
+        }
+
+        match self.decode(&encodings, request.body()) {
+            Ok(body) => {
+                request.set_decoded_body(body);
+                ControlFlow::Continue(request)
+            }
+            Err(status) => {
+                let mut response = Response::new(status);
+                if status == HttpStatus::UnsupportedMediaType {
+                    response.add_header(("Accept-Encoding", "gzip, deflate"));
+                }
+                ControlFlow::Break(response)
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Conditional request helpers (`If-None-Match`, `If-Match`)
+
+use std::{
+    fs::Metadata,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+
+use super::Request;
+
+/// Current `ETag` and modification time of a resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    /// Validators of a file from its size and modification time
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let last_modified = metadata.modified().ok()?;
+        let since_epoch = last_modified.duration_since(UNIX_EPOCH).ok()?;
+        let etag = format!(
+            "\"{:x}-{:x}-{:x}\"",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos(),
+            metadata.len()
+        );
+
+        Some(Self {
+            etag,
+            last_modified,
+        })
+    }
+}
+
+/// `If-None-Match` check, `true` when `etag` matches any of the listed tags
+///
+/// Comparison is weak as required for `If-None-Match`, `W/"a"` matches `"a"`.
+///
+/// ```
+/// use httpr::http::conditional::none_match;
+///
+/// assert!(none_match(r#""a", W/"b""#, r#""b""#));
+/// assert!(none_match("*", r#""b""#));
+/// assert!(!none_match(r#""a""#, r#""b""#));
+/// ```
+pub fn none_match(if_none_match: &str, etag: &str) -> bool {
+    let etag = weak(etag);
+
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || weak(tag) == etag)
+}
+
+/// `If-Match` check, `true` when `etag` matches any of the listed tags
+///
+/// Comparison is strong as required for `If-Match`, weak tags never match.
+///
+/// ```
+/// use httpr::http::conditional::match_strong;
+///
+/// assert!(match_strong(r#""a", "b""#, r#""b""#));
+/// assert!(match_strong("*", r#""b""#));
+/// assert!(!match_strong(r#"W/"b""#, r#""b""#));
+/// ```
+pub fn match_strong(if_match: &str, etag: &str) -> bool {
+    !etag.starts_with("W/")
+        && if_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Whether a write request may change the resource, `412` otherwise
+///
+/// Evaluates `If-Match`, `If-Unmodified-Since` and `If-None-Match` as RFC 9110
+/// section 13.2.2 does, `current` is `None` when the resource doesn't exist yet.
+pub fn write_allowed(request: &Request, current: Option<&Validators>) -> bool {
+    if let Some(if_match) = request.header("if-match") {
+        match current {
+            Some(current) if match_strong(if_match, &current.etag) => {}
+            _ => return false,
+        }
+    } else if let (Some(since), Some(current)) = (
+        request
+            .header("if-unmodified-since")
+            .and_then(parse_http_date),
+        current,
+    ) {
+        if unix_secs(current.last_modified) > unix_secs(since) {
+            return false;
+        }
+    }
+
+    match (request.header("if-none-match"), current) {
+        (Some(tags), Some(current)) => !none_match(tags, &current.etag),
+        _ => true,
+    }
+}
+
+/// Format `time` as a HTTP date, `Last-Modified` or `Date` headers
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// use httpr::http::conditional::{http_date, parse_http_date};
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(784111777);
+/// assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+/// ```
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Parse a HTTP date, `None` if it isn't valid
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .ok()
+        .map(SystemTime::from)
+}
+
+/// HTTP dates have second precision
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
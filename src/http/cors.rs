@@ -0,0 +1,65 @@
+//! Cross-origin resource sharing headers
+
+use std::time::Duration;
+
+/// CORS answer of the preflight requests, see [Response::options](super::Response::options)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsOptions {
+    allow_origin: String,
+    allow_headers: Vec<String>,
+    max_age: Option<Duration>,
+    allow_credentials: bool,
+}
+
+impl CorsOptions {
+    /// Let the `origin` pages, or any with `*`, use the allowed methods
+    pub fn new(origin: &str) -> Self {
+        Self {
+            allow_origin: origin.to_string(),
+            allow_headers: Vec::new(),
+            max_age: None,
+            allow_credentials: false,
+        }
+    }
+
+    /// Request headers the pages may send
+    pub fn with_headers<S: AsRef<str>>(mut self, headers: &[S]) -> Self {
+        self.allow_headers = headers.iter().map(|h| h.as_ref().to_string()).collect();
+        self
+    }
+
+    /// How long the browsers can cache the preflight answer
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Let the pages send cookies and credentials, never with a `*` origin
+    pub fn with_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// Headers besides `Access-Control-Allow-Methods`
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("Access-Control-Allow-Origin", self.allow_origin.clone())];
+
+        if self.allow_origin != "*" {
+            headers.push(("Vary", String::from("Origin")));
+        }
+        if !self.allow_headers.is_empty() {
+            headers.push((
+                "Access-Control-Allow-Headers",
+                self.allow_headers.join(", "),
+            ));
+        }
+        if let Some(max_age) = self.max_age {
+            headers.push(("Access-Control-Max-Age", max_age.as_secs().to_string()));
+        }
+        if self.allow_credentials && self.allow_origin != "*" {
+            headers.push(("Access-Control-Allow-Credentials", String::from("true")));
+        }
+
+        headers
+    }
+}
@@ -0,0 +1,177 @@
+//! Artificial latency, to try clients against a slow server
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    ops::ControlFlow,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::time::sleep;
+
+use super::{scoped::PathPattern, InterceptorReq, Named, Request, Response};
+
+/// How long each request waits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delay {
+    Fixed(Duration),
+    /// Any time between both, equally likely
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Normally distributed around `mean`, never below zero
+    Normal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+}
+
+impl FromStr for Delay {
+    type Err = &'static str;
+
+    /// Milliseconds: `250` fixed, `100-500` uniform or `300~50` normal
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use httpr::http::delay::Delay;
+    ///
+    /// let ms = Duration::from_millis;
+    /// assert_eq!("250".parse(), Ok(Delay::Fixed(ms(250))));
+    /// assert_eq!("100-500".parse(), Ok(Delay::Uniform { min: ms(100), max: ms(500) }));
+    /// assert_eq!("300~50".parse(), Ok(Delay::Normal { mean: ms(300), std_dev: ms(50) }));
+    /// assert!("500-100".parse::<Delay>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "expected milliseconds like 250, 100-500 or 300~50";
+        let ms = |s: &str| {
+            s.trim()
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| invalid)
+        };
+
+        if let Some((min, max)) = s.split_once('-') {
+            let (min, max) = (ms(min)?, ms(max)?);
+            if min > max {
+                return Err("the minimum delay is over the maximum");
+            }
+            return Ok(Delay::Uniform { min, max });
+        }
+
+        if let Some((mean, std_dev)) = s.split_once('~') {
+            return Ok(Delay::Normal {
+                mean: ms(mean)?,
+                std_dev: ms(std_dev)?,
+            });
+        }
+
+        ms(s).map(Delay::Fixed)
+    }
+}
+
+/// Hold the requests for a [Delay] before letting them through
+///
+/// Scoped with [DelayReqInterceptor::with_path], only the matching paths wait.
+///
+/// ```
+/// use std::{sync::Arc, time::Duration};
+///
+/// use httpr::http::{
+///     delay::{Delay, DelayReqInterceptor},
+///     scoped::PathPattern,
+///     Server,
+/// };
+/// use httpr::static_server::StaticFileHandler;
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let slow_api = DelayReqInterceptor::new(Delay::Uniform {
+///     min: Duration::from_millis(100),
+///     max: Duration::from_millis(800),
+/// })
+/// .with_path(PathPattern::new("/api")?);
+///
+/// let mut server = Server::new(
+///     String::from("127.0.0.1:4444"),
+///     StaticFileHandler::new(".", false)?,
+/// );
+/// server.push_req_inter(Arc::new(slow_api));
+/// # Ok(())
+/// # }
+/// ```
+pub struct DelayReqInterceptor {
+    delay: Delay,
+    paths: Vec<PathPattern>,
+    random: Random,
+}
+
+impl DelayReqInterceptor {
+    pub fn new(delay: Delay) -> Self {
+        Self {
+            delay,
+            paths: Vec::new(),
+            random: Random::new(),
+        }
+    }
+
+    /// Delay only the requests matching `pattern` or the other ones added
+    pub fn with_path(mut self, pattern: PathPattern) -> Self {
+        self.paths.push(pattern);
+        self
+    }
+
+    fn next_delay(&self) -> Duration {
+        match self.delay {
+            Delay::Fixed(delay) => delay,
+            Delay::Uniform { min, max } => min + (max - min).mul_f64(self.random.next()),
+            Delay::Normal { mean, std_dev } => {
+                // Box-Muller transform
+                let (u1, u2) = (1.0 - self.random.next(), self.random.next());
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                let secs = mean.as_secs_f64() + z * std_dev.as_secs_f64();
+                Duration::from_secs_f64(secs.max(0.0))
+            }
+        }
+    }
+}
+
+impl Named for DelayReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for DelayReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let url = request.url();
+        if !self.paths.is_empty() && !self.paths.iter().any(|p| p.matches(url.path())) {
+            return ControlFlow::Continue(request);
+        }
+
+        sleep(self.next_delay()).await;
+        ControlFlow::Continue(request)
+    }
+}
+
+/// Uniform numbers in `[0, 1)`, good enough for jitter and never for secrets
+pub(crate) struct Random {
+    state: RandomState,
+    counter: AtomicU64,
+}
+
+impl Random {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: RandomState::new(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn next(&self) -> f64 {
+        let mut hasher = self.state.build_hasher();
+        hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+
+        // The 53 bits of an f64 mantissa
+        (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
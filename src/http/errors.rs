@@ -0,0 +1,118 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{HttpStatus, Named, Request, Response};
+
+/// Failure while answering a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerError {
+    /// The request couldn't be parsed
+    BadRequest(String),
+    /// The client was too slow sending the request
+    Timeout,
+    /// The request target is longer than the server accepts
+    UriTooLong,
+    /// The request body is larger than the server reads
+    PayloadTooLarge,
+    /// The request method or transfer coding is unknown to the server, with what it is
+    NotImplemented(String),
+    /// The handler returned an error
+    Handler(&'static str),
+    /// Interceptors and handler took longer than the request timeout
+    HandlerTimeout,
+    /// An interceptor answered with an error status
+    Status(HttpStatus),
+    /// The server is shutting down and takes no more requests
+    ShuttingDown,
+    /// A handler or an interceptor panicked, with the panic message
+    Panic(String),
+}
+
+impl ServerError {
+    pub fn status(&self) -> HttpStatus {
+        match self {
+            ServerError::BadRequest(_) => HttpStatus::BadRequest,
+            ServerError::Timeout => HttpStatus::RequestTimeout,
+            ServerError::UriTooLong => HttpStatus::UriTooLong,
+            ServerError::PayloadTooLarge => HttpStatus::PayloadTooLarge,
+            ServerError::NotImplemented(_) => HttpStatus::NotImplemented,
+            ServerError::Handler(_) | ServerError::Panic(_) => HttpStatus::InternalServerError,
+            ServerError::HandlerTimeout | ServerError::ShuttingDown => {
+                HttpStatus::ServiceUnavailable
+            }
+            ServerError::Status(status) => *status,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::BadRequest(detail) => write!(f, "Bad request: {detail}"),
+            ServerError::Timeout => write!(f, "Request not received in time"),
+            ServerError::UriTooLong => write!(f, "Request target too long"),
+            ServerError::PayloadTooLarge => write!(f, "Request body too large"),
+            ServerError::NotImplemented(what) => write!(f, "{what} not implemented"),
+            ServerError::Handler(msg) => write!(f, "Handler failed: {msg}"),
+            ServerError::HandlerTimeout => write!(f, "Request not answered in time"),
+            ServerError::Status(status) => write!(f, "Answered with {}", status.code()),
+            ServerError::ShuttingDown => write!(f, "Shutting down"),
+            ServerError::Panic(msg) => write!(f, "Panicked: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// Customize the responses of the [ServerError]s
+#[async_trait]
+pub trait ErrorHandler: Send + Sync + Named {
+    /// Build the answer to `error` from the default `response`
+    ///
+    /// `request` is `None` when it couldn't be read, and it has no body nor extensions
+    /// when an interceptor broke the chain.
+    async fn handle_error(
+        &self,
+        request: Option<&Request>,
+        error: &ServerError,
+        response: Response,
+    ) -> Response;
+}
+
+/// Answer the errors with RFC 9457 problem details, `application/problem+json`
+///
+/// Handler messages and panics are logged only, they aren't sent to the client.
+pub struct ProblemDetailsErrorHandler;
+
+impl Named for ProblemDetailsErrorHandler {}
+
+#[async_trait]
+impl ErrorHandler for ProblemDetailsErrorHandler {
+    async fn handle_error(
+        &self,
+        request: Option<&Request>,
+        error: &ServerError,
+        mut response: Response,
+    ) -> Response {
+        let status = response.status();
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": response.reason(),
+            "status": status.code(),
+        });
+
+        if let ServerError::BadRequest(detail) = error {
+            problem["detail"] = json!(detail);
+        }
+
+        if let Some(request) = request {
+            problem["instance"] = json!(request.uri);
+        }
+
+        response.add_header(("Content-Type", "application/problem+json"));
+        response.add_body(problem.to_string().as_bytes());
+        response
+    }
+}
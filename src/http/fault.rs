@@ -0,0 +1,159 @@
+//! Injected failures, to try the retries and resilience of the clients
+
+use std::{io::Cursor, str::FromStr};
+
+use async_trait::async_trait;
+use tokio::io::AsyncReadExt;
+
+use super::{
+    delay::Random, scoped::PathPattern, Body, HttpStatus, InterceptorRes, Named, Request, Response,
+};
+
+/// Failure replacing a response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// Error response with this status, empty
+    Status(HttpStatus),
+    /// Half of the body is sent and the connection closed
+    Truncate,
+    /// Connection reset, nothing is sent
+    Reset,
+}
+
+impl FromStr for Fault {
+    type Err = &'static str;
+
+    /// A `4xx` or `5xx` status code, `truncate` or `reset`
+    ///
+    /// ```
+    /// use httpr::http::{fault::Fault, HttpStatus};
+    ///
+    /// assert_eq!("503".parse(), Ok(Fault::Status(HttpStatus::ServiceUnavailable)));
+    /// assert_eq!("reset".parse(), Ok(Fault::Reset));
+    /// assert!("200".parse::<Fault>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncate" => Ok(Fault::Truncate),
+            "reset" => Ok(Fault::Reset),
+            code => match code.parse().ok().and_then(HttpStatus::custom) {
+                Some(status) if status.code() >= 400 => Ok(Fault::Status(status)),
+                _ => Err("expected an error status, truncate or reset"),
+            },
+        }
+    }
+}
+
+/// Replace some responses with a [Fault], by probability
+///
+/// Each response gets at most one fault. Scoped with
+/// [FaultResInterceptor::with_path], only the matching paths fail. The handler
+/// still solves the failed requests, push it last to truncate the final bodies.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use httpr::http::{fault::{Fault, FaultResInterceptor}, HttpStatus, Server};
+/// use httpr::static_server::StaticFileHandler;
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let chaos = FaultResInterceptor::new()
+///     .with_fault(0.1, Fault::Status(HttpStatus::ServiceUnavailable))?
+///     .with_fault(0.01, Fault::Reset)?;
+///
+/// let mut server = Server::new(
+///     String::from("127.0.0.1:4444"),
+///     StaticFileHandler::new(".", false)?,
+/// );
+/// server.push_res_inter(Arc::new(chaos));
+/// # Ok(())
+/// # }
+/// ```
+pub struct FaultResInterceptor {
+    faults: Vec<(f64, Fault)>,
+    paths: Vec<PathPattern>,
+    random: Random,
+}
+
+impl FaultResInterceptor {
+    pub fn new() -> Self {
+        Self {
+            faults: Vec::new(),
+            paths: Vec::new(),
+            random: Random::new(),
+        }
+    }
+
+    /// Inject `fault` in a `probability` of the responses, from `0` to `1`
+    ///
+    /// The probabilities of all the faults can't add up over `1`.
+    pub fn with_fault(mut self, probability: f64, fault: Fault) -> Result<Self, &'static str> {
+        let total = self.faults.iter().map(|(p, _)| p).sum::<f64>() + probability;
+        if !(0.0..=1.0).contains(&probability) || total > 1.0 {
+            return Err("Fault probabilities must add up to 1 at most");
+        }
+
+        self.faults.push((probability, fault));
+        Ok(self)
+    }
+
+    /// Inject the faults only in the requests matching `pattern` or the other ones added
+    pub fn with_path(mut self, pattern: PathPattern) -> Self {
+        self.paths.push(pattern);
+        self
+    }
+
+    fn pick(&self) -> Option<Fault> {
+        let mut roll = self.random.next();
+        for (probability, fault) in &self.faults {
+            if roll < *probability {
+                return Some(*fault);
+            }
+            roll -= probability;
+        }
+
+        None
+    }
+}
+
+impl Default for FaultResInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for FaultResInterceptor {}
+
+#[async_trait]
+impl InterceptorRes for FaultResInterceptor {
+    async fn chain_res(&self, request: &Request, mut response: Response) -> Response {
+        let url = request.url();
+        if !self.paths.is_empty() && !self.paths.iter().any(|p| p.matches(url.path())) {
+            return response;
+        }
+
+        match self.pick() {
+            Some(Fault::Status(status)) => Response::new(status),
+            Some(Fault::Reset) => Response::reset(),
+            Some(Fault::Truncate) => {
+                let body = truncate(response.take_body());
+                response.set_body(body);
+                response
+            }
+            None => response,
+        }
+    }
+}
+
+/// Half of the bytes of `body` with its whole length announced, the client sees
+/// it end early
+fn truncate(body: Body) -> Body {
+    match body {
+        Body::Bytes(bytes) if !bytes.is_empty() => {
+            let len = bytes.len() as u64;
+            Body::stream(Cursor::new(bytes).take(len / 2), len)
+        }
+        Body::Stream { reader, len } => Body::stream(reader.take(len / 2), len),
+        body => body,
+    }
+}
@@ -0,0 +1,54 @@
+//! Request method filtering
+
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+
+use super::{cors::CorsOptions, InterceptorReq, Method, Named, Request, Response};
+
+/// Let through only the allowed methods
+///
+/// Other methods get a `405` with the `Allow` header, `OPTIONS` is always answered
+/// here with the allowed set.
+pub struct MethodFilterReqInterceptor {
+    allowed: Vec<Method>,
+    cors: Option<CorsOptions>,
+}
+
+impl MethodFilterReqInterceptor {
+    /// `OPTIONS` is added to the `methods`
+    pub fn allow<I: IntoIterator<Item = Method>>(methods: I) -> Self {
+        let mut allowed = Vec::new();
+        for method in methods.into_iter().chain([Method::Options]) {
+            if !allowed.contains(&method) {
+                allowed.push(method);
+            }
+        }
+
+        Self {
+            allowed,
+            cors: None,
+        }
+    }
+
+    /// Answer the CORS preflight requests with `cors`
+    pub fn with_cors(mut self, cors: CorsOptions) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+}
+
+impl Named for MethodFilterReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for MethodFilterReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        match request.method() {
+            Method::Options => {
+                ControlFlow::Break(Response::options(&self.allowed, self.cors.as_ref()))
+            }
+            method if self.allowed.contains(&method) => ControlFlow::Continue(request),
+            _ => ControlFlow::Break(Response::method_not_allowed(&self.allowed)),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Per-route request metrics in the Prometheus text format
+
+use std::{collections::BTreeMap, fmt::Write, ops::ControlFlow, sync::Arc, sync::Mutex};
+
+use async_trait::async_trait;
+
+use super::{HttpStatus, InterceptorReq, LogSink, Named, Request, RequestLog, Response};
+
+/// Label of the requests without a [MatchedRoute](super::MatchedRoute)
+///
+/// Raw paths are never used as labels, they would grow without bound.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Upper bounds of the latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Upper bounds of the response size buckets, in bytes
+const SIZE_BUCKETS: [f64; 8] = [1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9];
+
+const STATUS_CLASSES: [&str; 5] = ["1xx", "2xx", "3xx", "4xx", "5xx"];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// Observations of each bucket, the last one being `+Inf`
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, route: &str) {
+        let mut cumulative = 0;
+
+        for (i, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            let le = match self.bounds.get(i) {
+                Some(bound) => bound.to_string(),
+                None => String::from("+Inf"),
+            };
+
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{route=\"{route}\",le=\"{le}\"}} {cumulative}"
+            );
+        }
+
+        let _ = writeln!(out, "{name}_sum{{route=\"{route}\"}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{route=\"{route}\"}} {cumulative}");
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RouteStats {
+    latency: Histogram,
+    size: Histogram,
+    statuses: [u64; 5],
+}
+
+impl Default for RouteStats {
+    fn default() -> Self {
+        Self {
+            latency: Histogram::new(&LATENCY_BUCKETS),
+            size: Histogram::new(&SIZE_BUCKETS),
+            statuses: [0; 5],
+        }
+    }
+}
+
+/// Latency and response size histograms and status class counters of each route
+///
+/// Register it as a [LogSink] to collect the metrics and serve them with a
+/// [MetricsReqInterceptor].
+#[derive(Debug, Default)]
+pub struct RouteMetrics {
+    routes: Mutex<BTreeMap<String, RouteStats>>,
+}
+
+impl RouteMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap().clone();
+        let mut out = String::new();
+
+        out.push_str("# TYPE httpr_request_duration_seconds histogram\n");
+        for (route, stats) in &routes {
+            stats
+                .latency
+                .render(&mut out, "httpr_request_duration_seconds", route);
+        }
+
+        out.push_str("# TYPE httpr_response_size_bytes histogram\n");
+        for (route, stats) in &routes {
+            stats
+                .size
+                .render(&mut out, "httpr_response_size_bytes", route);
+        }
+
+        out.push_str("# TYPE httpr_responses_total counter\n");
+        for (route, stats) in &routes {
+            for (class, count) in STATUS_CLASSES.iter().zip(stats.statuses) {
+                let _ = writeln!(
+                    out,
+                    "httpr_responses_total{{route=\"{route}\",status=\"{class}\"}} {count}"
+                );
+            }
+        }
+
+        out
+    }
+}
+
+impl Named for RouteMetrics {}
+
+#[async_trait]
+impl LogSink for RouteMetrics {
+    async fn record(&self, log: &RequestLog) {
+        let route = log.route.as_deref().unwrap_or(UNMATCHED_ROUTE);
+        let route = route.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes.entry(route).or_default();
+
+        stats.latency.observe(log.duration.as_secs_f64());
+        stats.size.observe(log.bytes_out as f64);
+
+        let class = (log.status.code() / 100).clamp(1, 5) - 1;
+        stats.statuses[class as usize] += 1;
+    }
+}
+
+/// Serve the [RouteMetrics] on `path`
+pub struct MetricsReqInterceptor {
+    path: String,
+    metrics: Arc<RouteMetrics>,
+}
+
+impl MetricsReqInterceptor {
+    pub fn new(path: &str, metrics: Arc<RouteMetrics>) -> Self {
+        Self {
+            path: path.to_string(),
+            metrics,
+        }
+    }
+}
+
+impl Named for MetricsReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for MetricsReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let path = request.uri.split('?').next().unwrap_or_default();
+        if path != self.path {
+            return ControlFlow::Continue(request);
+        }
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "text/plain; version=0.0.4"));
+        response.add_header(("Cache-Control", "no-store"));
+        response.add_body(self.metrics.render().as_bytes());
+
+        ControlFlow::Break(response)
+    }
+}
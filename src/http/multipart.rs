@@ -0,0 +1,127 @@
+//! `multipart/form-data` request bodies
+
+/// Field of a form, a file when it has a file name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormPart<'a> {
+    pub name: Option<String>,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub data: &'a [u8],
+}
+
+/// Boundary of a `multipart/form-data` content type
+///
+/// ```
+/// use httpr::http::multipart::form_boundary;
+///
+/// assert_eq!(form_boundary("multipart/form-data; boundary=\"abc\""), Some("abc"));
+/// assert_eq!(form_boundary("application/json"), None);
+/// ```
+pub fn form_boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    let media = params.next()?.trim();
+
+    if !media.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        key.eq_ignore_ascii_case("boundary")
+            .then(|| value.trim_matches('"'))
+            .filter(|b| !b.is_empty())
+    })
+}
+
+/// Parts of a `multipart/form-data` body delimited by `boundary`
+///
+/// ```
+/// use httpr::http::multipart::parse_form_data;
+///
+/// let body = b"--b\r\n\
+///     Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+///     Content-Type: text/plain\r\n\r\n\
+///     hi\r\n\
+///     --b--\r\n";
+///
+/// let parts = parse_form_data(body, "b").unwrap();
+/// assert_eq!(parts[0].file_name.as_deref(), Some("a.txt"));
+/// assert_eq!(parts[0].data, b"hi");
+/// ```
+pub fn parse_form_data<'a>(
+    body: &'a [u8],
+    boundary: &str,
+) -> Result<Vec<FormPart<'a>>, &'static str> {
+    let delimiter = format!("--{boundary}");
+    let next_delimiter = format!("\r\n--{boundary}");
+
+    let start = find(body, delimiter.as_bytes()).ok_or("Missing boundary")?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        rest = rest.strip_prefix(b"\r\n").ok_or("Malformed boundary")?;
+
+        let head_end = find(rest, b"\r\n\r\n").ok_or("Malformed part headers")?;
+        let head = std::str::from_utf8(&rest[..head_end]).map_err(|_| "Malformed part headers")?;
+        rest = &rest[head_end + 4..];
+
+        let data_end = find(rest, next_delimiter.as_bytes()).ok_or("Missing closing boundary")?;
+        let mut part = FormPart {
+            name: None,
+            file_name: None,
+            content_type: None,
+            data: &rest[..data_end],
+        };
+        rest = &rest[data_end + next_delimiter.len()..];
+
+        for line in head.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err("Malformed part headers");
+            };
+
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                part.name = disposition_param(value, "name");
+                part.file_name = disposition_param(value, "filename");
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.trim().to_string());
+            }
+        }
+
+        parts.push(part);
+    }
+}
+
+/// Value of `param` in a `Content-Disposition`, quotes may hold `;`
+fn disposition_param(disposition: &str, param: &str) -> Option<String> {
+    let mut rest = disposition;
+
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.rsplit(';').next().unwrap_or_default().trim();
+
+        let (value, tail) = match value.trim_start().strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => value.split_once(';').unwrap_or((value, "")),
+        };
+
+        if key.eq_ignore_ascii_case(param) {
+            return Some(value.trim().to_string());
+        }
+        rest = tail;
+    }
+
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,307 @@
+//! Content negotiation helpers for `Accept`-like headers
+
+/// Parse a comma separated header with optional `q` weights
+///
+/// Items keep the header order, entries with an invalid weight are ignored and
+/// missing weights default to `1.0`.
+///
+/// ```
+/// use httpr::http::negotiation::parse_quality_list;
+///
+/// let items = parse_quality_list("text/html, application/json;q=0.5");
+/// assert_eq!(items, vec![("text/html", 1.0), ("application/json", 0.5)]);
+/// ```
+pub fn parse_quality_list(header: &str) -> Vec<(&str, f32)> {
+    let mut items = Vec::new();
+
+    for item in header.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let value = match parts.next() {
+            Some(v) if !v.is_empty() => v,
+            _ => continue,
+        };
+
+        let mut q = Some(1.0);
+        for param in parts {
+            if let Some((k, v)) = param.split_once('=') {
+                if k.trim().eq_ignore_ascii_case("q") {
+                    q = v
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q));
+                }
+            }
+        }
+
+        if let Some(q) = q {
+            items.push((value, q));
+        }
+    }
+
+    items
+}
+
+/// Pick the best media type from `available` according to an `Accept` header
+///
+/// Without header the first available type is chosen. Each candidate takes the weight
+/// of the most specific matching range (`type/subtype` > `type/*` > `*/*`), ties are
+/// solved by the order of `available`. `None` means nothing is acceptable.
+///
+/// ```
+/// use httpr::http::negotiation::negotiate_media;
+///
+/// let available = ["text/html", "application/json"];
+///
+/// assert_eq!(negotiate_media(None, &available), Some("text/html"));
+/// assert_eq!(
+///     negotiate_media(Some("application/json, */*;q=0.1"), &available),
+///     Some("application/json")
+/// );
+/// assert_eq!(negotiate_media(Some("image/*"), &available), None);
+/// ```
+pub fn negotiate_media<'a>(accept: Option<&str>, available: &[&'a str]) -> Option<&'a str> {
+    let accept = match accept {
+        Some(a) if !a.trim().is_empty() => a,
+        _ => return available.first().copied(),
+    };
+
+    let ranges = parse_quality_list(accept);
+
+    best_match(available, |candidate| {
+        let (c_type, c_subtype) = split_media(candidate)?;
+
+        ranges
+            .iter()
+            .filter_map(|(range, q)| {
+                let (r_type, r_subtype) = split_media(range)?;
+
+                let specificity = match (r_type, r_subtype) {
+                    ("*", "*") => 0,
+                    (t, "*") if t.eq_ignore_ascii_case(c_type) => 1,
+                    (t, s)
+                        if t.eq_ignore_ascii_case(c_type) && s.eq_ignore_ascii_case(c_subtype) =>
+                    {
+                        2
+                    }
+                    _ => return None,
+                };
+
+                Some((specificity, *q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+    })
+}
+
+/// Pick the best language tag from `available` according to an `Accept-Language` header
+///
+/// Ranges match exact tags or their prefix (`en` matches `en-US`), `*` matches
+/// anything. Same rules as [negotiate_media] for missing headers and ties.
+///
+/// ```
+/// use httpr::http::negotiation::negotiate_language;
+///
+/// let available = ["en", "es"];
+///
+/// assert_eq!(negotiate_language(Some("es-ES, en;q=0.8"), &available), Some("es"));
+/// assert_eq!(negotiate_language(Some("fr"), &available), None);
+/// ```
+pub fn negotiate_language<'a>(
+    accept_language: Option<&str>,
+    available: &[&'a str],
+) -> Option<&'a str> {
+    let accept_language = match accept_language {
+        Some(a) if !a.trim().is_empty() => a,
+        _ => return available.first().copied(),
+    };
+
+    let ranges = parse_quality_list(accept_language);
+
+    best_match(available, |candidate| {
+        ranges
+            .iter()
+            .filter_map(|(range, q)| {
+                let specificity = if *range == "*" {
+                    0
+                } else if range.eq_ignore_ascii_case(candidate) {
+                    3
+                } else if is_lang_prefix(candidate, range) {
+                    2
+                } else if is_lang_prefix(range, candidate) {
+                    1
+                } else {
+                    return None;
+                };
+
+                Some((specificity, *q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+    })
+}
+
+/// Pick the best content coding from `available` according to an `Accept-Encoding` header
+///
+/// Unlike the other headers, no header means only `identity` is acceptable so `None`
+/// is returned. `*` matches any coding.
+///
+/// ```
+/// use httpr::http::negotiation::negotiate_encoding;
+///
+/// let available = ["gzip", "deflate"];
+///
+/// assert_eq!(negotiate_encoding(Some("deflate, gzip;q=0.5"), &available), Some("deflate"));
+/// assert_eq!(negotiate_encoding(Some("br"), &available), None);
+/// assert_eq!(negotiate_encoding(None, &available), None);
+/// ```
+pub fn negotiate_encoding<'a>(
+    accept_encoding: Option<&str>,
+    available: &[&'a str],
+) -> Option<&'a str> {
+    let ranges = parse_quality_list(accept_encoding?);
+
+    best_match(available, |candidate| {
+        ranges
+            .iter()
+            .filter(|(coding, _)| *coding == "*" || coding.eq_ignore_ascii_case(candidate))
+            .max_by_key(|(coding, _)| *coding != "*")
+            .map(|(_, q)| *q)
+    })
+}
+
+fn best_match<'a, F>(available: &[&'a str], weight: F) -> Option<&'a str>
+where
+    F: Fn(&str) -> Option<f32>,
+{
+    let mut best: Option<(&'a str, f32)> = None;
+
+    for candidate in available {
+        let q = match weight(candidate) {
+            Some(q) if q > 0.0 => q,
+            _ => continue,
+        };
+
+        if best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((candidate, q));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate)
+}
+
+fn split_media(media: &str) -> Option<(&str, &str)> {
+    let essence = media.split(';').next()?.trim();
+    essence.split_once('/')
+}
+
+/// `prefix` is a language prefix of `tag`, `en` for `en-US`
+fn is_lang_prefix(tag: &str, prefix: &str) -> bool {
+    tag.len() > prefix.len()
+        && tag.as_bytes()[prefix.len()] == b'-'
+        && tag[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quality_lists() {
+        let cases: [(&str, &[(&str, f32)]); 10] = [
+            ("", &[]),
+            (" , ,", &[]),
+            ("a", &[("a", 1.0)]),
+            ("a;q=0.5, b", &[("a", 0.5), ("b", 1.0)]),
+            ("a ; Q = 0 ; level=1", &[("a", 0.0)]),
+            ("a;level=1;q=0.3", &[("a", 0.3)]),
+            ("a;q=1.5, b;q=-0.1, c;q=0.2", &[("c", 0.2)]),
+            ("a;q=, b;q=high, c;q=NaN, d;q=inf", &[]),
+            (";q=0.5, a", &[("a", 1.0)]),
+            ("a;q, b;q=1", &[("a", 1.0), ("b", 1.0)]),
+        ];
+
+        for (header, expected) in cases {
+            assert_eq!(parse_quality_list(header), expected, "{header}");
+        }
+    }
+
+    #[test]
+    fn negotiates_media_types() {
+        let available = ["text/html", "text/plain", "application/json"];
+        let cases = [
+            (None, Some("text/html")),
+            (Some(" "), Some("text/html")),
+            (Some("*/*"), Some("text/html")),
+            (Some("application/json"), Some("application/json")),
+            (Some("APPLICATION/JSON"), Some("application/json")),
+            (
+                Some("text/*;q=0.5, application/*"),
+                Some("application/json"),
+            ),
+            (Some("text/plain, text/html"), Some("text/html")),
+            (Some("text/*, text/html;q=0"), Some("text/plain")),
+            (
+                Some("*/*;q=0.1, text/plain;charset=utf-8"),
+                Some("text/plain"),
+            ),
+            (Some("*/*, text/*;q=0"), Some("application/json")),
+            (Some("*/*;q=0"), None),
+            (Some("image/*, text"), None),
+            (Some("text/html;q=2"), None),
+        ];
+
+        for (accept, expected) in cases {
+            assert_eq!(negotiate_media(accept, &available), expected, "{accept:?}");
+        }
+    }
+
+    #[test]
+    fn negotiates_languages() {
+        let available = ["en", "en-GB", "es"];
+        let cases = [
+            (None, Some("en")),
+            (Some("*"), Some("en")),
+            (Some("es"), Some("es")),
+            (Some("EN-gb, en;q=0.5"), Some("en-GB")),
+            (Some("en-US"), Some("en")),
+            (Some("es-ES, en;q=0.8"), Some("es")),
+            (Some("en-GB;q=0, en"), Some("en")),
+            (Some("*;q=0.1, es;q=0"), Some("en")),
+            (Some("e, english"), None),
+            (Some("*;q=0"), None),
+        ];
+
+        for (accept_language, expected) in cases {
+            assert_eq!(
+                negotiate_language(accept_language, &available),
+                expected,
+                "{accept_language:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn negotiates_encodings() {
+        let available = ["br", "gzip"];
+        let cases = [
+            (None, None),
+            (Some(""), None),
+            (Some("identity"), None),
+            (Some("*"), Some("br")),
+            (Some("GZIP"), Some("gzip")),
+            (Some("br;q=0.5, gzip"), Some("gzip")),
+            (Some("*, br;q=0"), Some("gzip")),
+            (Some("gzip;q=0.1, *;q=0.5"), Some("br")),
+            (Some("*;q=0"), None),
+        ];
+
+        for (accept_encoding, expected) in cases {
+            assert_eq!(
+                negotiate_encoding(accept_encoding, &available),
+                expected,
+                "{accept_encoding:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,556 @@
+//! Mock server of an OpenAPI 3 document
+
+use std::{collections::HashMap, fs, path::Path};
+
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Map, Value};
+
+use super::{HttpHandler, HttpStatus, Method, Named, Request, Response};
+
+/// Nested `$ref` and schemas followed at most, documents can be recursive
+const MAX_DEPTH: usize = 16;
+
+const METHODS: [(&str, Method); 8] = [
+    ("get", Method::Get),
+    ("put", Method::Put),
+    ("post", Method::Post),
+    ("delete", Method::Delete),
+    ("options", Method::Options),
+    ("head", Method::Head),
+    ("patch", Method::Patch),
+    ("trace", Method::Trace),
+];
+
+struct Operation {
+    method: Method,
+    /// Path segments, `None` for the `{param}` ones
+    segments: Vec<Option<String>>,
+    /// Path parameter names, in the path order
+    path_params: Vec<String>,
+    /// Path and operation parameters, resolved
+    parameters: Vec<Value>,
+    request_body: Option<Value>,
+    responses: Option<Value>,
+}
+
+impl Operation {
+    fn matches(&self, path: &[&str]) -> Option<HashMap<String, String>> {
+        if self.segments.len() != path.len() {
+            return None;
+        }
+
+        let mut params = self.path_params.iter();
+        let mut values = HashMap::new();
+        for (segment, value) in self.segments.iter().zip(path) {
+            match segment {
+                Some(segment) if segment == value => {}
+                Some(_) => return None,
+                None => {
+                    let value = percent_decode_str(value).decode_utf8_lossy().into_owned();
+                    values.insert(params.next()?.clone(), value);
+                }
+            }
+        }
+
+        Some(values)
+    }
+
+    /// Static segments first, `/users/me` wins over `/users/{id}`
+    fn specificity(&self) -> Vec<bool> {
+        self.segments.iter().map(Option::is_none).collect()
+    }
+}
+
+/// Handler answering the operations of an OpenAPI 3 document with mock responses
+///
+/// Responses are the lowest `2xx` of each operation, with its `example`, its first
+/// `examples` value or a value generated from its schema. Requests missing a
+/// required parameter or body, or with a parameter or JSON body not matching its
+/// schema, get a `400` telling why.
+///
+/// ```no_run
+/// use httpr::http::{openapi::OpenApiMockHandler, Server};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let mock = OpenApiMockHandler::from_file("openapi.yaml")?;
+/// let server = Server::new(String::from("127.0.0.1:4444"), mock);
+/// # Ok(())
+/// # }
+/// ```
+pub struct OpenApiMockHandler {
+    document: Value,
+    operations: Vec<Operation>,
+}
+
+impl OpenApiMockHandler {
+    /// Mock of a parsed OpenAPI 3 `document`
+    pub fn new(document: Value) -> Result<Self, &'static str> {
+        let version = document.get("openapi").and_then(Value::as_str);
+        if !version.is_some_and(|v| v.starts_with("3.")) {
+            return Err("Not an OpenAPI 3 document!");
+        }
+
+        let mut handler = Self {
+            document,
+            operations: Vec::new(),
+        };
+
+        let mut operations = Vec::new();
+        let paths = handler.document.get("paths").and_then(Value::as_object);
+        for (template, item) in paths.into_iter().flatten() {
+            let item = handler.resolve(item);
+            let shared = handler.parameters(item.get("parameters"));
+
+            let (segments, path_params) = parse_template(template)?;
+            for (key, method) in METHODS {
+                let Some(operation) = item.get(key).map(|o| handler.resolve(o)) else {
+                    continue;
+                };
+
+                // Operation parameters override the path ones with their name and location
+                let mut parameters = handler.parameters(operation.get("parameters"));
+                for parameter in &shared {
+                    if !parameters.iter().any(|p| same_parameter(p, parameter)) {
+                        parameters.push(parameter.clone());
+                    }
+                }
+
+                operations.push(Operation {
+                    method,
+                    segments: segments.clone(),
+                    path_params: path_params.clone(),
+                    parameters,
+                    request_body: operation
+                        .get("requestBody")
+                        .map(|b| handler.resolve(b).clone()),
+                    responses: operation.get("responses").cloned(),
+                });
+            }
+        }
+
+        operations.sort_by_key(Operation::specificity);
+        handler.operations = operations;
+        Ok(handler)
+    }
+
+    /// Load a YAML or JSON document
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let content = fs::read_to_string(path).map_err(|_| "Can't read the OpenAPI document")?;
+        let document = serde_yaml::from_str::<Value>(&content)
+            .map_err(|_| "Invalid OpenAPI document, expected YAML or JSON")?;
+
+        Self::new(document)
+    }
+
+    /// `value`, or the component its `$ref` points to
+    fn resolve<'a>(&'a self, mut value: &'a Value) -> &'a Value {
+        for _ in 0..MAX_DEPTH {
+            let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+                return value;
+            };
+
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.document.pointer(pointer))
+            {
+                Some(target) => value = target,
+                None => return &Value::Null,
+            }
+        }
+
+        &Value::Null
+    }
+
+    fn parameters(&self, parameters: Option<&Value>) -> Vec<Value> {
+        let parameters = parameters.and_then(Value::as_array);
+        parameters
+            .into_iter()
+            .flatten()
+            .map(|p| self.resolve(p).clone())
+            .collect()
+    }
+
+    /// Why `request` doesn't follow `operation`, if it doesn't
+    fn validate(
+        &self,
+        request: &Request,
+        operation: &Operation,
+        path_params: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let url = request.url();
+        let query = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+
+        for parameter in &operation.parameters {
+            let name = parameter.get("name").and_then(Value::as_str).unwrap_or("");
+            let location = parameter.get("in").and_then(Value::as_str).unwrap_or("");
+            let value = match location {
+                "path" => path_params.get(name).map(String::as_str),
+                "query" => query.get(name).map(String::as_str),
+                "header" => request.header(&name.to_lowercase()),
+                _ => continue,
+            };
+
+            let required = location == "path" || parameter["required"] == json!(true);
+            let Some(value) = value else {
+                match required {
+                    true => return Err(format!("Missing required {location} parameter '{name}'")),
+                    false => continue,
+                }
+            };
+
+            if let Some(schema) = parameter.get("schema") {
+                let schema = self.resolve(schema);
+                self.check(&parse_scalar(value, schema), schema, 0)
+                    .map_err(|e| format!("Invalid {location} parameter '{name}': {e}"))?;
+            }
+        }
+
+        let Some(body) = &operation.request_body else {
+            return Ok(());
+        };
+
+        if request.body().is_empty() {
+            return match body["required"] == json!(true) {
+                true => Err(String::from("Missing required request body")),
+                false => Ok(()),
+            };
+        }
+
+        let content_type = request.header("content-type").unwrap_or("");
+        let schema = body
+            .get("content")
+            .and_then(Value::as_object)
+            .and_then(|content| media_type(content, Some(content_type)))
+            .and_then(|media| media.get("schema"));
+
+        if content_type.contains("json") {
+            let value = serde_json::from_slice::<Value>(request.body())
+                .map_err(|e| format!("Invalid JSON body: {e}"))?;
+            if let Some(schema) = schema {
+                self.check(&value, schema, 0)
+                    .map_err(|e| format!("Invalid request body: {e}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `value` follows the basics of `schema`: types, enums and required properties
+    fn check(&self, value: &Value, schema: &Value, depth: usize) -> Result<(), String> {
+        let schema = self.resolve(schema);
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in all {
+                self.check(value, schema, depth + 1)?;
+            }
+        }
+
+        for key in ["oneOf", "anyOf"] {
+            if let Some(any) = schema.get(key).and_then(Value::as_array) {
+                if !any.iter().any(|s| self.check(value, s, depth + 1).is_ok()) {
+                    return Err(format!("{value} matches none of the {key} schemas"));
+                }
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                return Err(format!(
+                    "{value} isn't one of {}",
+                    Value::from(allowed.clone())
+                ));
+            }
+        }
+
+        let nullable = schema["nullable"] == json!(true);
+        let matches = match schema.get("type").and_then(Value::as_str) {
+            _ if value.is_null() && nullable => true,
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("boolean") => value.is_boolean(),
+            Some("array") => value.is_array(),
+            Some("object") => value.is_object(),
+            _ => true,
+        };
+        if !matches {
+            let expected = schema["type"].as_str().unwrap_or_default();
+            return Err(format!("expected {expected}, got {value}"));
+        }
+
+        if let Some(object) = value.as_object() {
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    return Err(format!("missing required property '{name}'"));
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, schema) in properties.into_iter().flatten() {
+                if let Some(value) = object.get(name) {
+                    self.check(value, schema, depth + 1)
+                        .map_err(|e| format!("property '{name}': {e}"))?;
+                }
+            }
+        }
+
+        if let (Some(items), Some(schema)) = (value.as_array(), schema.get("items")) {
+            for item in items {
+                self.check(item, schema, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Example value of `schema`, or one made up from its types
+    ///
+    /// `refs` are the components being generated, a component isn't generated
+    /// again inside itself.
+    fn generate(&self, schema: &Value, refs: &mut Vec<String>) -> Value {
+        let reference = schema.get("$ref").and_then(Value::as_str);
+        if let Some(reference) = reference {
+            if refs.len() >= MAX_DEPTH || refs.iter().any(|r| r == reference) {
+                return Value::Null;
+            }
+            refs.push(reference.to_string());
+        }
+
+        let value = self.generate_resolved(self.resolve(schema), refs);
+        if reference.is_some() {
+            refs.pop();
+        }
+        value
+    }
+
+    /// Whether `schema` points to a component being generated
+    fn recurses(schema: &Value, refs: &[String]) -> bool {
+        let reference = schema.get("$ref").and_then(Value::as_str);
+        reference.is_some_and(|reference| refs.iter().any(|r| r == reference))
+    }
+
+    fn generate_resolved(&self, schema: &Value, refs: &mut Vec<String>) -> Value {
+        if let Some(example) = schema.get("example") {
+            return example.clone();
+        }
+        if let Some(first) = schema.get("enum").and_then(|e| e.get(0)) {
+            return first.clone();
+        }
+        if let Some(default) = schema.get("default") {
+            return default.clone();
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            let mut merged = Map::new();
+            for schema in all {
+                if let Value::Object(object) = self.generate(schema, refs) {
+                    merged.extend(object);
+                }
+            }
+            return Value::Object(merged);
+        }
+        for key in ["oneOf", "anyOf"] {
+            if let Some(first) = schema.get(key).and_then(|s| s.get(0)) {
+                return self.generate(first, refs);
+            }
+        }
+
+        let minimum = schema.get("minimum").cloned();
+        match schema.get("type").and_then(Value::as_str) {
+            Some("string") => match schema.get("format").and_then(Value::as_str) {
+                Some("date") => json!("2024-01-01"),
+                Some("date-time") => json!("2024-01-01T00:00:00Z"),
+                Some("email") => json!("user@example.com"),
+                Some("uuid") => json!("00000000-0000-4000-8000-000000000000"),
+                Some("uri") => json!("https://example.com"),
+                _ => json!("string"),
+            },
+            Some("integer") => minimum.unwrap_or(json!(0)),
+            Some("number") => minimum.unwrap_or(json!(0.0)),
+            Some("boolean") => json!(true),
+            Some("array") => match schema.get("items") {
+                Some(items) if Self::recurses(items, refs) => json!([]),
+                Some(items) => json!([self.generate(items, refs)]),
+                None => json!([]),
+            },
+            _ => {
+                let required = schema.get("required").and_then(Value::as_array);
+                let is_required = |name: &str| {
+                    required.is_some_and(|r| r.iter().any(|n| n.as_str() == Some(name)))
+                };
+
+                let mut object = Map::new();
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (name, schema) in properties.into_iter().flatten() {
+                    // Optional self references are left out, ending the recursion
+                    if is_required(name) || !Self::recurses(schema, refs) {
+                        object.insert(name.clone(), self.generate(schema, refs));
+                    }
+                }
+                Value::Object(object)
+            }
+        }
+    }
+
+    /// Lowest `2xx` response of `operation`, or its default one
+    fn mock_response(&self, request: &Request, operation: &Operation) -> Response {
+        let responses = operation.responses.as_ref().and_then(Value::as_object);
+        let mut candidates = responses
+            .into_iter()
+            .flatten()
+            .filter_map(|(code, response)| match code.as_str() {
+                "default" => Some((200, response)),
+                "2XX" => Some((200, response)),
+                code => code
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|c| (200..300).contains(c))
+                    .map(|c| (c, response)),
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(code, _)| *code);
+
+        let Some((code, response)) = candidates.first() else {
+            return Response::no_content();
+        };
+        let status = HttpStatus::custom(*code).unwrap_or(HttpStatus::Ok);
+        let response = self.resolve(response);
+
+        let content = response.get("content").and_then(Value::as_object);
+        let Some((content_type, media)) = content.and_then(|content| {
+            let accept = request.header("accept");
+            let content_type = media_type_name(content, accept)?;
+            Some((content_type.to_string(), content.get(content_type)?))
+        }) else {
+            return Response::new(status);
+        };
+
+        let example = media.get("example").cloned().or_else(|| {
+            let examples = media.get("examples").and_then(Value::as_object)?;
+            let first = examples.values().next()?;
+            self.resolve(first).get("value").cloned()
+        });
+        let value = example.unwrap_or_else(|| match media.get("schema") {
+            Some(schema) => self.generate(schema, &mut Vec::new()),
+            None => Value::Null,
+        });
+
+        let body = match (&value, content_type.contains("json")) {
+            (Value::String(text), false) => text.clone().into_bytes(),
+            (value, _) => serde_json::to_vec(value).unwrap_or_default(),
+        };
+
+        let mut response = Response::new(status);
+        response.add_header(("Content-Type", &content_type));
+        response.add_body(&body);
+        response
+    }
+}
+
+impl Named for OpenApiMockHandler {}
+
+#[async_trait]
+impl HttpHandler for OpenApiMockHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let url = request.url();
+        let path = url
+            .path()
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut allowed = Vec::new();
+        for operation in &self.operations {
+            let Some(params) = operation.matches(&path) else {
+                continue;
+            };
+            if operation.method != request.method() {
+                allowed.push(operation.method);
+                continue;
+            }
+
+            if let Err(reason) = self.validate(request, operation, &params) {
+                let mut response = Response::text(&reason);
+                response.set_status(HttpStatus::BadRequest);
+                return Ok(response);
+            }
+
+            return Ok(self.mock_response(request, operation));
+        }
+
+        match allowed.is_empty() {
+            true => Ok(Response::not_found()),
+            false => Ok(Response::method_not_allowed(&allowed)),
+        }
+    }
+}
+
+fn parse_template(template: &str) -> Result<(Vec<Option<String>>, Vec<String>), &'static str> {
+    let mut segments = Vec::new();
+    let mut params = Vec::new();
+
+    for segment in template.split('/').filter(|s| !s.is_empty()) {
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) if !name.is_empty() => {
+                params.push(name.to_string());
+                segments.push(None);
+            }
+            Some(_) => return Err("Invalid OpenAPI path template!"),
+            None => segments.push(Some(segment.to_string())),
+        }
+    }
+
+    Ok((segments, params))
+}
+
+fn same_parameter(a: &Value, b: &Value) -> bool {
+    a.get("name") == b.get("name") && a.get("in") == b.get("in")
+}
+
+/// Parameters are strings, parsed as JSON for the other scalar types
+fn parse_scalar(value: &str, schema: &Value) -> Value {
+    match schema.get("type").and_then(Value::as_str) {
+        Some("integer" | "number" | "boolean") => {
+            serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+        }
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// Media type of `content` for `accept`, JSON first when anything goes
+fn media_type_name<'a>(content: &'a Map<String, Value>, accept: Option<&str>) -> Option<&'a str> {
+    let accepted = |name: &str| {
+        accept.is_none_or(|accept| {
+            accept.split(',').any(|range| {
+                let range = range.split(';').next().unwrap_or_default().trim();
+                range == "*/*"
+                    || range == name
+                    || range
+                        .strip_suffix("/*")
+                        .is_some_and(|kind| name.starts_with(&format!("{kind}/")))
+            })
+        })
+    };
+
+    let names = content.keys().map(String::as_str);
+    names
+        .clone()
+        .find(|name| name.contains("json") && accepted(name))
+        .or_else(|| names.clone().find(|name| accepted(name)))
+        .or_else(|| content.keys().next().map(String::as_str))
+}
+
+fn media_type<'a>(
+    content: &'a Map<String, Value>,
+    content_type: Option<&str>,
+) -> Option<&'a Value> {
+    let essence = content_type.map(|t| t.split(';').next().unwrap_or_default().trim());
+    essence
+        .and_then(|essence| content.get(essence))
+        .or_else(|| content.values().next())
+}
@@ -0,0 +1,73 @@
+//! OpenTelemetry spans of the served requests
+//!
+//! Spans go to the global tracer provider and the parent context is taken from the
+//! request headers with the global propagator, both are no-ops until the application
+//! installs them.
+
+use std::net::SocketAddr;
+
+use opentelemetry::{
+    global,
+    trace::{SpanKind, Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+
+use super::{Request, Response};
+
+const TRACER_NAME: &str = "httpr";
+
+/// Context with a new server span for `request`, child of the propagated one
+pub(crate) fn request_context(request: &Request, peer: SocketAddr) -> Context {
+    let peer = request.peer_addr.unwrap_or(peer);
+    let parent = global::get_text_map_propagator(|p| p.extract(&request.headers));
+
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", request.method.to_string()),
+        KeyValue::new("client.address", peer.ip().to_string()),
+        KeyValue::new("client.port", i64::from(peer.port())),
+        KeyValue::new(
+            "network.protocol.version",
+            request.version.trim_start_matches("http/").to_string(),
+        ),
+    ];
+
+    let (path, query) = match request.uri.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.uri.as_str(), None),
+    };
+    attributes.push(KeyValue::new("url.path", path.to_string()));
+    if let Some(query) = query {
+        attributes.push(KeyValue::new("url.query", query.to_string()));
+    }
+
+    if let Some(host) = request.header("host") {
+        attributes.push(KeyValue::new("server.address", host.to_string()));
+    }
+
+    if let Some(user_agent) = request.header("user-agent") {
+        attributes.push(KeyValue::new("user_agent.original", user_agent.to_string()));
+    }
+
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(request.method.to_string())
+        .with_kind(SpanKind::Server)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+
+    parent.with_span(span)
+}
+
+/// Record the response on the request span and end it
+pub(crate) fn end_request(cx: &Context, response: &Response) {
+    let span = cx.span();
+    let code = response.status().code();
+
+    span.set_attribute(KeyValue::new("http.response.status_code", i64::from(code)));
+    if code >= 500 {
+        span.set_attribute(KeyValue::new("error.type", code.to_string()));
+        span.set_status(Status::error(response.status().description()));
+    }
+
+    span.end();
+}
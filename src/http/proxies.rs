@@ -0,0 +1,157 @@
+//! Client address resolution behind trusted proxies
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+/// IP network in CIDR notation, a bare address is a single host network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// ```
+    /// use httpr::http::proxies::IpNet;
+    ///
+    /// let net: IpNet = "10.0.0.0/8".parse().unwrap();
+    /// assert!(net.contains("10.1.2.3".parse().unwrap()));
+    /// assert!(!net.contains("192.168.0.1".parse().unwrap()));
+    /// assert!("::1".parse::<IpNet>().unwrap().contains("::1".parse().unwrap()));
+    /// ```
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| "Invalid IP address")?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| "Invalid prefix length")?,
+            None => max,
+        };
+
+        if prefix > max {
+            return Err("Invalid prefix length");
+        }
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Networks whose connections are proxies allowed to tell the client address
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn new(nets: Vec<IpNet>) -> Self {
+        Self { nets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.nets.iter().any(|net| net.contains(ip))
+    }
+
+    /// Client address of a connection from `peer`
+    ///
+    /// The hops of `Forwarded`, or `X-Forwarded-For` without it, are walked from the
+    /// nearest one while they are trusted proxies. Addresses taken from the headers
+    /// have port `0` unless `Forwarded` tells it.
+    ///
+    /// ```
+    /// use httpr::http::proxies::TrustedProxies;
+    ///
+    /// let proxies = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+    /// let peer = "10.0.0.2:5000".parse().unwrap();
+    ///
+    /// assert_eq!(
+    ///     proxies.client_addr(peer, None, Some("203.0.113.7, 10.0.0.9")),
+    ///     "203.0.113.7:0".parse().unwrap()
+    /// );
+    /// assert_eq!(
+    ///     proxies.client_addr(peer, Some("for=\"[2001:db8::1]:4711\";proto=https"), None),
+    ///     "[2001:db8::1]:4711".parse().unwrap()
+    /// );
+    /// ```
+    pub fn client_addr(
+        &self,
+        peer: SocketAddr,
+        forwarded: Option<&str>,
+        x_forwarded_for: Option<&str>,
+    ) -> SocketAddr {
+        if !self.is_trusted(peer.ip()) {
+            return peer;
+        }
+
+        let hops: Vec<Option<SocketAddr>> = match (forwarded, x_forwarded_for) {
+            (Some(forwarded), _) => forwarded.split(',').map(forwarded_for).collect(),
+            (None, Some(xff)) => xff.split(',').map(|hop| parse_node(hop.trim())).collect(),
+            (None, None) => return peer,
+        };
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            // Unknown or obfuscated hops can't be trusted further
+            let Some(hop) = hop else { break };
+
+            client = hop;
+            if !self.is_trusted(hop.ip()) {
+                break;
+            }
+        }
+
+        client
+    }
+}
+
+/// Address of the `for` parameter of a `Forwarded` element
+fn forwarded_for(element: &str) -> Option<SocketAddr> {
+    element.split(';').find_map(|pair| {
+        let (key, value) = pair.trim().split_once('=')?;
+        key.eq_ignore_ascii_case("for")
+            .then(|| parse_node(value.trim().trim_matches('"')))
+            .flatten()
+    })
+}
+
+/// `ip`, `ip:port`, `[ipv6]` or `[ipv6]:port`
+fn parse_node(node: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+
+    let ip = node.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0))
+}
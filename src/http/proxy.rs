@@ -0,0 +1,613 @@
+//! Reverse proxy to a plain HTTP upstream
+
+use std::{net::IpAddr, time::Duration};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use strum_macros::{Display, EnumString};
+use tokio::{
+    io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+use url::Url;
+
+use cache::{Lookup, ResponseCache, Validators};
+
+use super::{
+    is_token_byte, proxies::TrustedProxies, Body, HttpHandler, HttpStatus, Method, Named,
+    OnUpgrade, Request, Response,
+};
+
+/// Time allowed to connect to the upstream and get its response head
+pub const DEFAULT_PROXY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection headers, meaningful for one hop only
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Forwarding headers of the clients, only kept from the trusted proxies
+const FORWARDING: [&str; 4] = [
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+];
+
+/// Largest response head read from the upstream
+const MAX_HEAD_SIZE: u64 = 64 * 1024;
+
+mod cache;
+
+/// Headers telling the upstream about the client
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ForwardingHeaders {
+    /// `X-Forwarded-For`, `X-Forwarded-Host` and `X-Forwarded-Proto`
+    #[default]
+    XForwarded,
+    /// `Forwarded`, from RFC 7239
+    Forwarded,
+    /// Both of them
+    Both,
+    /// Neither, the upstream sees the proxy as the client
+    #[strum(serialize = "none")]
+    Omit,
+}
+
+/// Handler forwarding the requests to an `http://` upstream
+///
+/// The upstream is asked with HTTP/1.0, one connection per request, so its
+/// responses are never chunked and end with the connection at worst. Requests to
+/// `Upgrade` the connection, like WebSockets, are sent with HTTP/1.1 instead, and
+/// once the upstream switches protocols the bytes are relayed both ways. The client
+/// address is added to the forwarding headers, the ones sent by the clients are
+/// dropped unless they connect from the [TrustedProxies] of the server.
+///
+/// ```
+/// use httpr::http::{proxy::ProxyHandler, Server};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let proxy = ProxyHandler::new("http://127.0.0.1:8080/app")?;
+/// let server = Server::new(String::from("127.0.0.1:4444"), proxy);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProxyHandler {
+    /// `host:port` to connect to
+    address: String,
+    /// `Host` header of the forwarded requests
+    authority: String,
+    /// Path prefix of the forwarded requests, without trailing slash
+    base_path: String,
+    timeout: Duration,
+    preserve_host: bool,
+    forwarding: ForwardingHeaders,
+    cache: Option<ResponseCache>,
+}
+
+impl ProxyHandler {
+    /// `upstream` is an `http://` URL, its path is prepended to the request paths
+    pub fn new(upstream: &str) -> Result<Self, &'static str> {
+        let url = Url::parse(upstream).map_err(|_| "Invalid upstream URL!")?;
+        if url.scheme() != "http" {
+            return Err("Upstream must be an http:// URL!");
+        }
+
+        let host = url.host_str().ok_or("Upstream URL without host!")?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let authority = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+
+        Ok(Self {
+            address: format!("{host}:{port}"),
+            authority,
+            base_path: url.path().trim_end_matches('/').to_string(),
+            timeout: DEFAULT_PROXY_TIMEOUT,
+            preserve_host: false,
+            forwarding: ForwardingHeaders::default(),
+            cache: None,
+        })
+    }
+
+    /// Time to connect and get the response head, [DEFAULT_PROXY_TIMEOUT] by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Forward the `Host` of the client instead of the upstream one
+    pub fn with_preserve_host(mut self, preserve: bool) -> Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    /// Forwarding headers sent to the upstream, [ForwardingHeaders::XForwarded] by default
+    pub fn with_forwarding(mut self, forwarding: ForwardingHeaders) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    /// Keep up to `max_size` bytes of upstream responses in memory and answer
+    /// with them while they are fresh
+    ///
+    /// The cache is shared by all the clients, responses that are `private`, set
+    /// cookies or answer requests with credentials aren't kept.
+    pub fn with_cache(mut self, max_size: u64) -> Self {
+        self.cache = Some(ResponseCache::new(max_size));
+        self
+    }
+
+    /// Head of the forwarded request, asking to switch to the `upgrade` protocol
+    /// or to revalidate a cached response with its `validators`
+    fn request_head(
+        &self,
+        request: &Request,
+        upgrade: Option<&str>,
+        validators: Option<&Validators>,
+    ) -> String {
+        let url = request.url();
+        let target = match url.query() {
+            Some(query) => format!("{}{}?{query}", self.base_path, url.path()),
+            None => format!("{}{}", self.base_path, url.path()),
+        };
+
+        let mut head = match upgrade {
+            Some(protocol) => format!(
+                "{} {target} HTTP/1.1\r\nconnection: upgrade\r\nupgrade: {protocol}\r\n",
+                request.method()
+            ),
+            None => format!("{} {target} HTTP/1.0\r\n", request.method()),
+        };
+
+        let host = match request.header("host") {
+            Some(host) if self.preserve_host => host,
+            _ => &self.authority,
+        };
+        head.push_str(&format!("host: {host}\r\n"));
+
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                head.push_str(&format!("if-none-match: {etag}\r\n"));
+            }
+            if let Some(date) = &validators.last_modified {
+                head.push_str(&format!("if-modified-since: {date}\r\n"));
+            }
+        }
+
+        let options = connection_options(request.header("connection"));
+        for (name, value) in request.headers() {
+            if !is_hop_by_hop(name, &options) && !FORWARDING.contains(&name) && name != "host" {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+        }
+
+        // Only trusted proxies tell about the hops before them
+        let remote = request.remote_addr().map(|addr| addr.ip());
+        let is_trusted = request
+            .state::<TrustedProxies>()
+            .zip(remote)
+            .is_some_and(|(proxies, ip)| proxies.is_trusted(ip));
+        let previous = |name: &str| request.header(name).filter(|_| is_trusted);
+
+        let host = previous("x-forwarded-host").or(request.header("host"));
+        let proto = previous("x-forwarded-proto").unwrap_or("http");
+
+        if matches!(
+            self.forwarding,
+            ForwardingHeaders::XForwarded | ForwardingHeaders::Both
+        ) {
+            if let Some(ip) = remote {
+                let forwarded_for = match previous("x-forwarded-for") {
+                    Some(previous) => format!("{previous}, {ip}"),
+                    None => ip.to_string(),
+                };
+                head.push_str(&format!("x-forwarded-for: {forwarded_for}\r\n"));
+            }
+            if let Some(host) = host {
+                head.push_str(&format!("x-forwarded-host: {host}\r\n"));
+            }
+            head.push_str(&format!("x-forwarded-proto: {proto}\r\n"));
+        }
+
+        if matches!(
+            self.forwarding,
+            ForwardingHeaders::Forwarded | ForwardingHeaders::Both
+        ) {
+            let mut element = Vec::new();
+            if let Some(ip) = remote {
+                element.push(format!("for={}", forwarded_node(ip)));
+            }
+            if let Some(host) = request.header("host") {
+                element.push(format!("host={}", forwarded_value(host)));
+            }
+            element.push(String::from("proto=http"));
+
+            let element = element.join(";");
+            let forwarded = match previous("forwarded") {
+                Some(previous) => format!("{previous}, {element}"),
+                None => element,
+            };
+            head.push_str(&format!("forwarded: {forwarded}\r\n"));
+        }
+
+        head.push_str("\r\n");
+        head
+    }
+
+    /// Response from the cache, or from the upstream and then cached
+    async fn exchange(&self, request: &Request) -> io::Result<Response> {
+        let Some(cache) = &self.cache else {
+            return self.forward(request, None).await;
+        };
+
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            let response = self.forward(request, None).await?;
+            if response.status().code() < 400 {
+                cache.invalidate(request);
+            }
+            return Ok(response);
+        }
+
+        // The conditional requests of the client are its own to answer
+        let is_conditional = request.header("if-none-match").is_some()
+            || request.header("if-modified-since").is_some();
+        let validators = match cache.lookup(request) {
+            Lookup::Fresh(response) => return Ok(response),
+            Lookup::Stale(validators) if !is_conditional => Some(validators),
+            _ => None,
+        };
+
+        let mut response = self.forward(request, validators.as_ref()).await?;
+        if validators.is_some() && response.status() == HttpStatus::NotModified {
+            if let Some(refreshed) = cache.refresh(request, &response) {
+                return Ok(refreshed);
+            }
+        }
+
+        cache.store(request, &mut response).await;
+        Ok(response)
+    }
+
+    async fn forward(
+        &self,
+        request: &Request,
+        validators: Option<&Validators>,
+    ) -> io::Result<Response> {
+        let mut upstream = TcpStream::connect(&self.address).await?;
+
+        // Only asked when the client connection can be handed over
+        let on_upgrade = requested_upgrade(request).and_then(|_| request.on_upgrade());
+        let upgrade = requested_upgrade(request).filter(|_| on_upgrade.is_some());
+
+        upstream
+            .write_all(self.request_head(request, upgrade, validators).as_bytes())
+            .await?;
+        match request.body_stream() {
+            Some(mut body) => {
+                io::copy(&mut body, &mut upstream).await?;
+            }
+            None => upstream.write_all(request.body()).await?,
+        }
+        upstream.flush().await?;
+
+        let mut reader = BufReader::new(upstream);
+        let mut response = read_response_head(&mut reader).await?;
+
+        if response.status() == HttpStatus::SwitchingProtocols {
+            let Some(on_upgrade) = on_upgrade.filter(|_| response.header("upgrade").is_some())
+            else {
+                return Err(invalid("Unasked upstream protocol switch"));
+            };
+            response.add_header(("Connection", "upgrade"));
+            tokio::spawn(tunnel(on_upgrade, reader));
+            return Ok(response);
+        }
+
+        let len = match response.remove_header("content-length") {
+            Some(len) => Some(
+                len.trim()
+                    .parse()
+                    .map_err(|_| invalid("Invalid upstream Content-Length"))?,
+            ),
+            None => None,
+        };
+
+        let is_head = request.method() == Method::Head;
+        let body = match len {
+            _ if matches!(response.status().code(), 100..=199 | 204 | 304) => {
+                Body::Bytes(Vec::new())
+            }
+            // Announced to the client, never read
+            Some(len) if is_head => Body::stream(io::empty(), len),
+            Some(len) => Body::stream(reader, len),
+            None if is_head => Body::Bytes(Vec::new()),
+            None => Body::unbounded(reader),
+        };
+        response.set_body(body);
+
+        Ok(response)
+    }
+}
+
+impl Named for ProxyHandler {}
+
+#[async_trait]
+impl HttpHandler for ProxyHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        match timeout(self.timeout, self.exchange(request)).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => {
+                warn!("Upstream {} failed: {e}", self.address);
+                Ok(Response::new(HttpStatus::BadGateway))
+            }
+            Err(_) => {
+                warn!("Upstream {} timed out", self.address);
+                Ok(Response::new(HttpStatus::GatewayTimeout))
+            }
+        }
+    }
+}
+
+/// Protocol the client asks to switch to, with `Upgrade` and `Connection: upgrade`
+fn requested_upgrade(request: &Request) -> Option<&str> {
+    let options = connection_options(request.header("connection"));
+    request
+        .header("upgrade")
+        .filter(|_| options.iter().any(|option| option == "upgrade"))
+}
+
+/// Relay the bytes between the client and the upstream once they switched protocols
+async fn tunnel(on_upgrade: OnUpgrade, upstream: BufReader<TcpStream>) {
+    let (mut client_reader, mut client_writer) = match on_upgrade.await {
+        Ok(upgraded) => upgraded.into_parts(),
+        Err(e) => return warn!("{e}"),
+    };
+    let (mut upstream_reader, mut upstream_writer) = io::split(upstream);
+
+    let to_upstream = async {
+        io::copy(&mut client_reader, &mut upstream_writer).await?;
+        upstream_writer.shutdown().await
+    };
+    let to_client = async {
+        io::copy(&mut upstream_reader, &mut client_writer).await?;
+        client_writer.shutdown().await
+    };
+
+    if let Err(e) = tokio::try_join!(to_upstream, to_client) {
+        debug!("Upgraded connection closed: {e}");
+    }
+}
+
+/// `for` node of a `Forwarded` element, IPv6 ones bracketed and quoted
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("\"[{ip}]\""),
+    }
+}
+
+/// `Forwarded` parameter value, quoted unless it is a token
+fn forwarded_value(value: &str) -> String {
+    if !value.is_empty() && value.bytes().all(is_token_byte) {
+        return value.to_string();
+    }
+
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Lowercase header names listed by a `Connection` header, RFC 9110 section 7.6.1
+fn connection_options(connection: Option<&str>) -> Vec<String> {
+    connection
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Whether the header `name` is only for the hop it came from
+fn is_hop_by_hop(name: &str, options: &[String]) -> bool {
+    HOP_BY_HOP.contains(&name) || options.iter().any(|option| option == name)
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+async fn read_response_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Response> {
+    let mut head = reader.take(MAX_HEAD_SIZE);
+    let mut line = String::new();
+
+    head.read_line(&mut line).await?;
+    let mut parts = line.trim_end().splitn(3, ' ');
+    let status = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/1.") => code
+            .parse()
+            .ok()
+            .and_then(HttpStatus::custom)
+            .ok_or_else(|| invalid("Invalid upstream status"))?,
+        _ => return Err(invalid("Invalid upstream status line")),
+    };
+
+    let mut response = Response::new(status);
+    if let Some(reason) = parts.next() {
+        response.set_reason(reason);
+    }
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if head.read_line(&mut line).await? == 0 {
+            return Err(invalid("Upstream response head too long or cut"));
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("Invalid upstream header"))?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+
+    // Never asked with HTTP/1.0, and the body couldn't be decoded
+    let is_switching = response.status() == HttpStatus::SwitchingProtocols;
+    if !is_switching && headers.iter().any(|(name, _)| name == "transfer-encoding") {
+        return Err(invalid("Upstream response with a Transfer-Encoding"));
+    }
+
+    let connection = headers
+        .iter()
+        .filter(|(name, _)| name == "connection")
+        .map(|(_, value)| value.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    let options = connection_options(Some(&connection));
+
+    // Repeated headers are kept apart, `Set-Cookie` can't be joined. The protocol
+    // switched to is told to the client as well
+    for (name, value) in &headers {
+        if !is_hop_by_hop(name, &options) || (is_switching && name == "upgrade") {
+            response.append_header((name, value));
+        }
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn request(remote: &str, headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::new(Method::Get, String::from("/"), String::from("http/1.1"));
+        request.remote_addr = Some(remote.parse().unwrap());
+        for (name, value) in headers {
+            request.headers.insert(name.to_string(), value.to_string());
+        }
+        request
+    }
+
+    fn trusting(mut request: Request, net: &str) -> Request {
+        let proxies = TrustedProxies::new(vec![net.parse().unwrap()]);
+        request.extensions.insert_arc(Arc::new(proxies));
+        request
+    }
+
+    fn header<'a>(head: &'a str, name: &str) -> Vec<&'a str> {
+        head.lines()
+            .filter_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+            .collect()
+    }
+
+    const SPOOFED: [(&str, &str); 4] = [
+        ("host", "example.com"),
+        ("x-forwarded-for", "10.0.0.1"),
+        ("x-forwarded-proto", "https"),
+        ("forwarded", "for=10.0.0.1"),
+    ];
+
+    #[test]
+    fn drops_the_forwarding_headers_of_clients() {
+        let proxy = ProxyHandler::new("http://127.0.0.1:8080")
+            .unwrap()
+            .with_forwarding(ForwardingHeaders::Both);
+        let head = proxy.request_head(&request("203.0.113.7:5000", &SPOOFED), None, None);
+
+        assert_eq!(header(&head, "x-forwarded-for"), ["203.0.113.7"]);
+        assert_eq!(header(&head, "x-forwarded-host"), ["example.com"]);
+        assert_eq!(header(&head, "x-forwarded-proto"), ["http"]);
+        assert_eq!(
+            header(&head, "forwarded"),
+            ["for=203.0.113.7;host=example.com;proto=http"]
+        );
+    }
+
+    #[test]
+    fn extends_the_forwarding_headers_of_trusted_proxies() {
+        let proxy = ProxyHandler::new("http://127.0.0.1:8080")
+            .unwrap()
+            .with_forwarding(ForwardingHeaders::Both);
+        let request = trusting(request("[::1]:5000", &SPOOFED), "::1");
+        let head = proxy.request_head(&request, None, None);
+
+        assert_eq!(header(&head, "x-forwarded-for"), ["10.0.0.1, ::1"]);
+        assert_eq!(header(&head, "x-forwarded-proto"), ["https"]);
+        assert_eq!(
+            header(&head, "forwarded"),
+            ["for=10.0.0.1, for=\"[::1]\";host=example.com;proto=http"]
+        );
+    }
+
+    #[test]
+    fn drops_the_headers_listed_by_connection() {
+        let client = request(
+            "203.0.113.7:5000",
+            &[
+                ("connection", "close, X-Hop"),
+                ("x-hop", "1"),
+                ("x-kept", "1"),
+            ],
+        );
+        let head = ProxyHandler::new("http://127.0.0.1:8080")
+            .unwrap()
+            .request_head(&client, None, None);
+
+        assert!(header(&head, "x-hop").is_empty());
+        assert!(header(&head, "connection").is_empty());
+        assert_eq!(header(&head, "x-kept"), ["1"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_repeated_upstream_headers() {
+        let head = "HTTP/1.1 200 OK\r\n\
+            Set-Cookie: a=1\r\n\
+            Connection: close\r\n\
+            Set-Cookie: b=2\r\n\
+            Connection: x-hop\r\n\
+            X-Hop: 1\r\n\
+            Keep-Alive: timeout=5\r\n\r\n";
+        let response = read_response_head(&mut head.as_bytes()).await.unwrap();
+        let headers = response.headers().collect::<Vec<_>>();
+
+        assert_eq!(headers, [("set-cookie", "a=1"), ("set-cookie", "b=2")]);
+    }
+
+    #[test]
+    fn sends_the_chosen_forwarding_headers() {
+        let client = request("203.0.113.7:5000", &[("host", "example.com:4444")]);
+
+        let proxy = ProxyHandler::new("http://127.0.0.1:8080").unwrap();
+        let head = proxy.request_head(&client, None, None);
+        assert!(header(&head, "forwarded").is_empty());
+        assert_eq!(header(&head, "x-forwarded-for"), ["203.0.113.7"]);
+
+        let proxy = proxy.with_forwarding(ForwardingHeaders::Forwarded);
+        let head = proxy.request_head(&client, None, None);
+        assert!(header(&head, "x-forwarded-for").is_empty());
+        assert_eq!(
+            header(&head, "forwarded"),
+            ["for=203.0.113.7;host=\"example.com:4444\";proto=http"]
+        );
+
+        let proxy = proxy.with_forwarding(ForwardingHeaders::Omit);
+        let head = proxy.request_head(&client, None, None);
+        assert!(FORWARDING.iter().all(|name| header(&head, name).is_empty()));
+    }
+}
@@ -0,0 +1,496 @@
+//! Shared cache of the upstream responses, after RFC 9111
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::io::AsyncReadExt;
+
+use super::super::{
+    conditional::{none_match, parse_http_date},
+    Body, HttpStatus, Method, Request, Response,
+};
+
+/// Statuses cached when the upstream gives them a lifetime, RFC 9110 section 15.1
+const CACHEABLE_STATUSES: [u16; 10] = [200, 203, 204, 300, 301, 404, 405, 410, 414, 501];
+
+/// Headers of the upstream response that aren't stored
+const UNSTORED_HEADERS: [&str; 2] = ["age", "content-length"];
+
+/// Validators of a stale response, sent upstream to revalidate it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Answer of the cache to a request
+pub(super) enum Lookup {
+    Fresh(Response),
+    Stale(Validators),
+    Miss,
+}
+
+struct CachedResponse {
+    status: HttpStatus,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// Values of the request headers named by `Vary`
+    vary: Vec<(String, Option<String>)>,
+    /// Age when received, from the `Age` of the upstream
+    initial_age: Duration,
+    received: Instant,
+    lifetime: Duration,
+}
+
+impl CachedResponse {
+    fn age(&self) -> Duration {
+        self.initial_age + self.received.elapsed()
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.age() < self.lifetime
+    }
+
+    fn size(&self) -> u64 {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(k, v)| k.len() + v.len())
+            .sum::<usize>();
+        (self.body.len() + headers) as u64
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request.header(name) == value.as_deref())
+    }
+
+    fn validators(&self) -> Validators {
+        Validators {
+            etag: self.header("etag").map(String::from),
+            last_modified: self.header("last-modified").map(String::from),
+        }
+    }
+
+    fn response(&self, request: &Request) -> Response {
+        let is_not_modified = self
+            .header("etag")
+            .zip(request.header("if-none-match"))
+            .is_some_and(|(etag, tags)| none_match(tags, etag));
+
+        let mut response = match is_not_modified {
+            true => Response::new(HttpStatus::NotModified),
+            false => {
+                let mut response = Response::new(self.status);
+                response.add_body(&self.body);
+                response
+            }
+        };
+        for (name, value) in &self.headers {
+            response.append_header((name, value));
+        }
+        response.add_header(("Age", &self.age().as_secs().to_string()));
+
+        response
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Variants of each target
+    responses: HashMap<String, Vec<CachedResponse>>,
+    size: u64,
+}
+
+/// Responses of the `GET` requests kept in memory, `max_size` bytes at most
+///
+/// Only the responses given a lifetime by `Cache-Control` or `Expires` are
+/// stored, with their `Vary` request headers. Stale ones are revalidated with
+/// their `ETag` or `Last-Modified`, and the unsafe requests to a target drop it.
+/// The responses setting cookies, `private` or asked with credentials are never
+/// stored, the cache is shared by every client.
+pub(super) struct ResponseCache {
+    max_size: u64,
+    entries: Mutex<Entries>,
+}
+
+impl ResponseCache {
+    pub fn new(max_size: u64) -> Self {
+        Self {
+            max_size,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub fn lookup(&self, request: &Request) -> Lookup {
+        let directives = CacheControl::parse(request.header("cache-control"));
+        if !matches!(request.method(), Method::Get | Method::Head) || directives.no_store {
+            return Lookup::Miss;
+        }
+
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let cached = entries
+            .responses
+            .get(&key(request))
+            .and_then(|variants| variants.iter().find(|cached| cached.matches(request)));
+
+        match cached {
+            Some(cached) if cached.is_fresh() && !directives.no_cache => {
+                Lookup::Fresh(cached.response(request))
+            }
+            Some(cached) => Lookup::Stale(cached.validators()),
+            None => Lookup::Miss,
+        }
+    }
+
+    /// Keep `response` if it can be reused, its body is read into memory for it
+    pub async fn store(&self, request: &Request, response: &mut Response) {
+        let Some(lifetime) = storable_lifetime(request, response) else {
+            return;
+        };
+
+        let body = match response.take_body() {
+            Body::Bytes(body) => body,
+            Body::Stream { reader, len } if len <= self.max_size => {
+                let mut body = Vec::with_capacity(len as usize);
+                if reader.take(len).read_to_end(&mut body).await.is_err() {
+                    response.set_body(Body::Bytes(body));
+                    return;
+                }
+                body
+            }
+            body => {
+                response.set_body(body);
+                return;
+            }
+        };
+        response.set_body(Body::Bytes(body.clone()));
+
+        let vary = response
+            .header("vary")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = request.header(&name).map(String::from);
+                (name, value)
+            })
+            .collect();
+
+        let cached = CachedResponse {
+            status: response.status(),
+            headers: stored_headers(response),
+            body,
+            vary,
+            initial_age: age(response),
+            received: Instant::now(),
+            lifetime,
+        };
+        self.insert(key(request), cached);
+    }
+
+    /// Cached response of `request` refreshed by the `304` of its revalidation
+    pub fn refresh(&self, request: &Request, not_modified: &Response) -> Option<Response> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let cached = entries
+            .responses
+            .get_mut(&key(request))?
+            .iter_mut()
+            .find(|cached| cached.matches(request))?;
+        let previous_size = cached.size();
+
+        for (name, value) in stored_headers(not_modified) {
+            cached.headers.retain(|(k, _)| *k != name);
+            cached.headers.push((name, value));
+        }
+        let mut refreshed = Response::new(cached.status);
+        for (name, value) in &cached.headers {
+            refreshed.append_header((name, value));
+        }
+        cached.lifetime = lifetime(
+            &refreshed,
+            &CacheControl::parse(refreshed.header("cache-control")),
+        )
+        .unwrap_or_default();
+        cached.initial_age = age(not_modified);
+        cached.received = Instant::now();
+
+        let response = cached.response(request);
+        let size = cached.size();
+        entries.size = entries.size - previous_size + size;
+        Some(response)
+    }
+
+    /// Drop the responses of the target of `request`
+    pub fn invalidate(&self, request: &Request) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(variants) = entries.responses.remove(&key(request)) {
+            entries.size -= variants.iter().map(CachedResponse::size).sum::<u64>();
+        }
+    }
+
+    fn insert(&self, key: String, cached: CachedResponse) {
+        let size = cached.size();
+        if size > self.max_size {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let variants = entries.responses.entry(key).or_default();
+        let replaced = variants
+            .iter()
+            .position(|variant| variant.vary == cached.vary)
+            .map(|i| variants.remove(i).size())
+            .unwrap_or_default();
+        variants.push(cached);
+        entries.size = entries.size - replaced + size;
+
+        // Stale responses go first, then everything
+        if entries.size > self.max_size {
+            for variants in entries.responses.values_mut() {
+                variants.retain(CachedResponse::is_fresh);
+            }
+            entries.responses.retain(|_, variants| !variants.is_empty());
+            entries.size = entries
+                .responses
+                .values()
+                .flatten()
+                .map(CachedResponse::size)
+                .sum();
+        }
+        if entries.size > self.max_size {
+            *entries = Entries::default();
+        }
+    }
+}
+
+/// `Cache-Control` directives the cache cares about
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(value: Option<&str>) -> Self {
+        let mut directives = Self::default();
+
+        for directive in value.unwrap_or_default().split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = argument.and_then(|argument| argument.parse().ok());
+
+            match name.trim().to_lowercase().as_str() {
+                "no-store" => directives.no_store = true,
+                "no-cache" => directives.no_cache = true,
+                "private" => directives.private = true,
+                "public" => directives.public = true,
+                "max-age" => directives.max_age = seconds,
+                "s-maxage" => directives.s_maxage = seconds,
+                _ => {}
+            }
+        }
+
+        directives
+    }
+}
+
+/// Lifetime of `response` if it can be stored for `request`
+fn storable_lifetime(request: &Request, response: &Response) -> Option<Duration> {
+    let asked = CacheControl::parse(request.header("cache-control"));
+    let directives = CacheControl::parse(response.header("cache-control"));
+    let is_shareable = request.header("authorization").is_none()
+        || directives.public
+        || directives.s_maxage.is_some();
+
+    let is_storable = request.method() == Method::Get
+        && CACHEABLE_STATUSES.contains(&response.status().code())
+        && !asked.no_store
+        && !directives.no_store
+        && !directives.private
+        && is_shareable
+        && response.header("set-cookie").is_none()
+        && response
+            .header("vary")
+            .is_none_or(|vary| vary.trim() != "*");
+    if !is_storable {
+        return None;
+    }
+
+    // Without a lifetime it would need revalidating at every use
+    let lifetime = lifetime(response, &directives)?;
+    let has_validators =
+        response.header("etag").is_some() || response.header("last-modified").is_some();
+    (!lifetime.is_zero() || has_validators).then_some(lifetime)
+}
+
+/// Lifetime given by the upstream, `None` without any
+fn lifetime(response: &Response, directives: &CacheControl) -> Option<Duration> {
+    if directives.no_cache {
+        return Some(Duration::ZERO);
+    }
+    if let Some(seconds) = directives.s_maxage.or(directives.max_age) {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let expires = response.header("expires")?;
+    let date = response
+        .header("date")
+        .and_then(parse_http_date)
+        .unwrap_or_else(SystemTime::now);
+    // Invalid dates, like `0`, are in the past
+    let lifetime = parse_http_date(expires)
+        .and_then(|expires| expires.duration_since(date).ok())
+        .unwrap_or_default();
+    Some(lifetime)
+}
+
+fn age(response: &Response) -> Duration {
+    let seconds = response
+        .header("age")
+        .and_then(|age| age.trim().parse().ok());
+    Duration::from_secs(seconds.unwrap_or_default())
+}
+
+fn stored_headers(response: &Response) -> Vec<(String, String)> {
+    response
+        .headers()
+        .filter(|(name, _)| !UNSTORED_HEADERS.contains(name))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Target of the request on the host it was sent to
+fn key(request: &Request) -> String {
+    let url = request.url();
+    let host = request.header("host").unwrap_or_default();
+    match url.query() {
+        Some(query) => format!("{host}{}?{query}", url.path()),
+        None => format!("{host}{}", url.path()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::new(Method::Get, String::from("/a"), String::from("http/1.1"));
+        for (name, value) in headers {
+            request.headers.insert(name.to_string(), value.to_string());
+        }
+        request
+    }
+
+    fn response(headers: &[(&str, &str)]) -> Response {
+        let mut response = Response::text("cached");
+        for header in headers {
+            response.add_header(*header);
+        }
+        response
+    }
+
+    type Headers = &'static [(&'static str, &'static str)];
+
+    #[test]
+    fn lifetimes() {
+        let cases: [(Headers, Option<u64>); 9] = [
+            (&[("cache-control", "max-age=60")], Some(60)),
+            (&[("cache-control", "max-age=60, s-maxage=10")], Some(10)),
+            (&[("cache-control", "public, max-age=\"5\"")], Some(5)),
+            (
+                &[
+                    ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                    ("expires", "Sun, 06 Nov 1994 08:50:37 GMT"),
+                ],
+                Some(60),
+            ),
+            (&[("expires", "0"), ("etag", "\"a\"")], Some(0)),
+            (&[("cache-control", "no-cache"), ("etag", "\"a\"")], Some(0)),
+            (&[("cache-control", "no-cache")], None),
+            (&[("cache-control", "max-age=60, private")], None),
+            (&[], None),
+        ];
+
+        for (headers, expected) in cases {
+            let lifetime = storable_lifetime(&request(&[]), &response(headers));
+            assert_eq!(lifetime, expected.map(Duration::from_secs), "{headers:?}");
+        }
+    }
+
+    #[test]
+    fn shared_responses_only() {
+        let fresh = [("cache-control", "max-age=60")];
+        let cookie = response(&[fresh[0], ("set-cookie", "a=1")]);
+        assert_eq!(storable_lifetime(&request(&[]), &cookie), None);
+
+        let credentials = request(&[("authorization", "Basic YTpi")]);
+        assert_eq!(storable_lifetime(&credentials, &response(&fresh)), None);
+        let public = response(&[("cache-control", "public, max-age=60")]);
+        assert!(storable_lifetime(&credentials, &public).is_some());
+
+        let everything = response(&[fresh[0], ("vary", "*")]);
+        assert_eq!(storable_lifetime(&request(&[]), &everything), None);
+    }
+
+    #[tokio::test]
+    async fn serves_the_matching_variant() {
+        let cache = ResponseCache::new(1024);
+        let english = request(&[("accept-language", "en")]);
+        let french = request(&[("accept-language", "fr")]);
+
+        let mut response =
+            response(&[("cache-control", "max-age=60"), ("vary", "Accept-Language")]);
+        cache.store(&english, &mut response).await;
+        assert_eq!(response.body().len(), 6);
+
+        let Lookup::Fresh(hit) = cache.lookup(&english) else {
+            panic!("English variant not cached");
+        };
+        assert_eq!(hit.header("age"), Some("0"));
+        assert!(matches!(cache.lookup(&french), Lookup::Miss));
+
+        cache.invalidate(&english);
+        assert!(matches!(cache.lookup(&english), Lookup::Miss));
+    }
+
+    #[tokio::test]
+    async fn revalidates_the_stale_responses() {
+        let cache = ResponseCache::new(1024);
+        let mut stale = response(&[("cache-control", "no-cache"), ("etag", "\"a\"")]);
+        cache.store(&request(&[]), &mut stale).await;
+
+        let Lookup::Stale(validators) = cache.lookup(&request(&[])) else {
+            panic!("Response without lifetime served");
+        };
+        assert_eq!(validators.etag.as_deref(), Some("\"a\""));
+
+        let not_modified = response(&[("cache-control", "max-age=60"), ("etag", "\"a\"")]);
+        let refreshed = cache.refresh(&request(&[]), &not_modified).unwrap();
+        assert_eq!(refreshed.status(), HttpStatus::Ok);
+        assert!(matches!(cache.lookup(&request(&[])), Lookup::Fresh(_)));
+
+        let Lookup::Fresh(hit) = cache.lookup(&request(&[("if-none-match", "\"a\"")])) else {
+            panic!("Refreshed response not cached");
+        };
+        assert_eq!(hit.status(), HttpStatus::NotModified);
+    }
+}
@@ -0,0 +1,283 @@
+//! `Range` header parsing
+
+/// Inclusive range of bytes of a representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Ranges hold at least one byte
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// `Content-Range` value for a representation of `size` bytes
+    pub fn content_range(&self, size: u64) -> String {
+        format!("bytes {}-{}/{size}", self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeError {
+    /// Malformed header or unknown unit, it must be ignored
+    Invalid,
+    /// None of the ranges overlap the representation, answer `416`
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for a representation of `size` bytes
+///
+/// Ends beyond the representation are clamped and the ranges that don't overlap it
+/// are dropped.
+///
+/// ```
+/// use httpr::http::range::{parse_byte_ranges, ByteRange, RangeError};
+///
+/// assert_eq!(
+///     parse_byte_ranges("bytes=0-99, -10", 1000),
+///     Ok(vec![ByteRange { start: 0, end: 99 }, ByteRange { start: 990, end: 999 }])
+/// );
+/// assert_eq!(parse_byte_ranges("bytes=2000-", 1000), Err(RangeError::Unsatisfiable));
+/// assert_eq!(parse_byte_ranges("items=0-1", 1000), Err(RangeError::Invalid));
+/// ```
+pub fn parse_byte_ranges(header: &str, size: u64) -> Result<Vec<ByteRange>, RangeError> {
+    let specs = header
+        .trim()
+        .strip_prefix("bytes=")
+        .ok_or(RangeError::Invalid)?;
+
+    let mut ranges = Vec::new();
+    let mut specs = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .peekable();
+
+    if specs.peek().is_none() {
+        return Err(RangeError::Invalid);
+    }
+
+    for spec in specs {
+        let (start, end) = spec.split_once('-').ok_or(RangeError::Invalid)?;
+        let (start, end) = (start.trim(), end.trim());
+
+        let range = if start.is_empty() {
+            // Suffix range, the last `end` bytes
+            let suffix = parse_position(end)?;
+            if suffix == 0 || size == 0 {
+                continue;
+            }
+
+            ByteRange {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            }
+        } else {
+            let start = parse_position(start)?;
+            let end = match end {
+                "" => u64::MAX,
+                e => parse_position(e)?,
+            };
+
+            if end < start {
+                return Err(RangeError::Invalid);
+            }
+
+            if start >= size {
+                continue;
+            }
+
+            ByteRange {
+                start,
+                end: end.min(size - 1),
+            }
+        };
+
+        ranges.push(range);
+    }
+
+    if ranges.is_empty() {
+        return Err(RangeError::Unsatisfiable);
+    }
+
+    Ok(ranges)
+}
+
+/// Only digits, unlike [str::parse] that takes a leading `+`
+fn parse_position(position: &str) -> Result<u64, RangeError> {
+    if !position.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RangeError::Invalid);
+    }
+
+    position.parse().map_err(|_| RangeError::Invalid)
+}
+
+/// Sort the ranges and merge the ones that overlap or are adjacent
+///
+/// ```
+/// use httpr::http::range::{coalesce_ranges, ByteRange};
+///
+/// let ranges = vec![
+///     ByteRange { start: 50, end: 99 },
+///     ByteRange { start: 0, end: 9 },
+///     ByteRange { start: 10, end: 60 },
+///     ByteRange { start: 200, end: 299 },
+/// ];
+///
+/// assert_eq!(
+///     coalesce_ranges(ranges),
+///     vec![ByteRange { start: 0, end: 99 }, ByteRange { start: 200, end: 299 }]
+/// );
+/// ```
+pub fn coalesce_ranges(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Headers of a `multipart/byteranges` part, preceded by its boundary delimiter
+pub fn multipart_part_head(
+    boundary: &str,
+    content_type: &str,
+    range: &ByteRange,
+    size: u64,
+) -> String {
+    format!(
+        "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+        range.content_range(size)
+    )
+}
+
+/// Closing delimiter of a `multipart/byteranges` body
+pub fn multipart_end(boundary: &str) -> String {
+    format!("\r\n--{boundary}--\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u64 = 1000;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn parses_satisfiable_ranges() {
+        let cases = [
+            ("bytes=0-0", vec![range(0, 0)]),
+            ("bytes=990-", vec![range(990, 999)]),
+            ("bytes=500-5000", vec![range(500, 999)]),
+            ("bytes=-10", vec![range(990, 999)]),
+            ("bytes=-1000", vec![range(0, 999)]),
+            ("bytes=-5000", vec![range(0, 999)]),
+            (" bytes= 1 - 2 ,, ", vec![range(1, 2)]),
+            ("bytes=-0, 0-0", vec![range(0, 0)]),
+            ("bytes=0-9, 1000-, 2000-3000", vec![range(0, 9)]),
+            ("bytes=0-499, 400-599", vec![range(0, 499), range(400, 599)]),
+            ("bytes=0-18446744073709551615", vec![range(0, 999)]),
+            ("bytes=-18446744073709551615", vec![range(0, 999)]),
+        ];
+
+        for (header, expected) in cases {
+            assert_eq!(parse_byte_ranges(header, SIZE), Ok(expected), "{header}");
+        }
+    }
+
+    #[test]
+    fn refuses_unsatisfiable_ranges() {
+        let cases = [
+            ("bytes=-0", SIZE),
+            ("bytes=1000-", SIZE),
+            ("bytes=1000-1000", SIZE),
+            ("bytes=-0, 1000-, 2000-3000", SIZE),
+            ("bytes=18446744073709551615-", SIZE),
+            ("bytes=0-", 0),
+            ("bytes=-10", 0),
+        ];
+
+        for (header, size) in cases {
+            assert_eq!(
+                parse_byte_ranges(header, size),
+                Err(RangeError::Unsatisfiable),
+                "{header} of {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_invalid_ranges() {
+        let cases = [
+            "",
+            "bytes=",
+            "bytes=,",
+            "items=0-1",
+            "bytes 0-1",
+            "bytes=1",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=--1",
+            "bytes=5-1",
+            "bytes=0-1, 5-1",
+            "bytes=0-1, x",
+            "bytes=+1-2",
+            "bytes=18446744073709551616-",
+            "bytes=0-18446744073709551616",
+            "bytes=-18446744073709551616",
+            "bytes=99999999999999999999999999-",
+        ];
+
+        for header in cases {
+            assert_eq!(
+                parse_byte_ranges(header, SIZE),
+                Err(RangeError::Invalid),
+                "{header}"
+            );
+        }
+    }
+
+    #[test]
+    fn coalesces_overlapping_and_adjacent_ranges() {
+        let cases = [
+            (vec![range(0, 9)], vec![range(0, 9)]),
+            (vec![range(0, 9), range(10, 19)], vec![range(0, 19)]),
+            (
+                vec![range(0, 9), range(11, 19)],
+                vec![range(0, 9), range(11, 19)],
+            ),
+            (vec![range(5, 50), range(0, 9)], vec![range(0, 50)]),
+            (vec![range(0, 99), range(10, 19)], vec![range(0, 99)]),
+            (vec![range(3, 3), range(3, 3)], vec![range(3, 3)]),
+            (
+                vec![range(20, 29), range(0, 4), range(5, 9), range(30, 30)],
+                vec![range(0, 9), range(20, 30)],
+            ),
+            (
+                vec![range(0, u64::MAX), range(u64::MAX, u64::MAX)],
+                vec![range(0, u64::MAX)],
+            ),
+        ];
+
+        for (ranges, expected) in cases {
+            let debug = format!("{ranges:?}");
+            assert_eq!(coalesce_ranges(ranges), expected, "{debug}");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Recording of the exchanges of a handler, replayed later as stubs
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use super::{Body, HttpHandler, HttpStatus, Method, Named, Request, Response};
+
+/// Largest response body recorded, bigger responses are let through unrecorded
+pub const MAX_RECORDED_BODY_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Headers set again when the response is written
+const SKIPPED_HEADERS: [&str; 2] = ["content-length", "transfer-encoding"];
+
+/// Request and its response, a line of a recording file
+///
+/// Bodies are kept as text when they are UTF-8 and in base64 otherwise.
+///
+/// ```json
+/// {"method":"GET","path":"/api/users","status":200,"headers":{"content-type":"application/json"},"body":"[]"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl RecordedExchange {
+    fn new(request: &Request, response: &Response, body: &[u8]) -> Self {
+        let url = request.url();
+        let (body, body_base64) = match std::str::from_utf8(body) {
+            Ok("") => (None, None),
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (None, Some(BASE64_STANDARD.encode(body))),
+        };
+
+        Self {
+            method: request.method().to_string(),
+            path: url.path().to_string(),
+            query: url.query().map(String::from),
+            request_body: request.body_string().ok().filter(|b| !b.is_empty()),
+            status: response.status().code(),
+            headers: response
+                .headers()
+                .filter(|(name, _)| !SKIPPED_HEADERS.contains(name))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body,
+            body_base64,
+        }
+    }
+
+    fn response(&self) -> Result<Response, &'static str> {
+        let status = HttpStatus::custom(self.status).ok_or("Invalid recorded status")?;
+        let mut response = Response::new(status);
+
+        for (name, value) in &self.headers {
+            if !SKIPPED_HEADERS.contains(&name.to_lowercase().as_str()) {
+                response.add_header((name, value));
+            }
+        }
+
+        match (&self.body, &self.body_base64) {
+            (Some(body), _) => response.add_body(body.as_bytes()),
+            (None, Some(body)) => {
+                let body = BASE64_STANDARD
+                    .decode(body)
+                    .map_err(|_| "Invalid recorded base64 body")?;
+                response.add_body(&body);
+            }
+            (None, None) => {}
+        }
+
+        Ok(response)
+    }
+}
+
+/// Handler recording the exchanges of another one, in JSON lines
+///
+/// Each answered request is appended to the file as a [RecordedExchange], for a
+/// [ReplayHandler]. `HEAD` requests and responses without a known length or over
+/// [MAX_RECORDED_BODY_SIZE] are left out.
+///
+/// ```no_run
+/// use httpr::http::{proxy::ProxyHandler, recording::RecordingHandler, Server};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let proxy = ProxyHandler::new("http://127.0.0.1:8080")?;
+/// let recording = RecordingHandler::new(proxy, "api.jsonl")?;
+/// let server = Server::new(String::from("127.0.0.1:4444"), recording);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RecordingHandler<H> {
+    handler: H,
+    file: Mutex<File>,
+}
+
+impl<H: HttpHandler> RecordingHandler<H> {
+    /// Record the exchanges of `handler` after the ones already in `path`
+    pub fn new<P: AsRef<Path>>(handler: H, path: P) -> Result<Self, &'static str> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|_| "Can't open the recording file")?;
+
+        Ok(Self {
+            handler,
+            file: Mutex::new(file),
+        })
+    }
+
+    fn record(&self, exchange: &RecordedExchange) {
+        let mut line = serde_json::to_string(exchange).expect("Serializable exchange");
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            warn!("Can't write the recording: {e}");
+        }
+    }
+}
+
+impl<H> Named for RecordingHandler<H> {}
+
+#[async_trait]
+impl<H: HttpHandler> HttpHandler for RecordingHandler<H> {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let mut response = self.handler.solve_request(request).await?;
+        if request.method() == Method::Head {
+            return Ok(response);
+        }
+
+        let body = match response.take_body() {
+            Body::Bytes(body) => body,
+            Body::Stream { reader, len } if len <= MAX_RECORDED_BODY_SIZE => {
+                let mut body = Vec::with_capacity(len as usize);
+                reader
+                    .take(len)
+                    .read_to_end(&mut body)
+                    .await
+                    .map_err(|_| "Failed reading the response to record")?;
+                body
+            }
+            body => {
+                warn!(
+                    "Response of {} not recorded, too large",
+                    request.url().path()
+                );
+                response.set_body(body);
+                return Ok(response);
+            }
+        };
+
+        self.record(&RecordedExchange::new(request, &response, &body));
+        response.set_body(Body::Bytes(body));
+
+        Ok(response)
+    }
+}
+
+struct Replayed {
+    exchanges: Vec<RecordedExchange>,
+    next: AtomicUsize,
+}
+
+/// Handler answering with the responses of a recording, by method and path
+///
+/// Exchanges recorded several times are replayed in order, the last one over and
+/// over. `HEAD` requests get the head of the `GET` ones, unmatched requests a `404`.
+///
+/// ```no_run
+/// use httpr::http::{recording::ReplayHandler, Server};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let replay = ReplayHandler::from_file("api.jsonl")?;
+/// let server = Server::new(String::from("127.0.0.1:4444"), replay);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReplayHandler {
+    exchanges: HashMap<(Method, String), Replayed>,
+}
+
+impl ReplayHandler {
+    pub fn new(exchanges: Vec<RecordedExchange>) -> Result<Self, &'static str> {
+        let mut replayed = HashMap::<_, Replayed>::new();
+
+        for exchange in exchanges {
+            exchange.response()?;
+            let method = exchange
+                .method
+                .parse()
+                .map_err(|_| "Invalid recorded method")?;
+
+            replayed
+                .entry((method, exchange.path.clone()))
+                .or_insert_with(|| Replayed {
+                    exchanges: Vec::new(),
+                    next: AtomicUsize::new(0),
+                })
+                .exchanges
+                .push(exchange);
+        }
+
+        Ok(Self {
+            exchanges: replayed,
+        })
+    }
+
+    /// Load the JSON lines written by a [RecordingHandler]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let content = fs::read_to_string(path).map_err(|_| "Can't read the recording file")?;
+
+        let exchanges = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "Invalid recording file")?;
+
+        Self::new(exchanges)
+    }
+}
+
+impl Named for ReplayHandler {}
+
+#[async_trait]
+impl HttpHandler for ReplayHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let method = match request.method() {
+            Method::Head => Method::Get,
+            method => method,
+        };
+
+        let Some(replayed) = self
+            .exchanges
+            .get(&(method, request.url().path().to_string()))
+        else {
+            return Ok(Response::not_found());
+        };
+
+        let next = replayed.next.fetch_add(1, Ordering::Relaxed);
+        let last = replayed.exchanges.len() - 1;
+        replayed.exchanges[next.min(last)].response()
+    }
+}
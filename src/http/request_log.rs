@@ -0,0 +1,80 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+
+use super::{HttpStatus, Method, Named};
+
+/// Route that solved a request, insert it in the request extensions to report it
+/// in the [RequestLog]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedRoute(pub String);
+
+/// Record of a request, completed once its response is sent
+///
+/// Request fields are `None` when the request couldn't be read, like on timeouts.
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    pub peer: SocketAddr,
+    pub method: Option<Method>,
+    /// Path and query, as sent by the client
+    pub uri: Option<String>,
+    pub version: Option<String>,
+    pub route: Option<String>,
+    pub user_agent: Option<String>,
+    pub status: HttpStatus,
+    /// Request body bytes
+    pub bytes_in: u64,
+    /// Response body bytes
+    pub bytes_out: u64,
+    pub started: DateTime<Local>,
+    pub duration: Duration,
+    start: Instant,
+}
+
+impl RequestLog {
+    pub(crate) fn new(peer: SocketAddr) -> Self {
+        Self {
+            peer,
+            method: None,
+            uri: None,
+            version: None,
+            route: None,
+            user_agent: None,
+            status: HttpStatus::Ok,
+            bytes_in: 0,
+            bytes_out: 0,
+            started: Local::now(),
+            duration: Duration::ZERO,
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn finish(&mut self, status: HttpStatus, bytes_out: u64) {
+        self.status = status;
+        self.bytes_out = bytes_out;
+        self.duration = self.start.elapsed();
+    }
+
+    /// `METHOD uri VERSION`, or `-` if the request couldn't be read
+    pub fn request_line(&self) -> String {
+        match (&self.method, &self.uri, &self.version) {
+            (Some(method), Some(uri), Some(version)) => {
+                format!("{method} {uri} {}", version.to_uppercase())
+            }
+            _ => String::from("-"),
+        }
+    }
+}
+
+/// Destination of the completed [RequestLog] records
+///
+/// Sinks run in the connection task after the response is sent, slow sinks should
+/// hand the records off to a channel.
+#[async_trait]
+pub trait LogSink: Send + Sync + Named {
+    async fn record(&self, log: &RequestLog);
+}
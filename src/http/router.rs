@@ -0,0 +1,406 @@
+//! Dispatch of the requests by method and path
+
+use std::{ops::ControlFlow, sync::Arc};
+
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+
+use super::{
+    HttpHandler, InterceptorReq, InterceptorRes, InterceptorStack, MatchedRoute, Method, Named,
+    Request, Response,
+};
+
+pub use path::{Path, PathError};
+
+mod path;
+
+#[derive(Clone)]
+enum Segment {
+    Static(String),
+    /// `{name}`, one path segment
+    Param(String),
+    /// `{*name}`, the rest of the path
+    CatchAll(String),
+}
+
+#[derive(Clone)]
+struct Route {
+    /// `None` for any method
+    method: Option<Method>,
+    template: String,
+    segments: Vec<Segment>,
+    handler: Arc<dyn HttpHandler>,
+    interceptors_req: Vec<Arc<dyn InterceptorReq>>,
+    interceptors_res: Vec<Arc<dyn InterceptorRes>>,
+}
+
+/// Values of the `{name}` and `{*name}` segments of the matched route
+///
+/// The [Router] inserts them in the request extensions, decoded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathParams {
+    params: Vec<(String, String)>,
+}
+
+impl PathParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Names and values in the route order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Handler dispatching the requests by method and path
+///
+/// Routes are tried in the order they were added, trailing slashes are ignored.
+/// A path segment `{name}` captures one segment and a last `{*name}` the rest of
+/// the path, both read from the [PathParams] extension. Paths matched with another
+/// method get a `405`, unmatched paths the fallback handler or a `404`.
+///
+/// ```
+/// use async_trait::async_trait;
+/// use httpr::http::{HttpHandler, Method, Named, PathParams, Request, Response, Router};
+/// use httpr::static_server::StaticFileHandler;
+///
+/// struct ReportHandler;
+/// impl Named for ReportHandler {}
+///
+/// #[async_trait]
+/// impl HttpHandler for ReportHandler {
+///     async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+///         let params = request.extensions().get::<PathParams>().unwrap();
+///         Ok(Response::text(params.get("name").unwrap()))
+///     }
+/// }
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let reports = Router::new().route(Method::Get, "/{*name}", ReportHandler)?;
+///
+/// let router = Router::new()
+///     .group("/reports", reports)?
+///     .with_fallback(StaticFileHandler::new(".", false)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    interceptors_req: Vec<Arc<dyn InterceptorReq>>,
+    interceptors_res: Vec<Arc<dyn InterceptorRes>>,
+    fallback: Option<Arc<dyn HttpHandler>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Solve the `method` requests of `path` with `handler`
+    pub fn route<H: HttpHandler>(
+        self,
+        method: Method,
+        path: &str,
+        handler: H,
+    ) -> Result<Self, &'static str> {
+        self.push_route(Some(method), path, Arc::new(handler))
+    }
+
+    /// Solve the requests of `path` with `handler`, whatever their method
+    pub fn any<H: HttpHandler>(self, path: &str, handler: H) -> Result<Self, &'static str> {
+        self.push_route(None, path, Arc::new(handler))
+    }
+
+    /// Handler of the requests matching no route
+    pub fn with_fallback<H: HttpHandler>(mut self, handler: H) -> Self {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    /// Run `interceptor` before the handler of every route of this router
+    ///
+    /// Unlike the server ones, they only run for the matched requests.
+    pub fn with_req_inter(mut self, interceptor: Arc<dyn InterceptorReq>) -> Self {
+        self.interceptors_req.push(interceptor);
+        self
+    }
+
+    /// Run `interceptor` after the handler of every route of this router
+    pub fn with_res_inter(mut self, interceptor: Arc<dyn InterceptorRes>) -> Self {
+        self.interceptors_res.push(interceptor);
+        self
+    }
+
+    /// Run the interceptors of `stack` around every route of this router
+    pub fn with_stack(mut self, stack: &InterceptorStack) -> Self {
+        self.interceptors_req
+            .extend(stack.interceptors_req().iter().cloned());
+        self.interceptors_res
+            .extend(stack.interceptors_res().iter().cloned());
+        self
+    }
+
+    /// Add the routes of `group` under `prefix`, with its interceptors
+    ///
+    /// The fallback of `group` is ignored.
+    pub fn group(mut self, prefix: &str, group: Router) -> Result<Self, &'static str> {
+        let prefix = prefix.trim_end_matches('/');
+        let prefix_segments = parse_path(prefix)?;
+        if matches!(prefix_segments.last(), Some(Segment::CatchAll(_))) {
+            return Err("Catch-all segment in a group prefix!");
+        }
+
+        for route in group.routes {
+            let mut interceptors_req = group.interceptors_req.clone();
+            interceptors_req.extend(route.interceptors_req);
+            let mut interceptors_res = route.interceptors_res;
+            interceptors_res.extend(group.interceptors_res.iter().cloned());
+
+            self.routes.push(Route {
+                template: format!("{prefix}{}", route.template),
+                segments: [prefix_segments.clone(), route.segments].concat(),
+                interceptors_req,
+                interceptors_res,
+                ..route
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Add the routes of `other` after the ones of this router
+    ///
+    /// The fallback of this router is kept, if any.
+    pub fn merge(self, mut other: Router) -> Self {
+        let fallback = other.fallback.take();
+        let mut router = self.group("", other).expect("Empty prefix is valid");
+        router.fallback = router.fallback.or(fallback);
+        router
+    }
+
+    fn push_route(
+        mut self,
+        method: Option<Method>,
+        path: &str,
+        handler: Arc<dyn HttpHandler>,
+    ) -> Result<Self, &'static str> {
+        self.routes.push(Route {
+            method,
+            template: path.to_string(),
+            segments: parse_path(path)?,
+            handler,
+            interceptors_req: Vec::new(),
+            interceptors_res: Vec::new(),
+        });
+
+        Ok(self)
+    }
+}
+
+impl Named for Router {}
+
+#[async_trait]
+impl HttpHandler for Router {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let url = request.url();
+        let path = url
+            .path()
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut allowed = Vec::new();
+        let mut matched = None;
+        for route in &self.routes {
+            let Some(params) = match_segments(&route.segments, &path) else {
+                continue;
+            };
+
+            match route.method {
+                Some(method) if method != request.method() => allowed.push(method),
+                _ => {
+                    matched = Some((route, params));
+                    break;
+                }
+            }
+        }
+
+        let Some((route, params)) = matched else {
+            return match &self.fallback {
+                Some(fallback) => fallback.solve_request(request).await,
+                None if !allowed.is_empty() => Ok(Response::method_not_allowed(&allowed)),
+                None => Ok(Response::not_found()),
+            };
+        };
+
+        let mut request = request.clone();
+        request.extensions_mut().insert(params);
+        request
+            .extensions_mut()
+            .insert(MatchedRoute(route.template.clone()));
+
+        for interceptor in self.interceptors_req.iter().chain(&route.interceptors_req) {
+            match interceptor.chain_req(request).await {
+                ControlFlow::Continue(r) => request = r,
+                ControlFlow::Break(response) => return Ok(response),
+            }
+        }
+
+        let mut response = route.handler.solve_request(&request).await?;
+
+        for interceptor in route.interceptors_res.iter().chain(&self.interceptors_res) {
+            response = interceptor.chain_res(&request, response).await;
+        }
+
+        Ok(response)
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, &'static str> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return Err("Route paths must start with '/'!");
+    }
+
+    let segments = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(
+            |segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) if name.starts_with('*') && is_param_name(&name[1..]) => {
+                    Ok(Segment::CatchAll(name[1..].to_string()))
+                }
+                Some(name) if is_param_name(name) => Ok(Segment::Param(name.to_string())),
+                None if !segment.contains(['{', '}']) => Ok(Segment::Static(segment.to_string())),
+                _ => Err("Invalid route segment!"),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let last = segments.len().saturating_sub(1);
+    if segments
+        .iter()
+        .enumerate()
+        .any(|(i, s)| matches!(s, Segment::CatchAll(_)) && i != last)
+    {
+        return Err("Catch-all segment must be the last!");
+    }
+
+    Ok(segments)
+}
+
+fn is_param_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['{', '}', '*'])
+}
+
+fn match_segments(segments: &[Segment], path: &[&str]) -> Option<PathParams> {
+    let decode = |s: &str| percent_decode_str(s).decode_utf8().ok().map(String::from);
+    let mut params = Vec::new();
+
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::CatchAll(name) => {
+                params.push((name.clone(), decode(&path[i.min(path.len())..].join("/"))?));
+                return Some(PathParams { params });
+            }
+            Segment::Static(s) if path.get(i) == Some(&s.as_str()) => {}
+            Segment::Param(name) => params.push((name.clone(), decode(path.get(i)?)?)),
+            Segment::Static(_) => return None,
+        }
+    }
+
+    (segments.len() == path.len()).then_some(PathParams { params })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Params = Option<&'static [(&'static str, &'static str)]>;
+
+    fn segments(path: &str) -> Vec<&str> {
+        path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+
+    #[test]
+    fn parses_route_paths() {
+        let valid = [
+            "",
+            "/",
+            "/users",
+            "//users//",
+            "/users/{id}",
+            "/users/{id}/posts/{post}",
+            "/files/{*rest}",
+            "/{*rest}",
+        ];
+        for path in valid {
+            assert!(parse_path(path).is_ok(), "{path}");
+        }
+
+        let invalid = [
+            "users",
+            "{id}",
+            "/users/{}",
+            "/users/{id",
+            "/users/id}",
+            "/users/{{id}}",
+            "/users/x{id}",
+            "/users/{i*d}",
+            "/files/{*}",
+            "/files/{**rest}",
+            "/files/{*rest}/more",
+            "/{*a}/{*b}",
+        ];
+        for path in invalid {
+            assert!(parse_path(path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn matches_request_paths() {
+        let cases: [(&str, &str, Params); 18] = [
+            ("/", "/", Some(&[])),
+            ("/", "/users", None),
+            ("/users", "/users", Some(&[])),
+            ("/users", "/users/", Some(&[])),
+            ("/users", "/Users", None),
+            ("/users", "/", None),
+            ("/users", "/users/1", None),
+            ("/users/{id}", "/users/1", Some(&[("id", "1")])),
+            ("/users/{id}", "/users", None),
+            ("/users/{id}", "/users/1/posts", None),
+            ("/users/{id}", "/users/a%20b", Some(&[("id", "a b")])),
+            ("/users/{id}", "/users/a%2Fb", Some(&[("id", "a/b")])),
+            ("/users/{id}", "/users/%FF", None),
+            (
+                "/users/{id}/posts/{post}",
+                "/users/1/posts/2",
+                Some(&[("id", "1"), ("post", "2")]),
+            ),
+            ("/files/{*rest}", "/files", Some(&[("rest", "")])),
+            (
+                "/files/{*rest}",
+                "/files/a/b%20c",
+                Some(&[("rest", "a/b c")]),
+            ),
+            ("/files/{*rest}", "/other/a", None),
+            ("/{*rest}", "/a//b/", Some(&[("rest", "a/b")])),
+        ];
+
+        for (route, path, expected) in cases {
+            let params = match_segments(&parse_path(route).unwrap(), &segments(path));
+            let expected = expected.map(|params| PathParams {
+                params: params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            });
+            assert_eq!(params, expected, "{route} {path}");
+        }
+    }
+}
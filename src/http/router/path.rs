@@ -0,0 +1,342 @@
+//! Typed path parameters
+
+use std::{fmt, ops::Deref, slice::Iter, str::FromStr};
+
+use log::warn;
+use serde::{
+    de::{
+        self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer,
+        MapAccess, SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+
+use super::PathParams;
+use crate::http::{HttpStatus, Request, Response};
+
+/// Path parameters of the matched route deserialized as `T`
+///
+/// Structs and maps take the parameters by name, tuples and sequences in the
+/// route order, any other type the single parameter of the route. Numbers,
+/// booleans and unit enum variants are parsed from the segments.
+///
+/// ```
+/// use async_trait::async_trait;
+/// use httpr::http::{router::Path, HttpHandler, Named, Request, Response};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Page {
+///     user: String,
+///     page: u32,
+/// }
+///
+/// struct PageHandler;
+/// impl Named for PageHandler {}
+///
+/// #[async_trait]
+/// impl HttpHandler for PageHandler {
+///     // Routed from `/users/{user}/pages/{page}`
+///     async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+///         let Path(page) = match Path::<Page>::from_request(request) {
+///             Ok(path) => path,
+///             Err(bad_request) => return Ok(bad_request),
+///         };
+///
+///         Ok(Response::text(&format!("Page {} of {}", page.page, page.user)))
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned> Path<T> {
+    /// Deserialize the [PathParams] of `request`, a `400` telling why on failure
+    ///
+    /// Requests not solved by a [Router](super::Router) get a `500`.
+    pub fn from_request(request: &Request) -> Result<Self, Response> {
+        let Some(params) = request.extensions().get::<PathParams>() else {
+            warn!("No path parameters, the handler isn't behind a router");
+            return Err(Response::new(HttpStatus::InternalServerError));
+        };
+
+        params.deserialize().map(Path).map_err(|e| {
+            let mut response = Response::text(&e.to_string());
+            response.set_status(HttpStatus::BadRequest);
+            response
+        })
+    }
+}
+
+impl<T> Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl PathParams {
+    /// Deserialize the parameters as `T`, see [Path]
+    ///
+    /// ```
+    /// use httpr::http::PathParams;
+    ///
+    /// let params = PathParams::default();
+    /// assert_eq!(params.deserialize::<()>(), Ok(()));
+    /// assert!(params.deserialize::<u32>().is_err());
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, PathError> {
+        T::deserialize(ParamsDeserializer(&self.params))
+    }
+}
+
+/// Path parameters not matching the expected type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError(String);
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl de::Error for PathError {
+    fn custom<E: fmt::Display>(msg: E) -> Self {
+        PathError(msg.to_string())
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, PathError>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| PathError(format!("Invalid value '{value}': {e}")))
+}
+
+struct ParamsDeserializer<'a>(&'a [(String, String)]);
+
+impl<'a> ParamsDeserializer<'a> {
+    fn single(self) -> Result<ValueDeserializer<'a>, PathError> {
+        match self.0 {
+            [(_, value)] => Ok(ValueDeserializer(value)),
+            params => Err(PathError(format!(
+                "Expected 1 path parameter, got {}",
+                params.len()
+            ))),
+        }
+    }
+}
+
+macro_rules! single_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ParamsDeserializer<'_> {
+    type Error = PathError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        visitor.visit_map(ParamsMap {
+            params: self.0.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        visitor.visit_seq(ParamsSeq(self.0.iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        if len != self.0.len() {
+            return Err(PathError(format!(
+                "Expected {len} path parameters, got {}",
+                self.0.len()
+            )));
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    single_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_option deserialize_identifier deserialize_ignored_any
+    }
+}
+
+struct ParamsMap<'a> {
+    params: Iter<'a, (String, String)>,
+    value: Option<&'a (String, String)>,
+}
+
+impl<'de> MapAccess<'de> for ParamsMap<'_> {
+    type Error = PathError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, PathError> {
+        let Some(param) = self.params.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some(param);
+        let name: StrDeserializer<PathError> = param.0.as_str().into_deserializer();
+        seed.deserialize(name).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, PathError> {
+        let (name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| PathError(String::from("Path parameter value without name")))?;
+
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|e| PathError(format!("Path parameter '{name}': {e}")))
+    }
+}
+
+struct ParamsSeq<'a>(Iter<'a, (String, String)>);
+
+impl<'de> SeqAccess<'de> for ParamsSeq<'_> {
+    type Error = PathError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, PathError> {
+        match self.0.next() {
+            Some((_, value)) => seed.deserialize(ValueDeserializer(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Single segment, parsed as the type asked for
+struct ValueDeserializer<'a>(&'a str);
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+                visitor.$visit(parse(self.0)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = PathError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        let variant: StrDeserializer<PathError> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool
+        deserialize_i8 => visit_i8
+        deserialize_i16 => visit_i16
+        deserialize_i32 => visit_i32
+        deserialize_i64 => visit_i64
+        deserialize_u8 => visit_u8
+        deserialize_u16 => visit_u16
+        deserialize_u32 => visit_u32
+        deserialize_u64 => visit_u64
+        deserialize_f32 => visit_f32
+        deserialize_f64 => visit_f64
+        deserialize_char => visit_char
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
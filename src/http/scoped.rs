@@ -0,0 +1,163 @@
+//! Interceptors running only for some request paths
+
+use std::{ops::ControlFlow, sync::Arc};
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use super::{InterceptorReq, InterceptorRes, Named, Request, Response};
+use crate::static_server::url_path::decode_path;
+
+/// Request paths an interceptor is scoped to
+///
+/// A pattern without wildcards is a prefix of whole segments. Otherwise it's a
+/// glob where `*` and `?` match within a segment and `**` across segments, a
+/// trailing `/**` also matches the directory itself. Paths are decoded and
+/// their empty and `.` segments dropped before matching.
+///
+/// ```
+/// use httpr::http::scoped::PathPattern;
+///
+/// let private = PathPattern::new("/private/**").unwrap();
+/// assert!(private.matches("/private"));
+/// assert!(private.matches("//%70rivate/./docs/a.txt"));
+/// assert!(!private.matches("/privates"));
+///
+/// let images = PathPattern::new("/*/images/*.png").unwrap();
+/// assert!(images.matches("/blog/images/cat.png"));
+/// assert!(!images.matches("/blog/images/2024/cat.png"));
+///
+/// let api = PathPattern::new("/api").unwrap();
+/// assert!(api.matches("/api/users"));
+/// assert!(!api.matches("/apis"));
+/// ```
+#[derive(Debug, Clone)]
+pub enum PathPattern {
+    Prefix(String),
+    Glob(Regex),
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Result<Self, &'static str> {
+        if !pattern.starts_with('/') {
+            return Err("Path patterns must start with '/'!");
+        }
+
+        if !pattern.contains(['*', '?']) {
+            return Ok(PathPattern::Prefix(normalize(pattern)));
+        }
+
+        let (pattern, any_below) = match pattern.strip_suffix("/**") {
+            Some(base) => (base, true),
+            None => (pattern, false),
+        };
+
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if any_below {
+            regex.push_str("(/.*)?");
+        }
+        regex.push('$');
+
+        Regex::new(&regex)
+            .map(PathPattern::Glob)
+            .map_err(|_| "Invalid path pattern!")
+    }
+
+    /// Whether the request `path` is in scope
+    ///
+    /// Paths that can't be decoded are always in scope, so a scoped check can't
+    /// be skipped with them.
+    pub fn matches(&self, path: &str) -> bool {
+        let Some(path) = decode_path(path) else {
+            return true;
+        };
+        let path = normalize(&path);
+
+        match self {
+            PathPattern::Prefix(prefix) => {
+                prefix == "/"
+                    || path
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }
+            PathPattern::Glob(regex) => regex.is_match(&path),
+        }
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let segments = path
+        .split('/')
+        .filter(|s| !s.is_empty() && *s != ".")
+        .collect::<Vec<_>>();
+
+    format!("/{}", segments.join("/"))
+}
+
+/// Run `inner` only for the requests whose path matches a [PathPattern]
+pub struct ScopedReqInterceptor {
+    pattern: PathPattern,
+    inner: Arc<dyn InterceptorReq>,
+}
+
+impl ScopedReqInterceptor {
+    pub fn new(pattern: PathPattern, inner: Arc<dyn InterceptorReq>) -> Self {
+        Self { pattern, inner }
+    }
+}
+
+impl Named for ScopedReqInterceptor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[async_trait]
+impl InterceptorReq for ScopedReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        match self.pattern.matches(request.url().path()) {
+            true => self.inner.chain_req(request).await,
+            false => ControlFlow::Continue(request),
+        }
+    }
+}
+
+/// Run `inner` only for the responses to requests whose path matches a [PathPattern]
+pub struct ScopedResInterceptor {
+    pattern: PathPattern,
+    inner: Arc<dyn InterceptorRes>,
+}
+
+impl ScopedResInterceptor {
+    pub fn new(pattern: PathPattern, inner: Arc<dyn InterceptorRes>) -> Self {
+        Self { pattern, inner }
+    }
+}
+
+impl Named for ScopedResInterceptor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[async_trait]
+impl InterceptorRes for ScopedResInterceptor {
+    async fn chain_res(&self, request: &Request, response: Response) -> Response {
+        match self.pattern.matches(request.url().path()) {
+            true => self.inner.chain_res(request, response).await,
+            false => response,
+        }
+    }
+}
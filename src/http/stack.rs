@@ -0,0 +1,76 @@
+use std::{ops::ControlFlow, sync::Arc};
+
+use super::{HttpHandler, InterceptorReq, InterceptorRes, Request, Response};
+
+/// Ordered request and response interceptors applied together
+///
+/// Push it to a [Server](super::Server) with `push_stack` or to a
+/// [Router](super::Router) group with `with_stack`.
+#[derive(Clone, Default)]
+pub struct InterceptorStack {
+    interceptors_req: Vec<Arc<dyn InterceptorReq>>,
+    interceptors_res: Vec<Arc<dyn InterceptorRes>>,
+}
+
+impl InterceptorStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_req_inter(mut self, interceptor: Arc<dyn InterceptorReq>) -> Self {
+        self.interceptors_req.push(interceptor);
+        self
+    }
+
+    pub fn with_res_inter(mut self, interceptor: Arc<dyn InterceptorRes>) -> Self {
+        self.interceptors_res.push(interceptor);
+        self
+    }
+
+    /// Add the interceptors of `other` after the ones of this stack
+    pub fn with_stack(mut self, other: &InterceptorStack) -> Self {
+        self.interceptors_req
+            .extend(other.interceptors_req.iter().cloned());
+        self.interceptors_res
+            .extend(other.interceptors_res.iter().cloned());
+        self
+    }
+
+    pub fn interceptors_req(&self) -> &[Arc<dyn InterceptorReq>] {
+        &self.interceptors_req
+    }
+
+    pub fn interceptors_res(&self) -> &[Arc<dyn InterceptorRes>] {
+        &self.interceptors_res
+    }
+
+    pub fn req_inter_names(&self) -> Vec<&str> {
+        self.interceptors_req.iter().map(|i| i.name()).collect()
+    }
+
+    pub fn res_inter_names(&self) -> Vec<&str> {
+        self.interceptors_res.iter().map(|i| i.name()).collect()
+    }
+
+    /// Solve `request` with `handler` inside the interceptors
+    pub(crate) async fn solve(
+        &self,
+        handler: &dyn HttpHandler,
+        mut request: Request,
+    ) -> Result<Response, &'static str> {
+        for interceptor in &self.interceptors_req {
+            match interceptor.chain_req(request).await {
+                ControlFlow::Continue(r) => request = r,
+                ControlFlow::Break(response) => return Ok(response),
+            }
+        }
+
+        let mut response = handler.solve_request(&request).await?;
+
+        for interceptor in &self.interceptors_res {
+            response = interceptor.chain_res(&request, response).await;
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,198 @@
+//! Runtime statistics of a [Server](super::Server)
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::{InterceptorReq, LogSink, Named, Request, RequestLog, Response};
+
+/// Live counters of the server, shared through [Server::stats](super::Server::stats)
+#[derive(Debug)]
+pub struct ServerStats {
+    started: Instant,
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+    total_requests: AtomicU64,
+    keep_alive_requests: AtomicU64,
+    bytes_served: AtomicU64,
+}
+
+/// Point in time copy of the [ServerStats]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StatsSnapshot {
+    pub uptime_secs: u64,
+    pub active_connections: u64,
+    pub total_connections: u64,
+    pub total_requests: u64,
+    /// Requests on a connection kept alive after a previous one
+    pub keep_alive_requests: u64,
+    /// Response body bytes
+    pub bytes_served: u64,
+}
+
+impl ServerStats {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            active_connections: AtomicU64::new(0),
+            total_connections: AtomicU64::new(0),
+            total_requests: AtomicU64::new(0),
+            keep_alive_requests: AtomicU64::new(0),
+            bytes_served: AtomicU64::new(0),
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            uptime_secs: self.started.elapsed().as_secs(),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            keep_alive_requests: self.keep_alive_requests.load(Ordering::Relaxed),
+            bytes_served: self.bytes_served.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Count a new connection until the guard is dropped
+    pub(crate) fn connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+
+    pub(crate) fn request(&self) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn kept_alive(&self) {
+        self.keep_alive_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn served(&self, bytes: u64) {
+        self.bytes_served.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct ConnectionGuard(Arc<ServerStats>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Paths counted by [TrafficStats] at most, new ones are left out after that
+pub const MAX_TRACKED_PATHS: usize = 1000;
+
+/// Responses by status class and requests by path
+///
+/// Register it as a [LogSink] to collect the counters.
+#[derive(Debug, Default)]
+pub struct TrafficStats {
+    traffic: Mutex<Traffic>,
+}
+
+#[derive(Debug, Default)]
+struct Traffic {
+    statuses: [u64; 5],
+    paths: HashMap<String, u64>,
+}
+
+/// Point in time copy of the [TrafficStats]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrafficSnapshot {
+    /// Responses of the `1xx` to `5xx` classes
+    pub statuses: [u64; 5],
+    /// Most requested paths, without query, and their requests
+    pub top_paths: Vec<(String, u64)>,
+}
+
+impl TrafficStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counters with the `top` most requested paths
+    pub fn snapshot(&self, top: usize) -> TrafficSnapshot {
+        let traffic = self.traffic.lock().unwrap();
+
+        let mut top_paths = traffic
+            .paths
+            .iter()
+            .map(|(path, count)| (path.clone(), *count))
+            .collect::<Vec<_>>();
+        top_paths.sort_by_key(|(path, count)| (Reverse(*count), path.clone()));
+        top_paths.truncate(top);
+
+        TrafficSnapshot {
+            statuses: traffic.statuses,
+            top_paths,
+        }
+    }
+}
+
+impl Named for TrafficStats {}
+
+#[async_trait]
+impl LogSink for TrafficStats {
+    async fn record(&self, log: &RequestLog) {
+        let mut traffic = self.traffic.lock().unwrap();
+
+        let class = (log.status.code() / 100).clamp(1, 5) - 1;
+        traffic.statuses[class as usize] += 1;
+
+        let Some(path) = log.uri.as_deref().and_then(|uri| uri.split('?').next()) else {
+            return;
+        };
+        let tracked = traffic.paths.len();
+        match traffic.paths.get_mut(path) {
+            Some(count) => *count += 1,
+            None if tracked < MAX_TRACKED_PATHS => {
+                traffic.paths.insert(path.to_string(), 1);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Serve a JSON [StatsSnapshot] on `path`
+pub struct StatsReqInterceptor {
+    path: String,
+    stats: Arc<ServerStats>,
+}
+
+impl StatsReqInterceptor {
+    pub fn new(path: &str, stats: Arc<ServerStats>) -> Self {
+        Self {
+            path: path.to_string(),
+            stats,
+        }
+    }
+}
+
+impl Named for StatsReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for StatsReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let path = request.uri.split('?').next().unwrap_or_default();
+        if path != self.path {
+            return ControlFlow::Continue(request);
+        }
+
+        let mut response = Response::json(&self.stats.snapshot());
+        response.add_header(("Cache-Control", "no-store"));
+
+        ControlFlow::Break(response)
+    }
+}
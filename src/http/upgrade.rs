@@ -0,0 +1,57 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{self, BufReader},
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    sync::oneshot,
+};
+
+/// Connection handed over after a `101 Switching Protocols` response
+///
+/// The reader keeps the bytes the client sent right after the request.
+#[derive(Debug)]
+pub struct Upgraded {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Upgraded {
+    pub(crate) fn new(reader: BufReader<OwnedReadHalf>, writer: OwnedWriteHalf) -> Self {
+        Self { reader, writer }
+    }
+
+    pub fn into_parts(self) -> (BufReader<OwnedReadHalf>, OwnedWriteHalf) {
+        (self.reader, self.writer)
+    }
+}
+
+/// Resolves to the [Upgraded] connection once the `101` response is sent
+///
+/// Get it with [Request::on_upgrade](super::Request::on_upgrade) and await it in
+/// another task, the handler has to return the response first. It fails when the
+/// response wasn't a `101` or couldn't be sent.
+#[derive(Debug)]
+pub struct OnUpgrade {
+    upgraded: oneshot::Receiver<Upgraded>,
+}
+
+impl OnUpgrade {
+    pub(crate) fn new() -> (oneshot::Sender<Upgraded>, Self) {
+        let (sender, upgraded) = oneshot::channel();
+        (sender, Self { upgraded })
+    }
+}
+
+impl Future for OnUpgrade {
+    type Output = io::Result<Upgraded>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.upgraded)
+            .poll(cx)
+            .map_err(|_| io::Error::other("Connection not upgraded"))
+    }
+}
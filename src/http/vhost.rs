@@ -0,0 +1,110 @@
+//! Dispatch of the requests by `Host`
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::debug;
+
+use super::{HttpHandler, HttpStatus, InterceptorStack, Named, Request, Response};
+
+/// Site served for some host names, with its own interceptors
+#[derive(Clone)]
+pub struct VirtualHost {
+    handler: Arc<dyn HttpHandler>,
+    stack: InterceptorStack,
+}
+
+impl VirtualHost {
+    pub fn new<H: HttpHandler>(handler: H) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            stack: InterceptorStack::new(),
+        }
+    }
+
+    /// Run the interceptors of `stack` for the requests of this host only
+    ///
+    /// Error pages, authentication or CORS policies of the host go here, the
+    /// server interceptors still run around them.
+    pub fn with_stack(mut self, stack: &InterceptorStack) -> Self {
+        self.stack = self.stack.with_stack(stack);
+        self
+    }
+}
+
+/// Handler picking the [VirtualHost] by the `Host` of the request
+///
+/// Host names are compared without port nor case, `*.example.com` matches any
+/// subdomain of `example.com`. Unknown hosts get the default host or a `421`.
+///
+/// ```
+/// use httpr::http::vhost::{VirtualHost, VirtualHosts};
+/// use httpr::static_server::StaticFileHandler;
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let docs = StaticFileHandler::new(".", true)?;
+/// let docs_stack = docs.site_stack();
+///
+/// let hosts = VirtualHosts::new()
+///     .with_host("docs.example.com", VirtualHost::new(docs).with_stack(&docs_stack))
+///     .with_default(VirtualHost::new(StaticFileHandler::new(".", false)?));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct VirtualHosts {
+    hosts: Vec<(String, VirtualHost)>,
+    default: Option<VirtualHost>,
+}
+
+impl VirtualHosts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `name`, or its subdomains with `*.name`, with `host`
+    pub fn with_host(mut self, name: &str, host: VirtualHost) -> Self {
+        self.hosts.push((name.to_lowercase(), host));
+        self
+    }
+
+    /// Host of the requests for unknown host names
+    pub fn with_default(mut self, host: VirtualHost) -> Self {
+        self.default = Some(host);
+        self
+    }
+
+    fn find(&self, name: &str) -> Option<&VirtualHost> {
+        self.hosts
+            .iter()
+            .find(|(pattern, _)| match pattern.strip_prefix("*.") {
+                Some(domain) => name
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => pattern == name,
+            })
+            .map(|(_, host)| host)
+            .or(self.default.as_ref())
+    }
+}
+
+impl Named for VirtualHosts {}
+
+#[async_trait]
+impl HttpHandler for VirtualHosts {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let name = request.url().host_str().unwrap_or_default().to_lowercase();
+
+        match self.find(&name) {
+            Some(host) => {
+                host.stack
+                    .solve(host.handler.as_ref(), request.clone())
+                    .await
+            }
+            None => {
+                debug!("No virtual host for {name}");
+                Ok(Response::new(HttpStatus::MisdirectedRequest))
+            }
+        }
+    }
+}
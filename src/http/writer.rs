@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::debug;
+use tokio::{
+    io::{self, AsyncWriteExt, DuplexStream},
+    sync::oneshot,
+};
+
+use super::{Body, HttpHandler, HttpStatus, Named, Request, Response};
+
+/// Body bytes a [ResponseWriter] gets ahead of the client before waiting
+pub const DEFAULT_WRITER_BUFFER: usize = 64 * 1024;
+
+/// Handler writing its response as it goes, for proxies or long generated output
+///
+/// Serve it with a [WriterAdapter]. Without a `Content-Length` header the body
+/// lasts until the writer is dropped.
+#[async_trait]
+pub trait WriterHandler: Send + Sync + Named + 'static {
+    async fn write_response(&self, request: Request, writer: ResponseWriter) -> io::Result<()>;
+}
+
+/// Sends a response head first and then its body in chunks
+pub struct ResponseWriter {
+    head: Option<oneshot::Sender<Response>>,
+    body: DuplexStream,
+}
+
+impl ResponseWriter {
+    /// Send the status and headers of `head`, its body is ignored
+    ///
+    /// Fails when the head was already sent.
+    pub fn send_head(&mut self, head: Response) -> io::Result<()> {
+        let sender = self
+            .head
+            .take()
+            .ok_or_else(|| io::Error::other("Response head already sent"))?;
+
+        sender
+            .send(head)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    /// Write a body chunk, waiting while the client is behind
+    ///
+    /// A `200` head is sent first if none was.
+    pub async fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if self.head.is_some() {
+            self.send_head(Response::new(HttpStatus::Ok))?;
+        }
+
+        self.body.write_all(chunk).await
+    }
+
+    /// End the body
+    pub async fn finish(mut self) -> io::Result<()> {
+        if self.head.is_some() {
+            self.send_head(Response::new(HttpStatus::Ok))?;
+        }
+
+        self.body.shutdown().await
+    }
+}
+
+/// Serve a [WriterHandler] as an [HttpHandler]
+///
+/// The writer runs in its own task and the response interceptors see its head
+/// only, the body is streamed to the client through a bounded buffer.
+pub struct WriterAdapter<W> {
+    handler: Arc<W>,
+    buffer: usize,
+}
+
+impl<W: WriterHandler> WriterAdapter<W> {
+    pub fn new(handler: W) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            buffer: DEFAULT_WRITER_BUFFER,
+        }
+    }
+
+    /// Bytes written ahead of the client, [DEFAULT_WRITER_BUFFER] by default
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer.max(1);
+        self
+    }
+}
+
+impl<W: WriterHandler> Named for WriterAdapter<W> {
+    fn name(&self) -> &str {
+        self.handler.name()
+    }
+}
+
+#[async_trait]
+impl<W: WriterHandler> HttpHandler for WriterAdapter<W> {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let (head, head_received) = oneshot::channel();
+        let (reader, body) = io::duplex(self.buffer);
+
+        let writer = ResponseWriter {
+            head: Some(head),
+            body,
+        };
+
+        let handler = self.handler.clone();
+        let request = request.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handler.write_response(request, writer).await {
+                debug!("Response writer stopped: {e}");
+            }
+        });
+
+        let mut response = head_received
+            .await
+            .map_err(|_| "Response writer ended without a response")?;
+
+        // The head is written with the length of the body
+        let body = match response.remove_header("content-length") {
+            Some(len) => match len.parse() {
+                Ok(len) => Body::stream(reader, len),
+                Err(_) => return Err("Invalid Content-Length of the response writer"),
+            },
+            None => Body::unbounded(reader),
+        };
+        response.set_body(body);
+
+        Ok(response)
+    }
+}
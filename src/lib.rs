@@ -14,25 +14,18 @@
 //!
 //! See full example on [examples/static_file_server.rs](https://github.com/calamarte/httpr/blob/main/examples/static_file_server.rs)
 //!
-//! ```
-//!   use httpr::{
-//!       http::Server,
-//!       static_server::{
-//!           NoBodyOnHeadResInterceptor, NotFoundRenderResInterceptor, OnlyGetReqInterceptor,
-//!           StaticFileHandler,
-//!       },
-//!   };
+//! ```no_run
+//!   use httpr::{http::Server, static_server::StaticFileHandler};
 //!
 //!   #[tokio::main]
 //!   async fn main() {
 //!
-//!       let bind = "127.0.0.1:4444";
+//!       let bind = "127.0.0.1:4444".to_string();
 //!       let handler = StaticFileHandler::new(".", true).expect("Failed creating handler");
+//!       let stack = handler.site_stack();
 //!
 //!       Server::new(bind, handler)
-//!           .push_req_inter(Arc::new(OnlyGetReqInterceptor))
-//!           .push_res_inter(Arc::new(NoBodyOnHeadResInterceptor))
-//!           .push_res_inter(Arc::new(NotFoundRenderResInterceptor))
+//!           .push_stack(&stack)
 //!           .run()
 //!           .await
 //!           .unwrap()
@@ -44,7 +37,7 @@
 //!
 //! See full example on [examples/dummy.rs](https://github.com/calamarte/httpr/blob/main/examples/dummy.rs)
 //!
-//! ```
+//! ```no_run
 //!   use async_trait::async_trait;
 //!   use httpr::http::{HttpHandler, Named, Response, Server};
 //!   use log::info;
@@ -60,10 +53,7 @@
 //!       ) -> Result<httpr::http::Response, &'static str> {
 //!           info!("request: {request:?}");
 //!
-//!           let mut response = Response::new(httpr::http::HttpStatus::Ok);
-//!           response.add_body(b"Hello, world :)");
-//!
-//!           Ok(response)
+//!           Ok(Response::text("Hello, world :)"))
 //!       }
 //!   }
 //!
@@ -82,5 +72,9 @@
 //! ```
 //!
 
+#[cfg(feature = "testing")]
+pub mod conformance;
 pub mod http;
 pub mod static_server;
+#[cfg(feature = "testing")]
+pub mod testing;
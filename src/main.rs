@@ -1,50 +1,1120 @@
-use std::{env, path::PathBuf, sync::Arc};
+use std::{env, net::TcpListener, path::PathBuf, process::ExitCode, sync::Arc, time::Duration};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+#[cfg(feature = "thumbnails")]
+use httpr::static_server::THUMBNAIL_ROUTE;
 use httpr::{
-    http::Server,
+    http::{
+        access_log::{AccessLog, Rotation, DEFAULT_ACCESS_LOG_RETENTION},
+        admin::AdminReqInterceptor,
+        auth::{
+            api_key::{ApiKeyReqInterceptor, ApiKeys},
+            htpasswd::Htpasswd,
+            BasicAuthReqInterceptor, ProtectedPaths,
+        },
+        compression::{
+            CompressionResInterceptor, DecompressionReqInterceptor, DEFAULT_COMPRESSION_LEVEL,
+            DEFAULT_MAX_DECOMPRESSED_SIZE, DEFAULT_MIN_COMPRESSION_SIZE, DEFAULT_SKIPPED_TYPES,
+        },
+        delay::{Delay, DelayReqInterceptor},
+        fault::{Fault, FaultResInterceptor},
+        method_filter::MethodFilterReqInterceptor,
+        metrics::{MetricsReqInterceptor, RouteMetrics},
+        proxies::{IpNet, TrustedProxies},
+        proxy::{ForwardingHeaders, ProxyHandler, DEFAULT_PROXY_TIMEOUT},
+        recording::{RecordingHandler, ReplayHandler},
+        scoped::PathPattern,
+        stats::{StatsReqInterceptor, TrafficStats},
+        HttpHandler, HttpStatus, Method, ProblemDetailsErrorHandler, Server, DEFAULT_BACKLOG,
+        DEFAULT_HEAD_TIMEOUT, DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_URI_LENGTH,
+        DEFAULT_SHUTDOWN_GRACE,
+    },
     static_server::{
-        NoBodyOnHeadResInterceptor, NotFoundRenderResInterceptor, OnlyGetReqInterceptor,
-        StaticFileHandler,
+        templates, DashboardReqInterceptor, ErrorPageResInterceptor, IconMap, OverlayHandler,
+        PermissionRule, Permissions, RedirectReqInterceptor, RobotsPolicy, StaticFileHandler,
+        Theme, DASHBOARD_ROUTE, DEFAULT_MAX_UPLOAD_SIZE, DEFAULT_PAGE_SIZE, INTERNAL_ROOT,
     },
 };
+use log::LevelFilter;
+use tokio::signal;
 
 #[derive(Debug, Parser)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// `serve` when no command is given
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve the files of a directory
+    Serve(ServeArgs),
+    /// Forward the requests to an upstream HTTP server
+    Proxy(ProxyArgs),
+    /// Answer with the responses recorded by `proxy --record`
+    Replay(ReplayArgs),
+    /// Mock the operations of an OpenAPI 3 document
+    #[cfg(feature = "openapi")]
+    Mock(MockArgs),
+    /// Validate the serve configuration and print it without starting the server
+    Check(ServeArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct ServeArgs {
+    #[arg(short('w'), help("Allow browse in directories"))]
+    browsable: bool,
+    #[arg(long, default_value_t = Theme::Auto, help("Browsable UI theme: light, dark or auto"))]
+    theme: Theme,
+    #[arg(long, help("CSS file injected in the browsable UI"))]
+    css: Option<PathBuf>,
+    #[arg(long, help("Icon served as /favicon.ico when the root has none"))]
+    favicon: Option<PathBuf>,
+    #[arg(long, default_value = INTERNAL_ROOT, help("Route of the browsable UI assets"))]
+    internal_root: String,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help("Read the UI templates and assets from this httpr checkout as they change")
+    )]
+    dev_ui: Option<PathBuf>,
+    #[arg(
+        long,
+        help("Serve a live statistics dashboard under the internal root")
+    )]
+    dashboard: bool,
+    #[arg(
+        long = "mime",
+        value_name = "EXT=TYPE",
+        value_parser = parse_mime_override,
+        help("Content type for an extension, like wasm=application/wasm")
+    )]
+    mime_overrides: Vec<(String, String)>,
+    #[arg(
+        long = "download",
+        value_name = "EXT",
+        help("Always send the files with this extension as downloads")
+    )]
+    download_exts: Vec<String>,
+    #[arg(
+        long,
+        help("Declare the charset of the text files starting as UTF-8 or with a BOM")
+    )]
+    detect_charset: bool,
+    #[arg(
+        long,
+        value_name = "CHARSET",
+        help("Charset of the other text files, like windows-1252")
+    )]
+    default_charset: Option<String>,
+    #[arg(
+        long,
+        value_name = "MAX_BYTES",
+        help("Load the files up to this size in memory at startup")
+    )]
+    preload: Option<u64>,
+    #[cfg(feature = "watch")]
+    #[arg(
+        long,
+        requires = "preload",
+        help("Reload the preloaded files when they change on disk")
+    )]
+    watch: bool,
+    #[arg(long, help("Accept file uploads into the directories"))]
+    upload: bool,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = DEFAULT_MAX_UPLOAD_SIZE,
+        help("Largest upload request accepted")
+    )]
+    max_upload_size: u64,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help("Refuse to serve the files larger than this")
+    )]
+    max_file_size: Option<u64>,
+    #[arg(
+        long,
+        value_name = "N",
+        help("Files open and directories read at most at once")
+    )]
+    fs_concurrency: Option<usize>,
+    #[arg(
+        long,
+        requires = "credentials",
+        help("Delete, rename and create folders from the browsable UI")
+    )]
+    manage: bool,
+    #[arg(
+        long = "auth",
+        value_name = "USER:PASSWORD",
+        value_parser = parse_user,
+        group = "credentials",
+        help("Require Basic authentication with these credentials")
+    )]
+    users: Vec<(String, String)>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        group = "credentials",
+        help(
+            "Require Basic authentication with the users of an htpasswd file (bcrypt, md5-crypt)"
+        )
+    )]
+    htpasswd: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        requires = "credentials",
+        help("Only ask for credentials under this path")
+    )]
+    auth_prefix: Vec<String>,
+    #[arg(
+        long,
+        value_name = "FILE_NAME",
+        requires = "credentials",
+        help("Only ask for credentials in the directories holding this file")
+    )]
+    auth_marker: Option<String>,
+    #[arg(
+        long = "permission",
+        value_name = "PREFIX=PERMISSIONS",
+        help("Permissions under a path, like /incoming=read,upload (read, upload, overwrite, delete)")
+    )]
+    permission_rules: Vec<PermissionRule>,
+    #[arg(
+        long,
+        default_value_t = DEFAULT_PAGE_SIZE,
+        help("Entries of each directory listing page")
+    )]
+    page_size: usize,
+    #[arg(long, help("Apply the .httpr.toml files of the served directories"))]
+    dir_config: bool,
+    #[arg(
+        long,
+        help("Hide the paths matched by the .gitignore and .httprignore files")
+    )]
+    ignore_files: bool,
+    #[arg(long, help("Serve the files also as name.<hash>.ext, cached forever"))]
+    fingerprints: bool,
+    #[arg(long, help("Render the directory readme on top of the listings"))]
+    readme: bool,
+    #[arg(long, help("Let the listed directories be downloaded as .tar.gz"))]
+    archives: bool,
+    #[arg(
+        long,
+        value_name = "BASE_URL",
+        help("Generate /sitemap.xml with the public URL of the root")
+    )]
+    sitemap: Option<String>,
+    #[arg(
+        long,
+        value_name = "POLICY",
+        value_parser = parse_robots,
+        help("Answer /robots.txt when missing: allow, deny or a file with the body")
+    )]
+    robots: Option<RobotsPolicy>,
+    #[arg(long, help("Directory with custom SVG icons for the listings"))]
+    icons: Option<PathBuf>,
+    #[arg(
+        long,
+        help("Browsable UI locale, negotiated with the client by default")
+    )]
+    locale: Option<String>,
+    #[arg(
+        long = "error-page",
+        value_name = "STATUS=FILE",
+        value_parser = parse_error_page,
+        help("Page of an error status, like 403=errors/403.html, rendered when it ends with .hbs")
+    )]
+    error_pages: Vec<(HttpStatus, PathBuf)>,
+    #[arg(
+        long,
+        default_value = "302",
+        value_parser = parse_redirect_status,
+        help("Status of the directory slash and index redirects: 301, 302, 303, 307 or 308")
+    )]
+    redirect_status: HttpStatus,
+    #[cfg(feature = "thumbnails")]
+    #[arg(long, help("Show image previews of this size in the listings"))]
+    thumbnails: Option<u32>,
+    #[arg(
+        long = "overlay",
+        value_name = "DIR",
+        help("Serve the paths missing in the working directory from this one")
+    )]
+    overlays: Vec<PathBuf>,
+    #[command(flatten)]
+    server: ServerArgs,
+    working_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, clap::Args)]
+struct ProxyArgs {
+    #[arg(
+        long,
+        value_name = "URL",
+        help("Upstream server, like http://127.0.0.1:8080")
+    )]
+    upstream: String,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_PROXY_TIMEOUT.as_secs(),
+        help("Seconds allowed to the upstream to connect and answer")
+    )]
+    upstream_timeout: u64,
+    #[arg(
+        long,
+        help("Forward the Host header of the clients instead of the upstream one")
+    )]
+    preserve_host: bool,
+    #[arg(
+        long,
+        value_name = "HEADERS",
+        default_value_t = ForwardingHeaders::XForwarded,
+        help("Forwarding headers sent upstream: x-forwarded, forwarded, both or none")
+    )]
+    forwarding: ForwardingHeaders,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help("Cache the upstream responses in memory, up to this size")
+    )]
+    cache_size: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help("Append the exchanges to this file, for the replay command")
+    )]
+    record: Option<PathBuf>,
+    #[command(flatten)]
+    server: ServerArgs,
+}
+
+#[derive(Debug, clap::Args)]
+struct ReplayArgs {
+    #[arg(help("Exchanges recorded by proxy --record"))]
+    recording: PathBuf,
+    #[command(flatten)]
+    server: ServerArgs,
+}
+
+#[cfg(feature = "openapi")]
+#[derive(Debug, clap::Args)]
+struct MockArgs {
+    #[arg(help("OpenAPI 3 document, YAML or JSON"))]
+    document: PathBuf,
+    #[command(flatten)]
+    server: ServerArgs,
+}
+
+// Listener, limits, logging and compression of every server
+#[derive(Debug, clap::Args)]
+struct ServerArgs {
     #[arg(short, default_value_t = 4444)]
     port: u16,
     #[arg(short, default_value = "127.0.0.1")]
     bind: String,
-    #[arg(short('w'), help("Allow browse in directories"))]
-    browsable: bool,
-    working_dir: Option<PathBuf>,
+    #[arg(long, help("Send the responses without waiting to fill the packets"))]
+    tcp_nodelay: bool,
+    #[arg(long, help("Share the port with other processes listening on it"))]
+    reuse_port: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_BACKLOG,
+        help("Connections waiting to be accepted")
+    )]
+    backlog: u32,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = DEFAULT_MAX_URI_LENGTH,
+        help("Longest request target accepted, longer ones get 414")
+    )]
+    max_uri_length: usize,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = DEFAULT_MAX_BODY_SIZE,
+        help("Largest request body read, larger ones get 413")
+    )]
+    max_body_size: u64,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_HEAD_TIMEOUT.as_secs(),
+        help("Seconds allowed to send the request headers")
+    )]
+    head_timeout: u64,
+    #[arg(
+        long,
+        value_name = "SECS",
+        help("Seconds allowed to answer a request before giving up with 503")
+    )]
+    request_timeout: Option<u64>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_SHUTDOWN_GRACE.as_secs(),
+        help("Seconds allowed to the in-flight requests to finish on shutdown")
+    )]
+    shutdown_grace: u64,
+    #[arg(
+        long,
+        value_name = "N",
+        help("Simultaneous connections allowed to each client address")
+    )]
+    max_client_connections: Option<usize>,
+    #[arg(
+        long = "trusted-proxy",
+        value_name = "CIDR",
+        help("Proxy network allowed to tell the client address, like 10.0.0.0/8")
+    )]
+    trusted_proxies: Vec<IpNet>,
+    #[arg(
+        long,
+        value_name = "MS",
+        help("Delay the requests: 250 fixed, 100-500 uniform or 300~50 normal")
+    )]
+    delay: Option<Delay>,
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        requires = "delay",
+        value_parser = parse_path_pattern,
+        help("Delay only the requests matching this path pattern, like /api/**")
+    )]
+    delay_path: Vec<PathPattern>,
+    #[arg(
+        long,
+        value_name = "PROBABILITY:FAULT",
+        value_parser = parse_fault,
+        help("Fail some responses, like 0.1:503, 0.05:truncate or 0.01:reset")
+    )]
+    fault: Vec<(f64, Fault)>,
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        requires = "fault",
+        value_parser = parse_path_pattern,
+        help("Fail only the requests matching this path pattern")
+    )]
+    fault_path: Vec<PathPattern>,
+    #[arg(long, value_name = "FILE", help("Write the access log to this file"))]
+    access_log: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "never",
+        help("Rotate the access log: never, daily or at a size like 10M")
+    )]
+    access_log_rotation: Rotation,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_ACCESS_LOG_RETENTION,
+        help("Rotated access log files to keep")
+    )]
+    access_log_retention: usize,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help("Serve Prometheus metrics on this path, like /metrics")
+    )]
+    metrics: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help("Serve runtime statistics as JSON on this path, like /stats")
+    )]
+    stats: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "admin_token",
+        help("Serve the runtime settings admin API on this path, like /admin")
+    )]
+    admin: Option<String>,
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        value_parser = parse_token,
+        help("Bearer token of the admin API")
+    )]
+    admin_token: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help("Require an API key of this file, with name:key[:scope,scope] lines")
+    )]
+    api_keys: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        requires = "api_keys",
+        help("Header with the API key, X-API-Key unless this or --api-key-query is set")
+    )]
+    api_key_header: Vec<String>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        requires = "api_keys",
+        help("Query parameter with the API key")
+    )]
+    api_key_query: Vec<String>,
+    #[arg(long, help("Describe the errors with application/problem+json bodies"))]
+    problem_details: bool,
+    #[arg(long, help("Compress the responses with gzip or deflate"))]
+    compress: bool,
+    #[arg(
+        long,
+        requires = "compress",
+        default_value_t = DEFAULT_COMPRESSION_LEVEL,
+        value_parser = clap::value_parser!(u32).range(0..=9),
+        help("Compression level, from 0 to 9")
+    )]
+    compression_level: u32,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        requires = "compress",
+        default_value_t = DEFAULT_MIN_COMPRESSION_SIZE,
+        help("Smallest body to compress")
+    )]
+    compression_min_size: usize,
+    #[arg(
+        long = "compression-skip",
+        value_name = "TYPE",
+        requires = "compress",
+        help("Content type never compressed besides the compressed formats, like text/csv or image/*")
+    )]
+    compression_skipped: Vec<String>,
+    #[arg(long, help("Decode the request bodies sent with gzip or deflate"))]
+    decompress_requests: bool,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        requires = "decompress_requests",
+        default_value_t = DEFAULT_MAX_DECOMPRESSED_SIZE,
+        help("Largest decoded request body accepted")
+    )]
+    max_decompressed_size: u64,
+}
+
+impl ServerArgs {
+    fn address(&self) -> String {
+        format!("{}:{}", self.bind, self.port)
+    }
+
+    /// Server of `handler` listening as asked
+    fn server<H: HttpHandler>(&self, handler: H) -> Server<H> {
+        Server::builder(&self.address(), handler)
+            .with_nodelay(self.tcp_nodelay)
+            .with_reuse_port(self.reuse_port)
+            .with_backlog(self.backlog)
+            .with_max_uri_length(self.max_uri_length)
+            .build()
+    }
 }
 
 #[tokio::main]
-async fn main() {
-    let Args {
-        port,
-        mut bind,
-        browsable,
-        working_dir,
-    } = Args::parse();
-
-    let working_dir = match working_dir {
-        Some(p) => p,
+async fn main() -> ExitCode {
+    let command = match Cli::parse() {
+        Cli {
+            command: Some(command),
+            ..
+        } => command,
+        Cli { serve, .. } => Command::Serve(serve),
+    };
+
+    // The logger lets everything through so the admin API can raise the level
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(LevelFilter::Trace);
+    match env::var("RUST_LOG") {
+        Ok(filters) => logger.parse_filters(&filters).init(),
+        Err(_) => {
+            logger.init();
+            log::set_max_level(LevelFilter::Info);
+        }
+    }
+
+    match command {
+        Command::Serve(args) => serve(args).await,
+        Command::Proxy(args) => proxy(args).await,
+        Command::Replay(args) => replay(args).await,
+        #[cfg(feature = "openapi")]
+        Command::Mock(args) => mock(args).await,
+        Command::Check(args) => check(args),
+    }
+}
+
+async fn serve(args: ServeArgs) -> ExitCode {
+    let working_dir = match &args.working_dir {
+        Some(p) => p.clone(),
         None => env::current_dir().expect("Failed to get current directory"),
     };
 
-    bind.push_str(&format!(":{port}"));
+    let redirects = RedirectReqInterceptor::from_root(&working_dir)
+        .transpose()
+        .expect("Failed loading redirects");
+
+    if let Some(dir) = &args.dev_ui {
+        templates::enable_dev_mode(dir).expect("Failed loading the UI");
+    }
+
+    let has_auth = !args.users.is_empty() || args.htpasswd.is_some();
+    let protected = has_auth.then(|| {
+        let paths = args
+            .auth_prefix
+            .iter()
+            .fold(ProtectedPaths::new(), |paths, prefix| {
+                paths.with_prefix(prefix)
+            });
+
+        match &args.auth_marker {
+            Some(marker) => paths.with_marker(&working_dir, marker),
+            None => paths,
+        }
+    });
+
+    let handler = args.overlays.iter().fold(
+        OverlayHandler::new(static_handler(
+            working_dir.clone(),
+            &args,
+            protected.as_ref(),
+        )),
+        |overlay, root| overlay.with_layer(static_handler(root.clone(), &args, protected.as_ref())),
+    );
+
+    let ServeArgs {
+        theme,
+        internal_root,
+        dashboard,
+        upload,
+        max_upload_size,
+        manage,
+        users,
+        htpasswd,
+        error_pages,
+        server: server_args,
+        ..
+    } = args;
+
+    let mut server = server_args.server(handler);
+    setup_server(&mut server, &server_args);
+
+    // Upload bodies are refused before reading them when too large
+    if upload {
+        server.with_max_body_size(Some(max_upload_size.max(server_args.max_body_size)));
+    }
+
+    if let Some(protected) = protected {
+        let mut auth = users.iter().fold(
+            BasicAuthReqInterceptor::new("httpr").with_protected_paths(protected),
+            |auth, (user, password)| auth.with_user(user, password),
+        );
 
-    let log_env = env_logger::Env::default().default_filter_or("info");
-    env_logger::init_from_env(log_env);
+        if let Some(path) = htpasswd {
+            let htpasswd = Htpasswd::load(path).expect("Failed loading htpasswd");
+            auth = auth.with_credentials(Arc::new(htpasswd));
+        }
+
+        #[cfg(feature = "thumbnails")]
+        {
+            let route = format!("{}/{THUMBNAIL_ROUTE}", internal_root.trim_end_matches('/'));
+            auth = auth.with_internal_route(&route);
+        }
+
+        server.push_req_inter(Arc::new(auth));
+    }
+
+    let mut methods = vec![Method::Get, Method::Head];
+    if upload || manage {
+        methods.push(Method::Post);
+    }
+    if manage {
+        methods.push(Method::Delete);
+    }
+    server.push_req_inter(Arc::new(MethodFilterReqInterceptor::allow(methods)));
+
+    push_monitoring(&mut server, &server_args);
+
+    if dashboard {
+        let traffic = Arc::new(TrafficStats::new());
+        let path = format!("{}/{DASHBOARD_ROUTE}", internal_root.trim_end_matches('/'));
+        let dashboard = DashboardReqInterceptor::new(server.stats(), traffic.clone())
+            .with_path(&path)
+            .with_theme(theme);
+        server
+            .push_log_sink(traffic)
+            .push_req_inter(Arc::new(dashboard));
+    }
+
+    if let Some(redirects) = redirects {
+        server.push_req_inter(Arc::new(redirects));
+    }
+
+    let error_pages = error_pages
+        .into_iter()
+        .try_fold(ErrorPageResInterceptor::new(), |pages, (status, path)| {
+            pages.with_page(status, path)
+        })
+        .expect("Failed loading the error pages");
+
+    server.push_res_inter(Arc::new(error_pages));
+    push_compression(&mut server, &server_args);
+    push_faults(&mut server, &server_args);
+
+    server.run_until(shutdown_signal()).await.unwrap();
+    ExitCode::SUCCESS
+}
+
+/// Static handler of `root` with the options of `args`
+fn static_handler(
+    root: PathBuf,
+    args: &ServeArgs,
+    protected: Option<&ProtectedPaths>,
+) -> StaticFileHandler {
+    let mut handler = StaticFileHandler::new(root, args.browsable)
+        .expect("Failed creating handler")
+        .with_internal_root(&args.internal_root)
+        .with_theme(args.theme)
+        .with_readme(args.readme)
+        .with_archives(args.archives)
+        .with_page_size(args.page_size)
+        .with_redirect_status(args.redirect_status)
+        .expect("Invalid redirect status");
+
+    if let Some(css) = &args.css {
+        handler = handler.with_custom_css(css);
+    }
+
+    if let Some(favicon) = &args.favicon {
+        handler = handler.with_favicon(favicon);
+    }
+
+    for (ext, content_type) in &args.mime_overrides {
+        handler = handler.with_mime_override(ext, content_type);
+    }
+
+    for ext in &args.download_exts {
+        handler = handler.with_download_ext(ext.trim_start_matches('.'));
+    }
+
+    handler = handler.with_charset_detection(args.detect_charset);
+    if let Some(charset) = &args.default_charset {
+        handler = handler.with_default_charset(charset);
+    }
+
+    if let Some(base_url) = &args.sitemap {
+        handler = handler
+            .with_sitemap(base_url)
+            .expect("Invalid sitemap base URL");
+    }
+
+    if let Some(policy) = args.robots.clone() {
+        handler = handler.with_robots(policy);
+    }
+
+    if let Some(max_size) = args.preload {
+        handler = handler.preload(max_size).expect("Failed preloading files");
+    }
+
+    #[cfg(feature = "watch")]
+    if args.watch {
+        handler = handler.with_watcher().expect("Failed watching files");
+    }
+
+    if args.upload {
+        handler = handler.with_uploads(args.max_upload_size);
+    }
+
+    if let Some(max_file_size) = args.max_file_size {
+        handler = handler.with_max_file_size(max_file_size);
+    }
+
+    if let Some(fs_concurrency) = args.fs_concurrency {
+        handler = handler.with_fs_concurrency(fs_concurrency);
+    }
+
+    let permissions = args
+        .permission_rules
+        .iter()
+        .cloned()
+        .fold(Permissions::default(), Permissions::with_rule);
+
+    handler = handler
+        .with_management(args.manage)
+        .with_permissions(permissions)
+        .with_dir_configs(args.dir_config)
+        .with_ignore_files(args.ignore_files)
+        .with_fingerprints(args.fingerprints);
+
+    if let Some(icons) = &args.icons {
+        handler = handler.with_icons(IconMap::from_dir(icons).expect("Failed loading icons"));
+    }
+
+    if let Some(locale) = &args.locale {
+        handler = handler.with_locale(locale);
+    }
+
+    #[cfg(feature = "thumbnails")]
+    if let Some(size) = args.thumbnails {
+        handler = handler.with_thumbnails(size);
+    }
+
+    if let Some(protected) = protected {
+        handler = handler.with_protected_paths(protected.clone());
+    }
+
+    handler
+}
+
+async fn proxy(args: ProxyArgs) -> ExitCode {
+    let mut handler = ProxyHandler::new(&args.upstream)
+        .expect("Invalid upstream")
+        .with_timeout(Duration::from_secs(args.upstream_timeout))
+        .with_preserve_host(args.preserve_host)
+        .with_forwarding(args.forwarding);
+    if let Some(size) = args.cache_size {
+        handler = handler.with_cache(size);
+    }
+
+    match &args.record {
+        Some(path) => {
+            let recording =
+                RecordingHandler::new(handler, path).expect("Failed opening the recording");
+            run(recording, &args.server).await
+        }
+        None => run(handler, &args.server).await,
+    }
+}
+
+async fn replay(args: ReplayArgs) -> ExitCode {
+    let handler = ReplayHandler::from_file(&args.recording).expect("Failed loading the recording");
+    run(handler, &args.server).await
+}
+
+#[cfg(feature = "openapi")]
+async fn mock(args: MockArgs) -> ExitCode {
+    let handler = httpr::http::openapi::OpenApiMockHandler::from_file(&args.document)
+        .expect("Failed loading the OpenAPI document");
+    run(handler, &args.server).await
+}
+
+/// Serve `handler` with the shared server settings
+async fn run<H: HttpHandler>(handler: H, args: &ServerArgs) -> ExitCode {
+    let mut server = args.server(handler);
+    setup_server(&mut server, args);
+    push_monitoring(&mut server, args);
+    push_compression(&mut server, args);
+    push_faults(&mut server, args);
+
+    server.run_until(shutdown_signal()).await.unwrap();
+    ExitCode::SUCCESS
+}
+
+/// Limits of the server and the admin API, ahead of every other interceptor
+fn setup_server<H: HttpHandler>(server: &mut Server<H>, args: &ServerArgs) {
+    // Before the method filter, the admin API takes PUT requests
+    if let (Some(path), Some(token)) = (&args.admin, &args.admin_token) {
+        let admin =
+            AdminReqInterceptor::new(path, token, server.config()).expect("Invalid admin token");
+        server.push_req_inter(Arc::new(admin));
+    }
+
+    server
+        .with_head_timeout(Duration::from_secs(args.head_timeout))
+        .with_max_body_size(Some(args.max_body_size))
+        .with_request_timeout(args.request_timeout.map(Duration::from_secs))
+        .with_shutdown_grace(Duration::from_secs(args.shutdown_grace))
+        .with_max_client_connections(args.max_client_connections)
+        .with_trusted_proxies(TrustedProxies::new(args.trusted_proxies.clone()));
+
+    if let Some(path) = &args.api_keys {
+        let keys = ApiKeys::load(path).expect("Failed loading API keys");
+        let mut auth = ApiKeyReqInterceptor::new(Arc::new(keys));
+        for header in &args.api_key_header {
+            auth = auth.with_header(header);
+        }
+        for param in &args.api_key_query {
+            auth = auth.with_query(param);
+        }
+        server.push_req_inter(Arc::new(auth));
+    }
+
+    if let Some(delay) = args.delay {
+        let delay = args.delay_path.iter().cloned().fold(
+            DelayReqInterceptor::new(delay),
+            DelayReqInterceptor::with_path,
+        );
+        server.push_req_inter(Arc::new(delay));
+    }
+
+    if args.decompress_requests {
+        let decompression =
+            DecompressionReqInterceptor::new().with_max_size(args.max_decompressed_size);
+        server.push_req_inter(Arc::new(decompression));
+    }
+}
+
+/// Access log, error bodies, metrics and stats, behind the access control
+fn push_monitoring<H: HttpHandler>(server: &mut Server<H>, args: &ServerArgs) {
+    if let Some(path) = &args.access_log {
+        let access_log = AccessLog::open(path)
+            .expect("Failed opening access log")
+            .with_rotation(args.access_log_rotation)
+            .with_retention(args.access_log_retention);
+        server.push_log_sink(Arc::new(access_log));
+    }
+
+    if args.problem_details {
+        server.with_error_handler(Arc::new(ProblemDetailsErrorHandler));
+    }
+
+    if let Some(path) = &args.metrics {
+        let metrics = Arc::new(RouteMetrics::new());
+        server
+            .push_log_sink(metrics.clone())
+            .push_req_inter(Arc::new(MetricsReqInterceptor::new(path, metrics)));
+    }
+
+    if let Some(path) = &args.stats {
+        let stats = server.stats();
+        server.push_req_inter(Arc::new(StatsReqInterceptor::new(path, stats)));
+    }
+}
+
+/// Compression of the final responses, the last response interceptor
+fn push_compression<H: HttpHandler>(server: &mut Server<H>, args: &ServerArgs) {
+    if !args.compress {
+        return;
+    }
+
+    let skipped_types = DEFAULT_SKIPPED_TYPES
+        .iter()
+        .map(|t| t.to_string())
+        .chain(args.compression_skipped.iter().cloned())
+        .collect::<Vec<_>>();
+
+    server.push_res_inter(Arc::new(
+        CompressionResInterceptor::new()
+            .with_level(args.compression_level)
+            .with_min_size(args.compression_min_size)
+            .with_skipped_types(&skipped_types),
+    ));
+}
+
+/// Injected faults, after the compression so truncated bodies are the final ones
+fn push_faults<H: HttpHandler>(server: &mut Server<H>, args: &ServerArgs) {
+    if args.fault.is_empty() {
+        return;
+    }
+
+    let faults = args
+        .fault
+        .iter()
+        .try_fold(
+            FaultResInterceptor::new(),
+            |faults, (probability, fault)| faults.with_fault(*probability, *fault),
+        )
+        .expect("Invalid faults");
+    let faults = args
+        .fault_path
+        .iter()
+        .cloned()
+        .fold(faults, FaultResInterceptor::with_path);
+
+    server.push_res_inter(Arc::new(faults));
+}
+
+/// Load everything `args` points to and bind its address, then print it
+fn check(mut args: ServeArgs) -> ExitCode {
+    let mut problems = Vec::new();
+    let mut fail = |what: &str, e: &dyn std::fmt::Display| problems.push(format!("{what}: {e}"));
+
+    let working_dir = match &args.working_dir {
+        Some(p) => p.clone(),
+        None => env::current_dir().expect("Failed to get current directory"),
+    };
+
+    match StaticFileHandler::new(working_dir.clone(), args.browsable) {
+        Ok(handler) => {
+            if let Some(base_url) = &args.sitemap {
+                if let Err(e) = handler.with_sitemap(base_url) {
+                    fail("Sitemap", &e);
+                }
+            }
+        }
+        Err(e) => fail("Served directory", &e),
+    }
+
+    if let Some(Err(e)) = args.dev_ui.as_deref().map(templates::enable_dev_mode) {
+        fail("Dev UI", &e);
+    }
+
+    for root in &args.overlays {
+        if let Err(e) = StaticFileHandler::new(root.clone(), args.browsable) {
+            fail(&format!("Overlay {root:?}"), &e);
+        }
+    }
+
+    if let Some(Err(e)) = RedirectReqInterceptor::from_root(&working_dir) {
+        fail("Redirects", &e);
+    }
+
+    for (what, path) in [("Custom CSS", &args.css), ("Favicon", &args.favicon)] {
+        if let Some(path) = path.as_ref().filter(|p| !p.is_file()) {
+            fail(what, &format!("{path:?} is not a file"));
+        }
+    }
+
+    if let Some(Err(e)) = args.icons.as_ref().map(IconMap::from_dir) {
+        fail("Icons", &e);
+    }
+
+    if let Some(Err(e)) = args.htpasswd.as_ref().map(Htpasswd::load) {
+        fail("Htpasswd", &e);
+    }
+
+    if let Some(Err(e)) = args.server.api_keys.as_ref().map(ApiKeys::load) {
+        fail("API keys", &e);
+    }
+
+    for (status, path) in &args.error_pages {
+        if let Err(e) = ErrorPageResInterceptor::new().with_page(*status, path) {
+            fail(&format!("Error page {}", status.code()), &e);
+        }
+    }
+
+    if let Some(path) = &args.server.access_log {
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+        if dir.is_some_and(|d| !d.is_dir()) {
+            fail("Access log", &format!("{path:?} has no parent directory"));
+        }
+    }
+
+    let bind = args.server.address();
+    if let Err(e) = TcpListener::bind(&bind) {
+        fail(&format!("Address {bind}"), &e);
+    }
+
+    // Only the resolved values are printed, never the secrets
+    args.working_dir = Some(working_dir);
+    for (_, password) in &mut args.users {
+        *password = String::from("***");
+    }
+    if let Some(token) = &mut args.server.admin_token {
+        *token = String::from("***");
+    }
+    println!("{args:#?}");
+
+    if problems.is_empty() {
+        println!("Configuration OK");
+        return ExitCode::SUCCESS;
+    }
+
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    ExitCode::FAILURE
+}
+
+/// Ctrl-C, or `SIGTERM` on unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("Failed listening Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed listening SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+fn parse_user(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((user, password)) if !user.is_empty() => Ok((user.to_string(), password.to_string())),
+        _ => Err(format!("expected USER:PASSWORD, got {value}")),
+    }
+}
+
+fn parse_token(value: &str) -> Result<String, String> {
+    match value.trim() {
+        "" => Err(String::from("expected a non-empty token")),
+        _ => Ok(value.to_string()),
+    }
+}
+
+fn parse_redirect_status(value: &str) -> Result<HttpStatus, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(HttpStatus::from_code)
+        .filter(HttpStatus::is_redirect)
+        .ok_or_else(|| format!("expected a redirect status, got {value}"))
+}
+
+fn parse_error_page(value: &str) -> Result<(HttpStatus, PathBuf), String> {
+    let (status, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected STATUS=FILE, got {value}"))?;
+
+    match status.parse().ok().and_then(HttpStatus::from_code) {
+        Some(status) if status.code() >= 400 && !path.is_empty() => Ok((status, path.into())),
+        _ => Err(format!("expected an error STATUS=FILE, got {value}")),
+    }
+}
+
+fn parse_mime_override(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((ext, content_type)) if !ext.is_empty() && content_type.contains('/') => Ok((
+            ext.trim_start_matches('.').to_string(),
+            content_type.to_string(),
+        )),
+        _ => Err(format!("expected EXT=TYPE, got {value}")),
+    }
+}
+
+fn parse_fault(value: &str) -> Result<(f64, Fault), String> {
+    let (probability, fault) = value
+        .split_once(':')
+        .ok_or("expected PROBABILITY:FAULT, like 0.1:503")?;
+    let probability = probability
+        .parse()
+        .map_err(|_| "invalid probability, from 0 to 1")?;
+
+    Ok((probability, fault.parse()?))
+}
+
+fn parse_path_pattern(value: &str) -> Result<PathPattern, String> {
+    PathPattern::new(value).map_err(String::from)
+}
 
-    let handler = StaticFileHandler::new(working_dir, browsable).expect("Failed creating handler");
-    Server::new(bind, handler)
-        .push_req_inter(Arc::new(OnlyGetReqInterceptor))
-        .push_res_inter(Arc::new(NoBodyOnHeadResInterceptor))
-        .push_res_inter(Arc::new(NotFoundRenderResInterceptor))
-        .run()
-        .await
-        .unwrap()
+fn parse_robots(value: &str) -> Result<RobotsPolicy, String> {
+    match value {
+        "allow" => Ok(RobotsPolicy::AllowAll),
+        "deny" => Ok(RobotsPolicy::DenyAll),
+        path => std::fs::read_to_string(path)
+            .map(RobotsPolicy::Custom)
+            .map_err(|e| format!("expected allow, deny or a readable file: {e}")),
+    }
 }
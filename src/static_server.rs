@@ -3,58 +3,252 @@
 use core::str;
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fs::Metadata,
     ops::ControlFlow,
     path::{Component, Path, PathBuf},
+    sync::{Arc, Once},
+    time::SystemTime,
 };
 
 use async_trait::async_trait;
-use handlebars::{Assets, DIRECTORY_TEMPLATE, HBS, NOT_FOUND_TEMPLATE};
-use log::{debug, warn};
+use dir_config::{DirConfig, DirConfigs};
+use file_cache::FileSource;
+use fingerprint::{Fingerprints, IMMUTABLE_CACHE_CONTROL};
+use fs_limit::FsLimit;
+use handlebars::{Assets, DIRECTORY_TEMPLATE};
+use ignore_rules::IgnoreRules;
+use log::{debug, info, warn};
+use manage::{Action, MANAGE_ROUTE};
+use percent_encoding::percent_decode_str;
 use serde::Serialize;
+use strum_macros::{Display, EnumString};
 use tokio::{
-    fs::{read_dir, File},
-    io::AsyncReadExt,
+    fs::{read_dir, read_to_string, File},
+    io::{self, AsyncReadExt},
+};
+use url::Url;
+use url_path::{decode_path, encode_path, to_relative_path, to_url_path};
+use utils::{
+    add_validators, hex, mime_by_ext, mime_by_path, multipart_boundary, validators, variant_path,
 };
-use utils::{mime_by_ext, mime_by_path};
 
 use crate::http::{
-    HttpHandler, HttpStatus, InterceptorReq, InterceptorRes, Method, Named, Request, Response,
+    auth::{AuthUser, ProtectedPaths},
+    compression::CompressionResInterceptor,
+    conditional::{none_match, write_allowed},
+    method_filter::MethodFilterReqInterceptor,
+    negotiation::negotiate_language,
+    range::{
+        coalesce_ranges, multipart_end, multipart_part_head, parse_byte_ranges, ByteRange,
+        RangeError,
+    },
+    Body, HttpHandler, HttpStatus, InterceptorReq, InterceptorRes, InterceptorStack, Method, Named,
+    Request, Response,
 };
 
+pub use dashboard::{DashboardReqInterceptor, DASHBOARD_REFRESH, DASHBOARD_ROUTE};
+pub use dir_config::DIR_CONFIG_FILE_NAME;
+pub use embedded::EmbeddedDirHandler;
+pub use error_pages::ErrorPageResInterceptor;
+pub use file_cache::FileCache;
+pub use fingerprint::MANIFEST_ROUTE;
+pub use icons::{Icon, IconMap};
+pub use ignore_rules::IGNORE_FILE_NAME;
+pub use overlay::OverlayHandler;
+pub use permissions::{Permission, PermissionRule, Permissions};
+pub use redirects::{RedirectReqInterceptor, RedirectRule, REDIRECTS_FILE_NAME};
+pub use robots::{RobotsPolicy, ROBOTS_PATH};
+pub use sendfile::{SendfileResInterceptor, SENDFILE_HEADER};
+pub use sitemap::{SITEMAP_MAX_AGE, SITEMAP_PATH};
+#[cfg(feature = "thumbnails")]
+pub use thumbnails::THUMBNAIL_ROUTE;
+pub use upload::DEFAULT_MAX_UPLOAD_SIZE;
+
+mod archive;
+mod charset;
+mod dashboard;
+mod dir_config;
+mod embedded;
+mod error_pages;
+mod file_cache;
+mod fingerprint;
+mod fs_limit;
 mod handlebars;
+pub mod i18n;
+mod icons;
+mod ignore_rules;
+mod json_listing;
+mod manage;
+mod overlay;
+mod permissions;
+mod readme;
+mod redirects;
+mod robots;
+mod sendfile;
+mod sitemap;
+pub mod templates;
+mod text_listing;
+#[cfg(feature = "thumbnails")]
+mod thumbnails;
+mod upload;
+pub mod url_path;
 mod utils;
+#[cfg(feature = "watch")]
+mod watcher;
 
 enum FileMatch {
-    File(File),
+    File(PathBuf),
     Redirect(PathBuf),
     NotFound,
 }
 
+/// Refused write operation with the status and reason to answer
+type Rejection = (HttpStatus, &'static str);
+
+/// Listing of `dir` as HTML, JSON or text, as negotiated with the client
+fn listing_response(
+    request: &Request,
+    dir: &Path,
+    context: &TemplateDirCtx,
+) -> Result<Response, &'static str> {
+    let content_type = match text_listing::is_text_client(request) {
+        true => LISTING_TEXT,
+        false => match request.negotiate(&[LISTING_HTML, LISTING_JSON, LISTING_TEXT]) {
+            Some(t) => t,
+            None => return Ok(Response::new(HttpStatus::NotAcceptable)),
+        },
+    };
+
+    let body = match content_type {
+        LISTING_JSON => json_listing::render(
+            &dir.to_string_lossy(),
+            &context.files,
+            context.pagination.as_ref(),
+        )?,
+        LISTING_TEXT => text_listing::render(
+            &dir.to_string_lossy(),
+            &context.files,
+            context.pagination.as_ref(),
+        )
+        .into_bytes(),
+        _ => templates::render(DIRECTORY_TEMPLATE, context)?.into_bytes(),
+    };
+
+    let mut response = Response::new(HttpStatus::Ok);
+    response.add_header(("Content-Type", content_type));
+    response.add_header(("Vary", "Accept"));
+    response.add_body(&body);
+
+    Ok(response)
+}
+
+fn rejected((status, reason): Rejection) -> Response {
+    let mut response = Response::text(reason);
+    response.set_status(status);
+    response
+}
+
+/// Whether an authentication interceptor let in the user of `request`
+fn is_authenticated(request: &Request) -> bool {
+    request.extensions().get::<AuthUser>().is_some()
+}
+
+const LISTING_HTML: &str = "text/html; charset=utf-8";
+const LISTING_JSON: &str = "application/json";
+const LISTING_TEXT: &str = "text/plain; charset=utf-8";
+
 const ALLOWED_METHODS: [Method; 3] = [Method::Get, Method::Head, Method::Options];
 
 const INDEX_FILE_NAME: &str = "index.html";
-pub(in crate::static_server) const INTERNAL_ROOT: &str = "/__internal/";
+/// Path of the favicon, answered with the embedded one when the root has none
+pub const FAVICON_PATH: &str = "/favicon.ico";
+/// Assets only change with httpr upgrades, revalidated with their ETag
+const ASSETS_CACHE_CONTROL: &str = "public, max-age=604800";
+/// Directory entries of each listing page, unless set with `per_page`
+pub const DEFAULT_PAGE_SIZE: usize = 500;
+/// Upper bound of `per_page`
+const MAX_PAGE_SIZE: usize = 10_000;
+/// Bytes buffered at most to answer a multiple ranges request
+const MULTIPART_RANGES_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Default route of the browsable UI assets
+pub const INTERNAL_ROOT: &str = "/__internal/";
+
+/// How to handle the trailing slash of request paths
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Directories must end with `/` and files mustn't
+    #[default]
+    RedirectToSlash,
+    /// Paths mustn't end with `/`, except the root
+    RedirectToNoSlash,
+    /// Serve both forms without redirecting
+    Ignore,
+}
+
+/// Color scheme of the browsable UI
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumString, Display, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follow the client preference (`prefers-color-scheme`)
+    #[default]
+    Auto,
+}
 
 #[derive(Serialize)]
 struct TemplateDirCtx<'a> {
+    internal_root: &'a str,
+    locale: String,
+    i18n: i18n::Bundle,
+    theme: Theme,
+    custom_css: Option<String>,
     is_root: bool,
     dir: Cow<'a, str>,
+    parent: String,
+    readme: Option<readme::Readme>,
     bread_crums: Vec<TemplateBreadCrumCtx>,
     files: Vec<TemplateEntryCtx<'a>>,
+    /// Only for listings of several pages
+    pagination: Option<TemplatePageCtx>,
+    upload: bool,
+    /// Whether `?download=tar.gz` is answered
+    archive: bool,
+    manage: bool,
+    /// Management actions allowed in the directory
+    new_folder: bool,
+    delete: bool,
+}
+
+#[derive(Serialize)]
+struct TemplatePageCtx {
+    page: usize,
+    pages: usize,
+    previous: Option<String>,
+    next: Option<String>,
 }
 
 #[derive(Eq, PartialEq, Serialize)]
 struct TemplateEntryCtx<'a> {
     is_dir: bool,
     file_name: Cow<'a, str>,
+    href: String,
     mime: Option<String>,
+    icon: String,
+    thumbnail: Option<String>,
+    /// Files only
+    size: Option<u64>,
+    #[serde(skip)]
+    modified: Option<SystemTime>,
 }
 
 #[derive(Serialize)]
 struct TemplateBreadCrumCtx {
     name: String,
-    path: PathBuf,
+    path: String,
 }
 
 impl<'a> Ord for TemplateEntryCtx<'a> {
@@ -75,6 +269,44 @@ impl<'a> PartialOrd for TemplateEntryCtx<'a> {
 pub struct StaticFileHandler {
     root: PathBuf,
     is_browsable: bool,
+    internal_root: String,
+    internal_root_check: Once,
+    trailing_slash: TrailingSlash,
+    redirect_status: HttpStatus,
+    theme: Theme,
+    custom_css: Option<PathBuf>,
+    favicon: Option<PathBuf>,
+    locale: Option<String>,
+    icons: IconMap,
+    mime_overrides: HashMap<String, String>,
+    detect_charset: bool,
+    default_charset: Option<String>,
+    download_exts: HashSet<String>,
+    show_readme: bool,
+    max_upload_size: Option<u64>,
+    max_file_size: Option<u64>,
+    manage: bool,
+    protected: Option<ProtectedPaths>,
+    permissions: Permissions,
+    dir_configs: Option<DirConfigs>,
+    ignore_rules: Option<IgnoreRules>,
+    fingerprints: Option<Arc<Fingerprints>>,
+    page_size: usize,
+    languages: Option<LanguageVariants>,
+    sitemap: Option<sitemap::Sitemap>,
+    archives: bool,
+    robots: Option<RobotsPolicy>,
+    #[cfg(feature = "thumbnails")]
+    thumbnails: Option<thumbnails::Thumbnails>,
+    cache: Arc<FileCache>,
+    fs_limit: FsLimit,
+    #[cfg(feature = "watch")]
+    watcher: Option<notify::RecommendedWatcher>,
+}
+
+/// Languages served as `<file>.<lang>` variants, default language goes first
+struct LanguageVariants {
+    languages: Vec<String>,
 }
 
 impl StaticFileHandler {
@@ -89,32 +321,1093 @@ impl StaticFileHandler {
             return Err("Path is not a directory!");
         }
 
-        Ok(StaticFileHandler {
+        let handler = StaticFileHandler {
             root,
             is_browsable: browsable,
-        })
+            internal_root: String::new(),
+            internal_root_check: Once::new(),
+            trailing_slash: TrailingSlash::default(),
+            redirect_status: HttpStatus::Found,
+            theme: Theme::default(),
+            custom_css: None,
+            favicon: None,
+            locale: None,
+            icons: IconMap::default(),
+            mime_overrides: HashMap::new(),
+            detect_charset: false,
+            default_charset: None,
+            download_exts: HashSet::new(),
+            show_readme: false,
+            max_upload_size: None,
+            max_file_size: None,
+            manage: false,
+            protected: None,
+            permissions: Permissions::default(),
+            dir_configs: None,
+            ignore_rules: None,
+            fingerprints: None,
+            page_size: DEFAULT_PAGE_SIZE,
+            languages: None,
+            sitemap: None,
+            archives: false,
+            robots: None,
+            #[cfg(feature = "thumbnails")]
+            thumbnails: None,
+            cache: Arc::default(),
+            fs_limit: FsLimit::default(),
+            #[cfg(feature = "watch")]
+            watcher: None,
+        };
+
+        Ok(handler.with_internal_root(INTERNAL_ROOT))
+    }
+
+    /// Route of the browsable UI assets, [INTERNAL_ROOT] by default
+    ///
+    /// Files of the served directory take precedence over the assets, pick a route
+    /// that doesn't exist in it to keep the UI working.
+    pub fn with_internal_root(mut self, route: &str) -> Self {
+        self.internal_root = format!("/{}", route.trim_matches('/'));
+        self
+    }
+
+    fn check_internal_root(&self) {
+        let shadowed = self.root.join(self.internal_root.trim_start_matches('/'));
+        if shadowed.exists() {
+            warn!(
+                "{shadowed:?} shadows the internal route {}, choose another one",
+                self.internal_root
+            );
+        }
+    }
+
+    /// Trailing slash policy, [TrailingSlash::RedirectToSlash] by default
+    pub fn with_trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Status of the trailing slash and index redirects, `302 Found` by default
+    ///
+    /// Browsers cache the permanent ones, use them once the tree layout is settled.
+    pub fn with_redirect_status(mut self, status: HttpStatus) -> Result<Self, &'static str> {
+        if !status.is_redirect() {
+            return Err("Invalid redirect status!");
+        }
+
+        self.redirect_status = status;
+        Ok(self)
+    }
+
+    /// Color scheme of the browsable UI, [Theme::Auto] by default
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// CSS file injected in the browsable UI after the built-in styles
+    ///
+    /// The file is read on every listing so it can be edited while the server runs.
+    pub fn with_custom_css<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.custom_css = Some(path.into());
+        self
+    }
+
+    /// Locale of the browsable UI, negotiated with `Accept-Language` by default
+    ///
+    /// Missing messages of unknown locales fall back to [i18n::DEFAULT_LOCALE],
+    /// see [i18n::register_bundle] to add translations.
+    pub fn with_locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Show image previews of at most `size` pixels in the browsable listing
+    #[cfg(feature = "thumbnails")]
+    pub fn with_thumbnails(mut self, size: u32) -> Self {
+        self.thumbnails = Some(thumbnails::Thumbnails::new(size));
+        self
+    }
+
+    /// Thumbnail url for the listing entry `href`
+    #[cfg(feature = "thumbnails")]
+    fn thumbnail_href(&self, href: &str, mime: Option<&str>) -> Option<String> {
+        let is_image = mime.is_some_and(|m| m.starts_with("image/") && m != "image/svg+xml");
+        if self.thumbnails.is_none() || !is_image {
+            return None;
+        }
+
+        Some(format!(
+            "{}/{}{href}",
+            self.internal_root,
+            thumbnails::THUMBNAIL_ROUTE
+        ))
+    }
+
+    #[cfg(not(feature = "thumbnails"))]
+    fn thumbnail_href(&self, _: &str, _: Option<&str>) -> Option<String> {
+        None
+    }
+
+    #[cfg(feature = "thumbnails")]
+    async fn solve_thumbnail_request(&self, request: &Request, path: &Path) -> Option<Response> {
+        let thumbnails = self.thumbnails.as_ref()?;
+        let path = path.strip_prefix(thumbnails::THUMBNAIL_ROUTE).ok()?;
+        let href = to_url_path(path);
+
+        // Only for the images the request could read
+        let is_config = self.dir_configs.is_some() && path.ends_with(DIR_CONFIG_FILE_NAME);
+        if is_config
+            || self.is_ignored(path, false).await
+            || self.is_hidden_from(Some(request), &href).await
+        {
+            return Some(self.not_found());
+        }
+        let Ok(config) = self.dir_config(&href).await else {
+            return Some(Response::new(HttpStatus::InternalServerError));
+        };
+        if !self.allows_with(&config, &href, Permission::Read) {
+            return Some(Response::new(HttpStatus::Forbidden));
+        }
+
+        let data = match thumbnails.get(&self.root.join(path)).await {
+            Some(data) => data,
+            None => return Some(self.not_found()),
+        };
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "image/png"));
+        response.add_body(&data);
+
+        Some(response)
+    }
+
+    fn not_found(&self) -> Response {
+        let mut response = Response::not_found();
+        if let Some(locale) = &self.locale {
+            response.add_header(("Content-Language", locale));
+        }
+
+        response
+    }
+
+    /// Answer to a failed filesystem access, gone or unreadable files aren't server errors
+    fn fs_error(&self, error: io::Error) -> Response {
+        match error.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => self.not_found(),
+            io::ErrorKind::PermissionDenied => Response::new(HttpStatus::Forbidden),
+            _ => {
+                warn!("{error:?}");
+                Response::new(HttpStatus::InternalServerError)
+            }
+        }
+    }
+
+    /// Icon file answering [FAVICON_PATH] instead of the embedded one
+    ///
+    /// A `favicon.ico` in the root is still served first.
+    pub fn with_favicon<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.favicon = Some(path.into());
+        self
+    }
+
+    /// The favicon of the root, the configured one or the embedded one
+    async fn solve_favicon_request(&self, request: &Request) -> Response {
+        let on_disk = self.root.join(&FAVICON_PATH[1..]);
+        let path = match on_disk.is_file() {
+            true => Some(on_disk.as_path()),
+            false => self.favicon.as_deref(),
+        };
+
+        if let Some(path) = path {
+            match tokio::fs::read(path).await {
+                Ok(data) => {
+                    let mut response = Response::new(HttpStatus::Ok);
+                    response.add_header(("Content-Type", &mime_by_path(path)));
+                    response.add_body(&data);
+                    return response;
+                }
+                Err(e) => warn!("Can't read favicon {path:?}: {e}"),
+            }
+        }
+
+        Self::asset_response(request, Path::new(&FAVICON_PATH[1..]))
+            .unwrap_or_else(|| self.not_found())
+    }
+
+    /// Icons of the browsable listing entries
+    pub fn with_icons(mut self, icons: IconMap) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    /// Serve the files with extension `ext` (without the dot) as `content_type`
+    ///
+    /// Overrides are checked before guessing the type from the extension.
+    pub fn with_mime_override(mut self, ext: &str, content_type: &str) -> Self {
+        self.mime_overrides
+            .insert(ext.to_lowercase(), content_type.to_string());
+        self
+    }
+
+    /// Always send the files with extension `ext` (without the dot) as downloads
+    ///
+    /// Any file is sent as download when requested with the `download` query parameter.
+    pub fn with_download_ext(mut self, ext: &str) -> Self {
+        self.download_exts.insert(ext.to_lowercase());
+        self
+    }
+
+    fn is_download(&self, url: &Url, path: &Path) -> bool {
+        url.query_pairs().any(|(k, _)| k == "download")
+            || path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| self.download_exts.contains(&e.to_lowercase()))
+    }
+
+    fn mime_by_ext(&self, ext: &str) -> String {
+        match self.mime_overrides.get(&ext.to_lowercase()) {
+            Some(content_type) => content_type.clone(),
+            None => mime_by_ext(ext),
+        }
+    }
+
+    fn mime_by_path(&self, path: &Path) -> String {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => self.mime_by_ext(ext),
+            None => mime_by_path(path),
+        }
+    }
+
+    /// Declare the charset of the text files starting as UTF-8 or with a BOM
+    ///
+    /// The other text files get the default charset, if set.
+    pub fn with_charset_detection(mut self, enabled: bool) -> Self {
+        self.detect_charset = enabled;
+        self
+    }
+
+    /// Charset of the text files not detected as Unicode, like `windows-1252`
+    ///
+    /// Without detection every text file is declared with it.
+    pub fn with_default_charset(mut self, charset: &str) -> Self {
+        self.default_charset = Some(charset.to_string());
+        self
+    }
+
+    /// `content_type` of the file at `file_path` with its charset, when known
+    async fn declare_charset(&self, content_type: String, file_path: &Path) -> String {
+        if (!self.detect_charset && self.default_charset.is_none())
+            || !charset::needs_charset(&content_type)
+        {
+            return content_type;
+        }
+
+        let detected = match self.detect_charset {
+            true => charset::detect(&self.file_head(file_path).await),
+            false => None,
+        };
+        match detected.or(self.default_charset.as_deref()) {
+            Some(charset) => format!("{content_type}; charset={charset}"),
+            None => content_type,
+        }
+    }
+
+    /// First bytes of the file at `file_path`, to sniff its contents
+    async fn file_head(&self, file_path: &Path) -> Vec<u8> {
+        if let Some(data) = self.cache.get(file_path) {
+            return data[..data.len().min(charset::SNIFF_LEN)].to_vec();
+        }
+
+        let _permit = self.fs_limit.acquire().await;
+        let mut head = Vec::with_capacity(charset::SNIFF_LEN);
+        if let Ok(file) = File::open(file_path).await {
+            let _ = file
+                .take(charset::SNIFF_LEN as u64)
+                .read_to_end(&mut head)
+                .await;
+        }
+        head
+    }
+
+    /// Render the directory readme (`README.md`, `README.txt` or `index.txt`) on top
+    /// of the browsable listing
+    pub fn with_readme(mut self, show: bool) -> Self {
+        self.show_readme = show;
+        self
+    }
+
+    /// Accept `multipart/form-data` uploads into the directories with `POST`
+    ///
+    /// Requests with bodies over `max_size` bytes are rejected, like hidden file names
+    /// and existing files. The browsable listings get an upload form.
+    pub fn with_uploads(mut self, max_size: u64) -> Self {
+        self.max_upload_size = Some(max_size);
+        self
+    }
+
+    /// Refuse the files over `max_size` bytes with a `403`
+    ///
+    /// They are left out of the sitemaps and archives too. Give the `403` a
+    /// friendlier page with an [ErrorPageResInterceptor].
+    pub fn with_max_file_size(mut self, max_size: u64) -> Self {
+        self.max_file_size = Some(max_size);
+        self
+    }
+
+    /// Open at most `limit` files and read `limit` directories at once
+    ///
+    /// Served files hold their slot until the body is sent, archives until they
+    /// are done. Further requests wait for a free slot, the in memory files don't.
+    pub fn with_fs_concurrency(mut self, limit: usize) -> Self {
+        self.fs_limit = FsLimit::new(limit);
+        self
+    }
+
+    /// Interceptors of a static site served by this handler
+    ///
+    /// The methods it answers are let through and the rest get a `405`, error
+    /// responses get their page and the bodies are compressed when the client accepts it.
+    pub fn site_stack(&self) -> InterceptorStack {
+        InterceptorStack::new()
+            .with_req_inter(Arc::new(MethodFilterReqInterceptor::allow(
+                self.allowed_methods(),
+            )))
+            .with_res_inter(Arc::new(ErrorPageResInterceptor::new()))
+            .with_res_inter(Arc::new(CompressionResInterceptor::new()))
+    }
+
+    fn allowed_methods(&self) -> Vec<Method> {
+        let mut methods = ALLOWED_METHODS.to_vec();
+        if self.max_upload_size.is_some() || self.manage {
+            methods.push(Method::Post);
+        }
+        if self.manage {
+            methods.push(Method::Delete);
+        }
+
+        methods
+    }
+
+    /// Delete, rename and create folders from the browsable UI
+    ///
+    /// The listings post the actions as forms to the `manage` route under the internal
+    /// root, files and empty folders can also be removed with `DELETE`. Only the requests
+    /// of an [AuthUser] change the files, put an authentication interceptor like
+    /// [BasicAuthReqInterceptor](crate::http::auth::BasicAuthReqInterceptor) in front.
+    pub fn with_management(mut self, enabled: bool) -> Self {
+        self.manage = enabled;
+        self
+    }
+
+    /// Paths protected by the authentication interceptor in front
+    ///
+    /// Requests without an [AuthUser] can't upload into them, and the archives, the
+    /// manifest and the sitemap leave them out.
+    pub fn with_protected_paths(mut self, paths: ProtectedPaths) -> Self {
+        self.protected = Some(paths);
+        self
+    }
+
+    /// Whether the request path `path` is protected and `request` isn't authenticated
+    async fn is_hidden_from(&self, request: Option<&Request>, path: &str) -> bool {
+        let Some(protected) = &self.protected else {
+            return false;
+        };
+
+        let path = percent_decode_str(path).decode_utf8_lossy();
+        !request.is_some_and(is_authenticated) && protected.is_protected(&path).await
+    }
+
+    /// Limit what can be done under each path, [Permissions::default] otherwise
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Apply the [DIR_CONFIG_FILE_NAME] files of the served directories
+    ///
+    /// Their permissions replace the handler ones in their subtrees, so keep them
+    /// out of reach of untrusted writers. Uploads already reject hidden names.
+    pub fn with_dir_configs(mut self, enabled: bool) -> Self {
+        self.dir_configs = enabled.then(DirConfigs::default);
+        self
+    }
+
+    /// Hide the paths matched by the `.gitignore` and [IGNORE_FILE_NAME] files
+    ///
+    /// Ignored paths are left out of the listings, sitemaps and archives, and
+    /// requested directly they get a `404`.
+    pub fn with_ignore_files(mut self, enabled: bool) -> Self {
+        self.ignore_rules = enabled.then(IgnoreRules::default);
+        self
+    }
+
+    /// Whether `path`, relative to the root, is hidden by the ignore files
+    async fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match &self.ignore_rules {
+            Some(_) if path.ends_with(IGNORE_FILE_NAME) => true,
+            Some(rules) => rules.is_ignored(&self.root, path, is_dir).await,
+            None => false,
+        }
+    }
+
+    /// Serve the files also as `<name>.<hash>.<ext>`, cached forever
+    ///
+    /// The hash comes from the contents, so a changed file gets a new name and
+    /// outdated names a `404`. The names are listed at [MANIFEST_ROUTE] under the
+    /// internal root, and templates get them with `{{fingerprint "/app.js"}}`.
+    /// The helper is shared by all the templates, the last handler registers it.
+    pub fn with_fingerprints(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.fingerprints = None;
+            return self;
+        }
+
+        let fingerprints = Arc::new(Fingerprints::default());
+        let (root, hashes) = (self.root.clone(), fingerprints.clone());
+        templates::register_helper(
+            "fingerprint",
+            move |h: &templates::Helper,
+                  _: &templates::Handlebars,
+                  _: &templates::Context,
+                  _: &mut templates::RenderContext,
+                  out: &mut dyn templates::Output|
+                  -> templates::HelperResult {
+                let path = h.param(0).and_then(|p| p.value().as_str()).unwrap_or("");
+                let href = Self::fingerprinted_with(&root, &hashes, path);
+                out.write(href.as_deref().unwrap_or(path))?;
+                Ok(())
+            },
+        );
+
+        self.fingerprints = Some(fingerprints);
+        self
+    }
+
+    /// Fingerprinted URL path of the file at the URL `path`, when enabled
+    ///
+    /// `/css/site.css` gets like `/css/site.3f9c2d1a.css`.
+    pub fn fingerprinted(&self, path: &str) -> Option<String> {
+        Self::fingerprinted_with(&self.root, self.fingerprints.as_ref()?, path)
+    }
+
+    fn fingerprinted_with(root: &Path, fingerprints: &Fingerprints, path: &str) -> Option<String> {
+        let relative = to_relative_path(&decode_path(path)?)?;
+        let hash = fingerprints.hash(&root.join(&relative))?;
+        Some(to_url_path(&fingerprint::fingerprinted(&relative, &hash)))
+    }
+
+    /// Copy of `request` for the file its fingerprinted path names
+    ///
+    /// `None` for the other paths, and files with such names are served as is.
+    fn unfingerprinted(&self, request: &Request) -> Option<Request> {
+        let fingerprints = self.fingerprints.as_ref()?;
+        let url = request.url();
+        let relative = to_relative_path(&decode_path(url.path())?)?;
+        if self.root.join(&relative).exists() {
+            return None;
+        }
+
+        let (original, hash) = fingerprint::split(&relative)?;
+        if fingerprints.hash(&self.root.join(&original))? != hash {
+            return None;
+        }
+
+        let uri = match url.query() {
+            Some(query) => format!("{}?{query}", to_url_path(&original)),
+            None => to_url_path(&original),
+        };
+        Some(request.internal(&uri))
+    }
+
+    /// JSON object of the URL paths of the readable files and their fingerprinted ones
+    async fn solve_manifest_request(&self, request: &Request) -> Response {
+        let mut manifest = serde_json::Map::new();
+        for (relative, metadata) in self.walk(Path::new(""), Some(request)).await {
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let path = to_url_path(&relative);
+            if let Some(fingerprinted) = self.fingerprinted(&path) {
+                manifest.insert(path, fingerprinted.into());
+            }
+        }
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "application/json"));
+        response.add_header(("Cache-Control", "no-cache"));
+        response.add_body(&serde_json::to_vec(&manifest).unwrap());
+        response
+    }
+
+    /// Generate [SITEMAP_PATH] with the readable files, under `base_url`
+    ///
+    /// Hidden files and the config files are left out, `index.html` files are
+    /// listed as their directory. The sitemap is kept for [SITEMAP_MAX_AGE], a
+    /// `sitemap.xml` file in the root is served instead.
+    pub fn with_sitemap(mut self, base_url: &str) -> Result<Self, &'static str> {
+        self.sitemap = Some(sitemap::Sitemap::new(base_url)?);
+        Ok(self)
+    }
+
+    /// Answer [ROBOTS_PATH] following `policy` when the root has no such file
+    pub fn with_robots(mut self, policy: RobotsPolicy) -> Self {
+        self.robots = Some(policy);
+        self
+    }
+
+    /// Synthesized robots file, when enabled and not shadowed by a file
+    fn solve_robots_request(&self) -> Option<Response> {
+        let robots = self.robots.as_ref()?;
+        if self.root.join(&ROBOTS_PATH[1..]).exists() {
+            return None;
+        }
+
+        let sitemap = self.sitemap.as_ref().map(sitemap::Sitemap::url);
+        Some(Response::text(&robots.body(sitemap.as_deref())))
+    }
+
+    /// Readable files and directories under `dir`, paths relative to the root
+    ///
+    /// The hidden names and the config files are left out, and the protected paths
+    /// unless `request` is authenticated. Symbolic links aren't followed.
+    async fn walk(&self, dir: &Path, request: Option<&Request>) -> Vec<(PathBuf, Metadata)> {
+        let mut entries = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            let _permit = self.fs_limit.acquire().await;
+            let Ok(mut dir_reading) = read_dir(self.root.join(&dir)).await else {
+                continue;
+            };
+
+            while let Ok(Some(entry)) = dir_reading.next_entry().await {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if file_name.starts_with('.')
+                    || file_name == DIR_CONFIG_FILE_NAME
+                    || file_name == REDIRECTS_FILE_NAME
+                {
+                    continue;
+                }
+
+                let Ok(metadata) = entry.metadata().await else {
+                    continue;
+                };
+
+                let relative = dir.join(&file_name);
+                if self.is_ignored(&relative, metadata.is_dir()).await {
+                    continue;
+                }
+                let path = to_url_path(&relative);
+                if !self.allows(&path, Permission::Read).await
+                    || self.is_hidden_from(request, &path).await
+                {
+                    continue;
+                }
+
+                if metadata.is_dir() {
+                    pending.push(relative.clone());
+                } else if !metadata.is_file() || self.is_too_large(metadata.len()) {
+                    continue;
+                }
+                entries.push((relative, metadata));
+            }
+        }
+
+        entries
+    }
+
+    /// Files of the sitemap, walking the root
+    async fn sitemap_entries(&self) -> Vec<sitemap::SitemapEntry> {
+        let mut entries = Vec::new();
+
+        for (relative, metadata) in self.walk(Path::new(""), None).await {
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let dir = relative.parent().unwrap_or(Path::new(""));
+            let path = match to_url_path(dir) {
+                dir_path if relative.ends_with(INDEX_FILE_NAME) && dir_path == "/" => dir_path,
+                dir_path if relative.ends_with(INDEX_FILE_NAME) => dir_path + "/",
+                _ => to_url_path(&relative),
+            };
+
+            entries.push(sitemap::SitemapEntry {
+                path,
+                modified: metadata.modified().ok(),
+            });
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        if entries.len() > sitemap::MAX_SITEMAP_URLS {
+            warn!("Sitemap truncated to {} URLs", sitemap::MAX_SITEMAP_URLS);
+            entries.truncate(sitemap::MAX_SITEMAP_URLS);
+        }
+
+        entries
     }
 
-    async fn match_file(&self, mut path: &Path) -> FileMatch {
-        let request_path = path;
+    /// Let the listed directories be downloaded as `.tar.gz` with `?download=tar.gz`
+    ///
+    /// The archives hold the readable files, built as they are sent.
+    pub fn with_archives(mut self, enabled: bool) -> Self {
+        self.archives = enabled;
+        self
+    }
+
+    /// `.tar.gz` of the directory `request_path`, named after it
+    async fn solve_archive_request(&self, request: &Request, request_path: &Path) -> Response {
+        let relative = to_relative_path(&request_path.to_string_lossy()).unwrap_or_default();
+        let name = relative
+            .file_name()
+            .or(self
+                .root
+                .canonicalize()
+                .ok()
+                .as_deref()
+                .and_then(Path::file_name))
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("files"));
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "application/gzip"));
+        response.add_attachment(&format!("{name}.tar.gz"));
 
-        if let Ok(p) = path.strip_prefix("/") {
-            path = p;
+        if request.method() == Method::Head {
+            response.set_body(Body::unbounded(io::empty()));
+            return response;
+        }
+
+        let entries = self
+            .walk(&relative, Some(request))
+            .await
+            .into_iter()
+            .filter_map(|(path, _)| {
+                let archived = Path::new(&name).join(path.strip_prefix(&relative).ok()?);
+                Some((self.root.join(path), archived))
+            })
+            .collect();
+
+        let permit = self.fs_limit.acquire().await;
+        response.set_body(Body::unbounded(archive::tar_gz(entries, permit)));
+        response
+    }
+
+    /// Generated sitemap, when enabled and not shadowed by a file
+    async fn solve_sitemap_request(&self) -> Option<Response> {
+        let sitemap = self.sitemap.as_ref()?;
+        if self.root.join(&SITEMAP_PATH[1..]).exists() {
+            return None;
+        }
+
+        let xml = match sitemap.cached() {
+            Some(xml) => xml,
+            None => sitemap.generate(&self.sitemap_entries().await),
+        };
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "application/xml; charset=utf-8"));
+        response.add_body(xml.as_bytes());
+        Some(response)
+    }
+
+    /// Effective directory config of the request path `path`
+    async fn dir_config(&self, path: &str) -> Result<DirConfig, ()> {
+        let Some(dir_configs) = &self.dir_configs else {
+            return Ok(DirConfig::default());
+        };
+
+        let relative = decode_path(path)
+            .and_then(|path| to_relative_path(&path))
+            .ok_or(())?;
+
+        let dir = match self.root.join(&relative).is_dir() {
+            true => &relative,
+            false => relative.parent().unwrap_or(Path::new("")),
+        };
+
+        dir_configs.resolve(&self.root, dir).await
+    }
+
+    /// Whether the request path `path` has `permission`
+    async fn allows(&self, path: &str, permission: Permission) -> bool {
+        match self.dir_config(path).await {
+            Ok(config) => self.allows_with(&config, path, permission),
+            Err(()) => false,
+        }
+    }
+
+    /// [Self::allows] with the directory config already resolved
+    fn allows_with(&self, config: &DirConfig, path: &str, permission: Permission) -> bool {
+        if let Some(granted) = &config.permissions {
+            return granted.contains(&permission);
+        }
+
+        decode_path(path).is_some_and(|path| self.permissions.allows(&path, permission))
+    }
+
+    /// Decoded path of the request, checked by [HttpHandler::solve_request]
+    fn request_path(request: &Request) -> PathBuf {
+        PathBuf::from(decode_path(request.url().path()).unwrap_or_default())
+    }
+
+    /// Path under the root of the decoded request path `path`, without trailing slash
+    fn local_path(&self, path: &Path) -> PathBuf {
+        let relative = to_relative_path(&path.to_string_lossy()).unwrap_or_default();
+        self.root.join(relative)
+    }
+
+    /// Redirect to the file names `path`, keeping its trailing slash
+    fn redirect(&self, path: &Path) -> Response {
+        let path = path.to_string_lossy();
+        let slash = if path.len() > 1 && path.ends_with('/') {
+            "/"
+        } else {
+            ""
+        };
+
+        Response::redirect_with(
+            self.redirect_status,
+            to_url_path(Path::new(path.as_ref())) + slash,
+        )
+    }
+
+    fn is_manage_route(&self, request: &Request) -> bool {
+        let url = request.url();
+        Path::new(url.path())
+            .strip_prefix(&self.internal_root)
+            .is_ok_and(|route| route == Path::new(MANAGE_ROUTE))
+    }
+
+    /// Whether `request` can change the path under the root `relative`
+    ///
+    /// The ignored and protected paths are out of its reach like for reads.
+    async fn is_changeable(&self, request: &Request, relative: &Path) -> bool {
+        let is_dir = self.root.join(relative).is_dir();
+        !self.is_ignored(relative, is_dir).await
+            && !self
+                .is_hidden_from(Some(request), &to_url_path(relative))
+                .await
+    }
+
+    async fn solve_manage_request(&self, request: &Request, action: Action) -> Response {
+        if !is_authenticated(request) {
+            return rejected((HttpStatus::Forbidden, "Authentication required"));
+        }
+
+        let path = match manage::resolve(&self.root, action.path()) {
+            Ok(path) => path,
+            Err(rejection) => return rejected(rejection),
+        };
+
+        // Checked as the files the action changes, whatever the spelling of the path
+        let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+        let is_allowed = match &action {
+            Action::NewFolder { name, .. } => {
+                self.allows(&to_url_path(relative), Permission::Upload)
+                    .await
+                    && self.is_changeable(request, &relative.join(name)).await
+            }
+            Action::Delete { .. } => {
+                self.allows(&to_url_path(relative), Permission::Delete)
+                    .await
+                    && self.is_changeable(request, relative).await
+            }
+            Action::Rename { name, .. } => {
+                let renamed = relative.with_file_name(name);
+                self.allows(&to_url_path(relative), Permission::Delete)
+                    .await
+                    && self
+                        .allows(&to_url_path(&renamed), Permission::Upload)
+                        .await
+                    && self.is_changeable(request, relative).await
+                    && self.is_changeable(request, &renamed).await
+            }
+        };
+
+        if !is_allowed {
+            return rejected((HttpStatus::Forbidden, "Not allowed"));
+        }
+
+        // Another client may have changed what this one saw
+        let is_change = matches!(action, Action::Delete { .. } | Action::Rename { .. });
+        if is_change && !write_allowed(request, validators(&path).as_ref()) {
+            return rejected((HttpStatus::PreconditionFailed, "Changed since it was read"));
+        }
+
+        let is_root = path == self.root.components().collect::<PathBuf>();
+        let changed = match &action {
+            Action::NewFolder { name, .. } => manage::new_folder(&path, name).await,
+            _ if is_root => Err((HttpStatus::Forbidden, "The root can't be changed")),
+            Action::Delete { .. } => manage::delete(&path).await.map(|_| path.clone()),
+            Action::Rename { name, .. } => manage::rename(&path, name).await,
+        };
+
+        let changed = match changed {
+            Ok(changed) => changed,
+            Err(rejection) => {
+                debug!("{action:?} rejected: {}", rejection.1);
+                return rejected(rejection);
+            }
+        };
+
+        info!("{action:?} done");
+        self.cache.refresh(&path);
+        self.cache.refresh(&changed);
+
+        if request.method() == Method::Delete {
+            return Response::no_content();
+        }
+
+        // Back to the listing where the action was made
+        let listing = match &action {
+            Action::NewFolder { path, .. } => path.as_str(),
+            _ => action
+                .path()
+                .trim_end_matches('/')
+                .rsplit_once('/')
+                .map_or("/", |(parent, _)| parent),
+        };
+
+        let mut response = Response::new(HttpStatus::SeeOther);
+        response.add_header(("Location", &format!("{}/", listing.trim_end_matches('/'))));
+        response
+    }
+
+    async fn solve_upload_request(&self, request: &Request, max_size: u64) -> Response {
+        let url = request.url();
+        let request_path = &Self::request_path(request);
+        let dir = self.local_path(request_path);
+
+        if !dir.exists() {
+            return self.not_found();
+        }
+
+        if !dir.is_dir() {
+            let methods = self.allowed_methods();
+            let methods = methods.iter().filter(|m| **m != Method::Post).copied();
+            return Response::method_not_allowed(&methods.collect::<Vec<_>>());
+        }
+
+        if !self.allows(url.path(), Permission::Upload).await
+            || self.is_hidden_from(Some(request), url.path()).await
+        {
+            return rejected((HttpStatus::Forbidden, "Not allowed"));
+        }
+
+        let overwrite = self.allows(url.path(), Permission::Overwrite).await;
+        let saved = match upload::save_files(request, &dir, max_size, overwrite).await {
+            Ok(saved) => saved,
+            Err(rejection) => {
+                debug!("Upload rejected: {}", rejection.1);
+                return rejected(rejection);
+            }
+        };
+
+        for name in &saved {
+            info!("Uploaded {:?}", dir.join(name));
+            self.cache.refresh(&dir.join(name));
+        }
+
+        // Form posts without scripts go back to the listing
+        if request.negotiate(&[LISTING_HTML, LISTING_JSON]) != Some(LISTING_JSON) {
+            let mut response = Response::new(HttpStatus::SeeOther);
+            response.add_header(("Location", url.path()));
+            return response;
+        }
+
+        let mut response = Response::new(HttpStatus::Created);
+        response.add_header(("Content-Type", LISTING_JSON));
+        response.add_body(&serde_json::to_vec(&serde_json::json!({ "files": saved })).unwrap());
+        response
+    }
+
+    /// Directory entries of each listing page, [DEFAULT_PAGE_SIZE] by default
+    ///
+    /// Clients pick the page with the `page` query parameter, starting at 1, and may
+    /// change the size with `per_page`.
+    pub fn with_page_size(mut self, size: usize) -> Self {
+        self.page_size = size.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    fn paginate<T>(&self, url: &Url, entries: Vec<T>) -> (Vec<T>, Option<TemplatePageCtx>) {
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .and_then(|(_, v)| v.parse::<usize>().ok())
+        };
+
+        let per_page = query("per_page").map_or(self.page_size, |n| n.clamp(1, MAX_PAGE_SIZE));
+        let pages = entries.len().div_ceil(per_page).max(1);
+        let page = query("page").unwrap_or(1).clamp(1, pages);
+
+        let entries = entries
+            .into_iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .collect();
+
+        if pages == 1 {
+            return (entries, None);
+        }
+
+        let href = |page: usize| match per_page == self.page_size {
+            true => format!("?page={page}"),
+            false => format!("?page={page}&per_page={per_page}"),
+        };
+
+        let pagination = TemplatePageCtx {
+            page,
+            pages,
+            previous: (page > 1).then(|| href(page - 1)),
+            next: (page < pages).then(|| href(page + 1)),
+        };
+
+        (entries, Some(pagination))
+    }
+
+    /// Serve language variants of the files (`page.html.en`, `page.html.es`, ...)
+    ///
+    /// The variant is selected with the `Accept-Language` header, falling back to
+    /// `default` and then to the file without language suffix.
+    pub fn with_languages(mut self, languages: &[&str], default: &str) -> Self {
+        let mut all = vec![default.to_string()];
+        all.extend(
+            languages
+                .iter()
+                .filter(|l| **l != default)
+                .map(|l| l.to_string()),
+        );
+
+        self.languages = Some(LanguageVariants { languages: all });
+        self
+    }
+
+    /// Load the files up to `max_file_size` bytes in memory to serve them from there
+    ///
+    /// Changes made on disk afterwards aren't served unless the root is watched, see
+    /// `with_watcher` of the `watch` feature.
+    pub fn preload(self, max_file_size: u64) -> Result<Self, &'static str> {
+        let (files, bytes) = self
+            .cache
+            .preload(&self.root, max_file_size)
+            .map_err(|_| "Failed to preload the files!")?;
+
+        info!("Preloaded {files} files, {bytes} bytes");
+        Ok(self)
+    }
+
+    /// Watch the root to keep the [preloaded](Self::preload) files up to date
+    #[cfg(feature = "watch")]
+    pub fn with_watcher(mut self) -> Result<Self, &'static str> {
+        let watcher = watcher::watch(&self.root, self.cache.clone())
+            .map_err(|_| "Failed to watch the files!")?;
+
+        self.watcher = Some(watcher);
+        Ok(self)
+    }
+
+    fn language_variant(&self, file_path: &Path, accept_language: Option<&str>) -> Option<PathBuf> {
+        let variants = self.languages.as_ref()?;
+
+        let existing = variants
+            .languages
+            .iter()
+            .map(String::as_str)
+            .filter(|lang| variant_path(file_path, lang).is_file())
+            .collect::<Vec<_>>();
+
+        let default = variants.languages.first().map(String::as_str);
+
+        negotiate_language(accept_language, &existing)
+            .or(default.filter(|d| existing.contains(d)))
+            .map(|lang| variant_path(file_path, lang))
+    }
+
+    /// Location to redirect `path` according to the trailing slash policy
+    fn slash_redirect(&self, path: &Path, is_dir: bool) -> Option<PathBuf> {
+        let path = path.to_string_lossy();
+        if path == "/" {
+            return None;
+        }
+
+        let wants_slash = match self.trailing_slash {
+            TrailingSlash::RedirectToSlash => is_dir,
+            TrailingSlash::RedirectToNoSlash => false,
+            TrailingSlash::Ignore => return None,
+        };
+
+        match (path.ends_with('/'), wants_slash) {
+            (false, true) => Some(PathBuf::from(format!("{path}/"))),
+            (true, false) => Some(PathBuf::from(path.trim_end_matches('/'))),
+            _ => None,
+        }
+    }
+
+    async fn match_file(
+        &self,
+        request_path: &Path,
+        accept_language: Option<&str>,
+        config: &DirConfig,
+    ) -> FileMatch {
+        let file_path = self.local_path(request_path);
+        if let Some(variant) = self.language_variant(&file_path, accept_language) {
+            return FileMatch::File(variant);
         }
 
-        let file_path = self.root.join(path);
         if !file_path.exists() {
             return FileMatch::NotFound;
         }
 
+        if let Some(location) = self.slash_redirect(request_path, file_path.is_dir()) {
+            return FileMatch::Redirect(location);
+        }
+
         if file_path.is_dir() {
-            let mut request_path = request_path.to_path_buf();
-            request_path.push(INDEX_FILE_NAME);
+            let index = Self::index_file(&file_path, config).unwrap_or(INDEX_FILE_NAME);
+            return FileMatch::Redirect(request_path.join(index));
+        }
 
-            return FileMatch::Redirect(request_path);
+        FileMatch::File(file_path)
+    }
+
+    /// First index file of the config found in `dir`
+    fn index_file<'a>(dir: &Path, config: &'a DirConfig) -> Option<&'a str> {
+        let names = config.index.as_ref()?;
+
+        names
+            .iter()
+            .find(|name| dir.join(name).is_file())
+            .or(names.first())
+            .map(String::as_str)
+    }
+
+    async fn open(&self, file_path: &Path) -> io::Result<FileSource> {
+        match self.cache.get(file_path) {
+            Some(data) => Ok(FileSource::Memory(data)),
+            None => {
+                let permit = self.fs_limit.acquire().await;
+                let file = File::open(file_path).await?;
+                Ok(FileSource::Disk(file, permit))
+            }
         }
+    }
 
-        FileMatch::File(File::open(&file_path).await.expect("File access"))
+    fn is_too_large(&self, size: u64) -> bool {
+        self.max_file_size.is_some_and(|max| size > max)
+    }
+
+    /// Size of the file, without opening it
+    async fn file_size(&self, file_path: &Path) -> io::Result<u64> {
+        match self.cache.get(file_path) {
+            Some(data) => Ok(data.len() as u64),
+            None => Ok(tokio::fs::metadata(file_path).await?.len()),
+        }
     }
 
     fn generate_bread_crum(path: &Path) -> Vec<TemplateBreadCrumCtx> {
@@ -127,13 +1420,13 @@ impl StaticFileHandler {
                 Component::RootDir => {
                     bread_crums.push(TemplateBreadCrumCtx {
                         name: String::from("/"),
-                        path: current_path.clone(),
+                        path: encode_path(&current_path.to_string_lossy()),
                     });
                 }
                 Component::Normal(n) => {
                     bread_crums.push(TemplateBreadCrumCtx {
                         name: n.to_string_lossy().to_string(),
-                        path: current_path.clone(),
+                        path: encode_path(&current_path.to_string_lossy()),
                     });
                 }
                 _ => unreachable!("Never here!"),
@@ -143,112 +1436,426 @@ impl StaticFileHandler {
         bread_crums
     }
 
-    async fn solve_file_request(&self, request: &Request) -> Result<Response, &'static str> {
+    async fn solve_file_request(
+        &self,
+        request: &Request,
+        config: &DirConfig,
+    ) -> Result<Response, &'static str> {
         let url = request.url();
-        let path = Path::new(url.path());
+        let path = &Self::request_path(request);
 
         debug!("Reading {:?}", path);
 
-        let mut file = match self.match_file(path).await {
-            FileMatch::File(f) => f,
-            FileMatch::Redirect(p) => return Ok(Response::redirect(p)),
-            FileMatch::NotFound => return Ok(Response::not_found()),
+        let file_path = match self
+            .match_file(path, request.header("accept-language"), config)
+            .await
+        {
+            FileMatch::File(file_path) => file_path,
+            FileMatch::Redirect(p) => return Ok(self.redirect(&p)),
+            FileMatch::NotFound => return Ok(self.not_found()),
         };
 
-        let mut body = Vec::new();
+        let validators = validators(&file_path);
+        if let (Some(tags), Some(validators)) = (request.header("if-none-match"), &validators) {
+            if none_match(tags, &validators.etag) {
+                let mut response = Response::new(HttpStatus::NotModified);
+                add_validators(&mut response, validators);
+                return Ok(response);
+            }
+        }
 
-        if let Err(e) = file.read_to_end(&mut body).await {
-            warn!("{e:?}");
-            return Ok(Response::new(HttpStatus::InternalServerError));
+        let size = match self.file_size(&file_path).await {
+            Ok(size) => size,
+            Err(e) => return Ok(self.fs_error(e)),
+        };
+
+        if self.is_too_large(size) {
+            return Ok(rejected((
+                HttpStatus::Forbidden,
+                "File too large to be served",
+            )));
         }
 
-        let mut response = Response::new(HttpStatus::Ok);
+        let ranges = match request.header("range").map(|r| parse_byte_ranges(r, size)) {
+            Some(Ok(ranges)) => coalesce_ranges(ranges),
+            Some(Err(RangeError::Unsatisfiable)) => {
+                let mut response = Response::new(HttpStatus::RangeNotSatisfiable);
+                response.add_header(("Content-Range", &format!("bytes */{size}")));
+                return Ok(response);
+            }
+            _ => vec![],
+        };
+
+        // Sniffed before the file holds its filesystem slot
+        let content_type = self
+            .declare_charset(self.mime_by_path(path), &file_path)
+            .await;
+
+        // HEAD only needs the metadata, the body is announced but never read
+        let mut file = match request.method() {
+            Method::Head => None,
+            _ => match self.open(&file_path).await {
+                Ok(file) => Some(file),
+                Err(e) => return Ok(self.fs_error(e)),
+            },
+        };
 
-        response.add_header(("Content-Type", &mime_by_path(path)));
-        response.add_body(&body);
+        let mut response = match ranges.as_slice() {
+            [range] => {
+                let mut response = Response::new(HttpStatus::PartialContent);
+                response.add_header(("Content-Range", &range.content_range(size)));
+                response.add_header(("Content-Type", &content_type));
+
+                match file {
+                    Some(file) => {
+                        if let Err(e) = file.send(&mut response, Some(range)).await {
+                            warn!("{e:?}");
+                            return Ok(Response::new(HttpStatus::InternalServerError));
+                        }
+                    }
+                    None => response.stream_body(io::empty(), range.len()),
+                }
+                response
+            }
+            ranges
+                if ranges.len() > 1
+                    && ranges.iter().map(ByteRange::len).sum::<u64>() <= MULTIPART_RANGES_LIMIT =>
+            {
+                let boundary = multipart_boundary();
+                let mut body = Vec::new();
+                let mut unread = 0;
+
+                for range in ranges {
+                    body.extend(multipart_part_head(&boundary, &content_type, range, size).bytes());
+
+                    let Some(file) = &mut file else {
+                        unread += range.len();
+                        continue;
+                    };
+                    match file.read_range(range).await {
+                        Ok(part) => body.extend(part),
+                        Err(e) => {
+                            warn!("{e:?}");
+                            return Ok(Response::new(HttpStatus::InternalServerError));
+                        }
+                    }
+                }
+                body.extend(multipart_end(&boundary).bytes());
+
+                let mut response = Response::new(HttpStatus::PartialContent);
+                response.add_header((
+                    "Content-Type",
+                    &format!("multipart/byteranges; boundary={boundary}"),
+                ));
+                match file {
+                    Some(_) => response.add_body(&body),
+                    None => response.stream_body(io::empty(), body.len() as u64 + unread),
+                }
+                response
+            }
+            // Too much to buffer, the ranges are ignored
+            _ => {
+                let mut response = Response::new(HttpStatus::Ok);
+                response.add_header(("Content-Type", &content_type));
+
+                match file {
+                    Some(file) => {
+                        if let Err(e) = file.send(&mut response, None).await {
+                            warn!("{e:?}");
+                            return Ok(Response::new(HttpStatus::InternalServerError));
+                        }
+                    }
+                    None => response.stream_body(io::empty(), size),
+                }
+                response
+            }
+        };
+
+        if self.is_download(&url, path) {
+            if let Some(name) = path.file_name() {
+                let name = name.to_string_lossy();
+                response.add_attachment(&name);
+            }
+        }
+        response.add_header(("Accept-Ranges", "bytes"));
+        if let Some(validators) = &validators {
+            add_validators(&mut response, validators);
+        }
+        if self.languages.is_some() {
+            response.add_header(("Vary", "Accept-Language"));
+        }
 
         Ok(response)
     }
 
-    async fn solve_browsable_request(&self, request: &Request) -> Result<Response, &'static str> {
-        let url = request.url();
-        let request_path = Path::new(url.path());
+    async fn solve_internal_request(&self, request: &Request, internal_path: &Path) -> Response {
+        #[cfg(feature = "thumbnails")]
+        if let Some(response) = self.solve_thumbnail_request(request, internal_path).await {
+            return response;
+        }
+
+        if internal_path == Path::new(&FAVICON_PATH[1..]) {
+            return self.solve_favicon_request(request).await;
+        }
 
-        // Internal access
-        if request_path.starts_with(INTERNAL_ROOT) {
-            let internal_path = request_path.strip_prefix(INTERNAL_ROOT).unwrap();
+        Self::asset_response(request, internal_path).unwrap_or_else(|| self.not_found())
+    }
+
+    /// Embedded asset at `path`, revalidated with its hash
+    ///
+    /// In dev mode it is read from disk and never cached.
+    fn asset_response(request: &Request, path: &Path) -> Option<Response> {
+        if templates::is_dev_mode() {
+            let data = handlebars::asset(&path.to_string_lossy())?;
+            let mut response = Response::new(HttpStatus::Ok);
+            response.add_header(("Content-Type", &mime_by_path(path)));
+            response.add_header(("Cache-Control", "no-store"));
+            response.add_body(&data);
+            return Some(response);
+        }
 
-            if let Some(asset) = Assets::get(internal_path.to_str().unwrap()) {
-                let ext = internal_path
-                    .extension()
-                    .map(|ext| ext.to_str().unwrap())
-                    .unwrap();
+        let asset = Assets::get(&path.to_string_lossy())?;
+        let etag = format!("\"{}\"", hex(&asset.metadata.sha256_hash()[..16]));
 
+        let mut response = match request.header("if-none-match") {
+            Some(tags) if none_match(tags, &etag) => Response::new(HttpStatus::NotModified),
+            _ => {
                 let mut response = Response::new(HttpStatus::Ok);
-                response.add_header(("Content-Type", &mime_by_ext(ext)));
+                response.add_header(("Content-Type", &mime_by_path(path)));
                 response.add_body(&asset.data);
-
-                return Ok(response);
+                response
             }
+        };
+
+        response.add_header(("ETag", &etag));
+        response.add_header(("Cache-Control", ASSETS_CACHE_CONTROL));
+
+        Some(response)
+    }
+
+    async fn solve_browsable_request(
+        &self,
+        request: &Request,
+        config: &DirConfig,
+    ) -> Result<Response, &'static str> {
+        let url = request.url();
+        let request_path = &Self::request_path(request);
 
-            return Ok(Response::not_found());
+        let absolute_path = self.local_path(request_path);
+
+        let has_variant = self
+            .language_variant(&absolute_path, request.header("accept-language"))
+            .is_some();
+
+        if !absolute_path.exists() && !has_variant {
+            return Ok(self.not_found());
         }
 
-        let path = if let Ok(p) = request_path.strip_prefix("/") {
-            p
-        } else {
-            request_path
-        };
+        if absolute_path.is_file() || has_variant {
+            return self.solve_file_request(request, config).await;
+        }
 
-        let absolute_path = self.root.join(path);
-        if !absolute_path.exists() {
-            return Ok(Response::not_found());
+        if let Some(location) = self.slash_redirect(request_path, true) {
+            return Ok(self.redirect(&location));
         }
 
-        if absolute_path.is_file() {
-            return self.solve_file_request(request).await;
+        // Index files configured for the directory take the place of the listing
+        if let Some(index) = Self::index_file(&absolute_path, config) {
+            if absolute_path.join(index).is_file() {
+                return Ok(self.redirect(&request_path.join(index)));
+            }
         }
 
-        if !request_path.to_string_lossy().ends_with("/") {
-            let location = format!("{}/", request_path.display());
-            return Ok(Response::redirect(location));
+        let wants_archive = url
+            .query_pairs()
+            .any(|(k, v)| k == "download" && v == archive::TAR_GZ);
+        if self.archives && wants_archive {
+            return Ok(self.solve_archive_request(request, request_path).await);
         }
 
-        let mut dir_reading = read_dir(absolute_path).await.unwrap();
+        let base = request_path.to_string_lossy();
+        let base = base.trim_end_matches('/');
+        let dir_suffix = match self.trailing_slash {
+            TrailingSlash::RedirectToSlash => "/",
+            _ => "",
+        };
+
+        let _permit = self.fs_limit.acquire().await;
+        let readme = match self.show_readme {
+            true => readme::find_readme(&absolute_path).await,
+            false => None,
+        };
+
+        let relative_dir = to_relative_path(&request_path.to_string_lossy()).unwrap_or_default();
+        let mut dir_reading = match read_dir(absolute_path).await {
+            Ok(dir_reading) => dir_reading,
+            Err(e) => return Ok(self.fs_error(e)),
+        };
         let mut files = Vec::new();
-        while let Some(entry) = dir_reading.next_entry().await.unwrap() {
+        while let Some(entry) = dir_reading.next_entry().await.transpose() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => return Ok(self.fs_error(e)),
+            };
             let file_name = entry.file_name().to_string_lossy().into_owned();
-            let is_dir = entry.file_type().await.unwrap().is_dir();
+            if self.dir_configs.is_some() && file_name == DIR_CONFIG_FILE_NAME {
+                continue;
+            }
 
-            let mime = entry
-                .path()
-                .extension()
-                .and_then(|v| v.to_str())
-                .map(mime_by_ext);
+            // Entries removed meanwhile are left out
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let is_dir = file_type.is_dir();
+            if self
+                .is_ignored(&relative_dir.join(&file_name), is_dir)
+                .await
+            {
+                continue;
+            }
+            let metadata = entry.metadata().await.ok();
+
+            let entry_path = entry.path();
+            let ext = entry_path.extension().and_then(|v| v.to_str());
+            let mime = ext.map(|e| self.mime_by_ext(e));
+
+            let href = if is_dir {
+                encode_path(&format!("{base}/{file_name}{dir_suffix}"))
+            } else {
+                encode_path(&format!("{base}/{file_name}"))
+            };
+
+            let thumbnail = self.thumbnail_href(&href, mime.as_deref());
+            let icon = if is_dir {
+                self.icons.folder_markup()
+            } else {
+                self.icons.file_markup(ext, mime.as_deref())
+            };
 
             let file = TemplateEntryCtx {
                 is_dir,
                 file_name: Cow::Owned(file_name),
+                href,
                 mime,
+                icon,
+                thumbnail,
+                size: metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+                modified: metadata.and_then(|m| m.modified().ok()),
             };
 
             files.push(file);
         }
 
         files.sort();
+        let (files, pagination) = self.paginate(&url, files);
+
+        let custom_css = match &self.custom_css {
+            Some(path) => match read_to_string(path).await {
+                Ok(css) => Some(css),
+                Err(e) => {
+                    warn!("Can't read custom css {path:?}: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let locale = i18n::select_locale(self.locale.as_deref(), request.header("accept-language"));
 
         let context = TemplateDirCtx {
-            is_root: request_path.to_str().unwrap().trim() == "/",
+            internal_root: &self.internal_root,
+            i18n: i18n::bundle(&locale),
+            locale,
+            theme: self.theme,
+            custom_css,
+            is_root: request_path == Path::new("/"),
+            parent: match Path::new(base).parent() {
+                Some(p) if p != Path::new("/") => format!("{}{dir_suffix}", to_url_path(p)),
+                _ => String::from("/"),
+            },
+            readme,
             bread_crums: StaticFileHandler::generate_bread_crum(request_path),
-            dir: Cow::Borrowed(request_path.to_str().unwrap()),
+            dir: Cow::Owned(encode_path(&request_path.to_string_lossy())),
             files,
+            pagination,
+            upload: self.max_upload_size.is_some()
+                && self.allows_with(config, url.path(), Permission::Upload),
+            archive: self.archives,
+            manage: self.manage,
+            new_folder: self.manage && self.allows_with(config, url.path(), Permission::Upload),
+            delete: self.manage && self.allows_with(config, url.path(), Permission::Delete),
         };
 
-        let body = HBS.render(DIRECTORY_TEMPLATE, &context).unwrap();
+        listing_response(request, request_path, &context)
+    }
 
-        let mut response = Response::new(HttpStatus::Ok);
-        response.add_header(("Content-Type", "text/html; charset=utf-8"));
-        response.add_body(body.as_bytes());
+    async fn solve_read_request(&self, request: &Request) -> Result<Response, &'static str> {
+        let url = request.url();
+        let path = Self::request_path(request);
+
+        if self.fingerprints.is_some()
+            && path == Path::new(&self.internal_root).join(MANIFEST_ROUTE)
+        {
+            return Ok(self.solve_manifest_request(request).await);
+        }
+
+        // The UI assets aren't under any directory config, real files there go
+        // through the same checks as the others
+        if let Ok(internal_path) = path.strip_prefix(&self.internal_root) {
+            self.internal_root_check
+                .call_once(|| self.check_internal_root());
+
+            if !self.local_path(&path).exists() {
+                return Ok(match self.is_browsable || self.dir_configs.is_some() {
+                    true => self.solve_internal_request(request, internal_path).await,
+                    false => self.not_found(),
+                });
+            }
+        }
+
+        if self.dir_configs.is_some() && path.ends_with(DIR_CONFIG_FILE_NAME) {
+            return Ok(self.not_found());
+        }
+
+        if let Some(relative) = to_relative_path(&path.to_string_lossy()) {
+            let is_dir = self.root.join(&relative).is_dir();
+            if self.is_ignored(&relative, is_dir).await {
+                return Ok(self.not_found());
+            }
+        }
+
+        if path == Path::new(SITEMAP_PATH) {
+            if let Some(response) = self.solve_sitemap_request().await {
+                return Ok(response);
+            }
+        }
+
+        if path == Path::new(ROBOTS_PATH) {
+            if let Some(response) = self.solve_robots_request() {
+                return Ok(response);
+            }
+        }
+
+        if path == Path::new(FAVICON_PATH) && !self.root.join(&FAVICON_PATH[1..]).exists() {
+            return Ok(self.solve_favicon_request(request).await);
+        }
+
+        let Ok(config) = self.dir_config(url.path()).await else {
+            return Ok(Response::new(HttpStatus::InternalServerError));
+        };
+
+        if !self.allows_with(&config, url.path(), Permission::Read) {
+            return Ok(Response::new(HttpStatus::Forbidden));
+        }
+
+        let mut response = match config.listing.unwrap_or(self.is_browsable) {
+            true => self.solve_browsable_request(request, &config).await?,
+            false => self.solve_file_request(request, &config).await?,
+        };
+
+        for (name, value) in &config.headers {
+            response.add_header((name, value));
+        }
 
         Ok(response)
     }
@@ -259,57 +1866,290 @@ impl Named for StaticFileHandler {}
 #[async_trait]
 impl HttpHandler for StaticFileHandler {
     async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
-        if self.is_browsable {
-            self.solve_browsable_request(request).await
-        } else {
-            self.solve_file_request(request).await
+        if decode_path(request.url().path())
+            .and_then(|path| to_relative_path(&path))
+            .is_none()
+        {
+            return Ok(rejected((HttpStatus::BadRequest, "Invalid path")));
+        }
+
+        match (request.method(), self.max_upload_size) {
+            (Method::Get | Method::Head, _) => match self.unfingerprinted(request) {
+                Some(request) => {
+                    let mut response = self.solve_read_request(&request).await?;
+                    if matches!(response.status().code(), 200..=299 | 304) {
+                        response.add_header(("Cache-Control", IMMUTABLE_CACHE_CONTROL));
+                    }
+                    Ok(response)
+                }
+                None => self.solve_read_request(request).await,
+            },
+            (Method::Post, _) if self.manage && self.is_manage_route(request) => {
+                match Action::from_form(request.body()) {
+                    Ok(action) => Ok(self.solve_manage_request(request, action).await),
+                    Err(rejection) => Ok(rejected(rejection)),
+                }
+            }
+            (Method::Delete, _) if self.manage => {
+                let action = Action::Delete {
+                    path: request.url().path().to_string(),
+                };
+                Ok(self.solve_manage_request(request, action).await)
+            }
+            (Method::Post, Some(max_size)) => {
+                Ok(self.solve_upload_request(request, max_size).await)
+            }
+            (Method::Options, _) => Ok(Response::options(&self.allowed_methods(), None)),
+            _ => Ok(Response::method_not_allowed(&self.allowed_methods())),
         }
     }
 }
 
+#[deprecated(note = "use `MethodFilterReqInterceptor::allow([Method::Get, Method::Head])`")]
 pub struct OnlyGetReqInterceptor;
 
+#[allow(deprecated)]
 impl Named for OnlyGetReqInterceptor {}
 
+#[allow(deprecated)]
 #[async_trait]
 impl InterceptorReq for OnlyGetReqInterceptor {
     async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
-        match request.method() {
-            Method::Get | Method::Head => ControlFlow::Continue(request),
-            Method::Options => {
-                ControlFlow::Break(Response::allowed(HashSet::from(ALLOWED_METHODS)))
-            }
-            _ => ControlFlow::Break(Response::new(HttpStatus::MethodNotAllowed)),
-        }
+        MethodFilterReqInterceptor::allow([Method::Get, Method::Head])
+            .chain_req(request)
+            .await
     }
 }
 
+#[deprecated(note = "the server skips the body of `HEAD` responses on its own")]
 pub struct NoBodyOnHeadResInterceptor;
 
+#[allow(deprecated)]
 impl Named for NoBodyOnHeadResInterceptor {}
 
+/// Leaves the response untouched so `Content-Length` keeps describing the body
+#[allow(deprecated)]
 #[async_trait]
 impl InterceptorRes for NoBodyOnHeadResInterceptor {
-    async fn chain_res(&self, request: &Request, mut response: Response) -> Response {
-        if request.method() == Method::Head {
-            response.clean_body();
-        }
-
+    async fn chain_res(&self, _request: &Request, response: Response) -> Response {
         response
     }
 }
 
+#[deprecated(note = "use `ErrorPageResInterceptor`, it renders the same `404` page by default")]
 pub struct NotFoundRenderResInterceptor;
 
+#[allow(deprecated)]
 impl Named for NotFoundRenderResInterceptor {}
 
+#[allow(deprecated)]
 #[async_trait]
 impl InterceptorRes for NotFoundRenderResInterceptor {
-    async fn chain_res(&self, _: &Request, mut response: Response) -> Response {
-        if response.status() == HttpStatus::NotFound {
-            response.add_body(HBS.render(NOT_FOUND_TEMPLATE, &()).unwrap().as_bytes());
+    async fn chain_res(&self, request: &Request, response: Response) -> Response {
+        ErrorPageResInterceptor::new()
+            .chain_res(request, response)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::http::DEFAULT_MAX_URI_LENGTH;
+
+    /// Served directory with `files`, removed when dropped
+    struct Root(PathBuf);
+
+    impl Root {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let root = std::env::temp_dir().join(format!("httpr-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for file in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, file).unwrap();
+            }
+            fs::create_dir_all(&root).unwrap();
+            Self(root)
         }
+    }
 
-        response
+    impl Drop for Root {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn request(method: &str, uri: &str, body: &str, user: Option<&str>) -> Request {
+        let raw = format!(
+            "{method} {uri} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\
+            Content-Type: application/x-www-form-urlencoded\r\n\r\n{body}",
+            body.len()
+        );
+        let mut reader = raw.as_bytes();
+        let mut request = Request::read_head(&mut reader, DEFAULT_MAX_URI_LENGTH)
+            .await
+            .unwrap();
+        request.read_body(&mut reader).await.unwrap();
+
+        if let Some(user) = user {
+            request.extensions_mut().insert(AuthUser(user.to_string()));
+        }
+        request
+    }
+
+    async fn status(handler: &StaticFileHandler, request: Request) -> u16 {
+        handler
+            .solve_request(&request)
+            .await
+            .unwrap()
+            .status()
+            .code()
+    }
+
+    #[tokio::test]
+    async fn permission_rules_match_normalized_paths() {
+        let root = Root::new("permission-rules", &["private/a.txt", "public/a.txt"]);
+        let handler = StaticFileHandler::new(&root.0, false)
+            .unwrap()
+            .with_uploads(1024)
+            .with_permissions(
+                Permissions::new(&[Permission::Read, Permission::Upload])
+                    .with_rule("/private=".parse().unwrap()),
+            );
+
+        for uri in ["/private/a.txt", "//private/a.txt", "/./private/a.txt"] {
+            let get = request("GET", uri, "", None).await;
+            assert_eq!(status(&handler, get).await, 403, "{uri}");
+        }
+        for uri in ["/private/", "//private/", "/./private/"] {
+            let post = request("POST", uri, "", None).await;
+            assert_eq!(status(&handler, post).await, 403, "{uri}");
+        }
+
+        let get = request("GET", "//public/a.txt", "", None).await;
+        assert_eq!(status(&handler, get).await, 200);
+    }
+
+    #[tokio::test]
+    async fn real_files_under_the_internal_root_are_checked() {
+        let root = Root::new(
+            "internal-root",
+            &["__internal/private.txt", "__internal/a.tmp", ".httprignore"],
+        );
+        fs::write(root.0.join(".httprignore"), "*.tmp\n").unwrap();
+        let permissions = || {
+            Permissions::new(&[Permission::Read])
+                .with_rule("/__internal/private.txt=".parse().unwrap())
+        };
+
+        let browsable = StaticFileHandler::new(&root.0, true)
+            .unwrap()
+            .with_ignore_files(true)
+            .with_permissions(permissions());
+        let get = request("GET", "/__internal/private.txt", "", None).await;
+        assert_eq!(status(&browsable, get).await, 403);
+        let get = request("GET", "/__internal/a.tmp", "", None).await;
+        assert_eq!(status(&browsable, get).await, 404);
+        let get = request("GET", "/__internal/style.css", "", None).await;
+        assert_eq!(status(&browsable, get).await, 200);
+
+        let files_only = StaticFileHandler::new(&root.0, false)
+            .unwrap()
+            .with_permissions(permissions());
+        let get = request("GET", "/__internal/", "", None).await;
+        assert_ne!(status(&files_only, get).await, 200);
+        let get = request("GET", "/__internal/style.css", "", None).await;
+        assert_eq!(status(&files_only, get).await, 404);
+    }
+
+    #[cfg(feature = "thumbnails")]
+    #[tokio::test]
+    async fn thumbnails_are_checked_like_their_images() {
+        let root = Root::new("thumbnails", &["denied/.httpr.toml", ".httprignore"]);
+        fs::write(root.0.join("denied/.httpr.toml"), "permissions = []\n").unwrap();
+        fs::write(root.0.join(".httprignore"), "ignored.png\n").unwrap();
+        for image in ["a.png", "ignored.png", "denied/a.png", "private/a.png"] {
+            let path = root.0.join(image);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image::RgbImage::new(2, 2).save(path).unwrap();
+        }
+        let handler = StaticFileHandler::new(&root.0, true)
+            .unwrap()
+            .with_thumbnails(64)
+            .with_ignore_files(true)
+            .with_dir_configs(true)
+            .with_protected_paths(ProtectedPaths::new().with_prefix("/private"));
+
+        let cases = [
+            ("/a.png", None, 200),
+            ("/ignored.png", None, 404),
+            ("/denied/a.png", None, 403),
+            ("/private/a.png", None, 404),
+            ("/private/a.png", Some("user"), 200),
+        ];
+        for (image, user, expected) in cases {
+            let uri = format!("/__internal/thumbnail{image}");
+            let get = request("GET", &uri, "", user).await;
+            assert_eq!(status(&handler, get).await, expected, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn management_matches_normalized_paths() {
+        let root = Root::new(
+            "management",
+            &[
+                "private/a.txt",
+                "public/a.txt",
+                "public/b.tmp",
+                ".httprignore",
+            ],
+        );
+        fs::write(root.0.join(".httprignore"), "*.tmp\n").unwrap();
+        let handler = StaticFileHandler::new(&root.0, false)
+            .unwrap()
+            .with_management(true)
+            .with_ignore_files(true)
+            .with_permissions(
+                Permissions::new(&[Permission::Read, Permission::Upload, Permission::Delete])
+                    .with_rule("/private=read".parse().unwrap()),
+            );
+
+        let forms = [
+            "action=delete&path=//private/a.txt",
+            "action=delete&path=/./private/a.txt",
+            "action=rename&path=//private/a.txt&name=b.txt",
+            "action=rename&path=/public/a.txt&name=..%2Fprivate%2Fb.txt",
+            "action=rename&path=/./public/a.txt&name=c.tmp",
+            "action=mkdir&path=//private/&name=new",
+            "action=mkdir&path=/./private&name=new",
+            "action=delete&path=/public/b.tmp",
+            "action=delete&path=//public/./b.tmp",
+        ];
+        for form in forms {
+            let post = request("POST", "/__internal/manage", form, Some("user")).await;
+            assert!(status(&handler, post).await >= 400, "{form}");
+        }
+        for uri in ["//private/a.txt", "/./private/a.txt"] {
+            let delete = request("DELETE", uri, "", Some("user")).await;
+            assert!(status(&handler, delete).await >= 400, "{uri}");
+        }
+        assert!(root.0.join("private/a.txt").is_file());
+        assert!(root.0.join("public/a.txt").is_file());
+        assert!(root.0.join("public/b.tmp").is_file());
+        assert!(!root.0.join("private/new").exists());
+
+        let post = request(
+            "POST",
+            "/__internal/manage",
+            "action=delete&path=//public/./a.txt",
+            Some("user"),
+        )
+        .await;
+        assert_eq!(status(&handler, post).await, 303);
+        assert!(!root.0.join("public/a.txt").exists());
     }
 }
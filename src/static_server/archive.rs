@@ -0,0 +1,70 @@
+//! Directories downloaded as one `.tar.gz`
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use log::debug;
+use tokio::{
+    io::{duplex, AsyncWriteExt, DuplexStream},
+    runtime::Handle,
+    sync::OwnedSemaphorePermit,
+    task,
+};
+
+/// `download` query value asking for the archive of a directory
+pub(in crate::static_server) const TAR_GZ: &str = "tar.gz";
+
+/// Archive bytes produced ahead of the client
+const ARCHIVE_BUFFER: usize = 64 * 1024;
+
+/// Blocking side of the pipe the archive is written to
+struct PipeWriter {
+    handle: Handle,
+    pipe: DuplexStream,
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.handle.block_on(self.pipe.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stream a gzipped tar of `entries`, absolute paths with their archive names
+///
+/// The archive is built as the client reads it, in a blocking task, so nothing
+/// is written to disk. Directories only add their entry, list their files too.
+/// The `permit` is held until the archive is done.
+pub(in crate::static_server) fn tar_gz(
+    entries: Vec<(PathBuf, PathBuf)>,
+    permit: Option<OwnedSemaphorePermit>,
+) -> DuplexStream {
+    let (reader, pipe) = duplex(ARCHIVE_BUFFER);
+    let writer = PipeWriter {
+        handle: Handle::current(),
+        pipe,
+    };
+
+    task::spawn_blocking(move || {
+        let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        builder.follow_symlinks(false);
+
+        let written = entries
+            .iter()
+            .try_for_each(|(path, name)| builder.append_path_with_name(path, name))
+            .and_then(|_| builder.into_inner()?.finish().map(drop));
+
+        if let Err(e) = written {
+            debug!("Archive stopped: {e}");
+        }
+        drop(permit);
+    });
+
+    reader
+}
@@ -0,0 +1,43 @@
+//! Charset of the text files, declared instead of left to the browsers to guess
+
+use std::str;
+
+/// Bytes read from the start of a file to tell its charset
+pub(in crate::static_server) const SNIFF_LEN: usize = 8 * 1024;
+
+/// Content types decoded with a charset, besides `text/*`
+const TEXT_TYPES: [&str; 3] = [
+    "application/javascript",
+    "application/json",
+    "application/xml",
+];
+
+/// Whether `content_type` is text without a declared charset
+pub(in crate::static_server) fn needs_charset(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+    !content_type.to_lowercase().contains("charset=")
+        && (essence.starts_with("text/")
+            || essence.ends_with("+json")
+            || essence.ends_with("+xml")
+            || TEXT_TYPES.contains(&essence))
+}
+
+/// Charset of a file starting with `head`, `None` unless it is some Unicode
+///
+/// Byte order marks tell UTF-8 and UTF-16, otherwise `head` must be valid
+/// UTF-8, but for a character cut at its end.
+pub(in crate::static_server) fn detect(head: &[u8]) -> Option<&'static str> {
+    match head {
+        [0xEF, 0xBB, 0xBF, ..] => return Some("utf-8"),
+        [0xFF, 0xFE, ..] => return Some("utf-16le"),
+        [0xFE, 0xFF, ..] => return Some("utf-16be"),
+        _ => {}
+    }
+
+    match str::from_utf8(head) {
+        Ok(_) => Some("utf-8"),
+        Err(e) if e.error_len().is_none() => Some("utf-8"),
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,172 @@
+//! Live statistics page of the server
+
+use std::{ops::ControlFlow, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use tokio::{
+    io::{duplex, AsyncWriteExt},
+    time::{interval, Instant},
+};
+
+use super::{templates, Theme, INTERNAL_ROOT};
+use crate::http::{
+    stats::{ServerStats, StatsSnapshot, TrafficSnapshot, TrafficStats},
+    Body, HttpStatus, InterceptorReq, Named, Request, Response,
+};
+
+/// Time between two updates of the dashboard
+pub const DASHBOARD_REFRESH: Duration = Duration::from_secs(1);
+
+/// Paths shown in the dashboard
+const TOP_PATHS: usize = 10;
+
+/// Route of the dashboard under the [INTERNAL_ROOT]
+pub const DASHBOARD_ROUTE: &str = "dashboard";
+
+#[derive(Serialize)]
+struct DashboardEvent {
+    #[serde(flatten)]
+    stats: StatsSnapshot,
+    requests_per_sec: f64,
+    #[serde(flatten)]
+    traffic: TrafficSnapshot,
+}
+
+/// Serve an HTML dashboard of the server counters
+///
+/// The page follows its `/events` path, a `text/event-stream` sending the
+/// counters every [DASHBOARD_REFRESH]. Register the [TrafficStats] as a log
+/// sink of the server for the status and path counters.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use httpr::{
+///     http::{stats::TrafficStats, Server},
+///     static_server::{DashboardReqInterceptor, StaticFileHandler},
+/// };
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let mut server = Server::new(
+///     String::from("127.0.0.1:4444"),
+///     StaticFileHandler::new(".", true)?,
+/// );
+///
+/// let traffic = Arc::new(TrafficStats::new());
+/// let dashboard = DashboardReqInterceptor::new(server.stats(), traffic.clone());
+/// server
+///     .push_log_sink(traffic)
+///     .push_req_inter(Arc::new(dashboard));
+/// # Ok(())
+/// # }
+/// ```
+pub struct DashboardReqInterceptor {
+    path: String,
+    events_path: String,
+    theme: Theme,
+    stats: Arc<ServerStats>,
+    traffic: Arc<TrafficStats>,
+}
+
+impl DashboardReqInterceptor {
+    /// Dashboard at [DASHBOARD_ROUTE] under the [INTERNAL_ROOT]
+    pub fn new(stats: Arc<ServerStats>, traffic: Arc<TrafficStats>) -> Self {
+        Self {
+            path: String::new(),
+            events_path: String::new(),
+            theme: Theme::default(),
+            stats,
+            traffic,
+        }
+        .with_path(&format!("{INTERNAL_ROOT}{DASHBOARD_ROUTE}"))
+    }
+
+    /// Serve the dashboard on `path` instead
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = format!("/{}", path.trim_matches('/'));
+        self.events_path = format!("{}/events", self.path);
+        self
+    }
+
+    /// Color scheme of the page, [Theme::Auto] by default
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    fn page(&self) -> Response {
+        let context = json!({ "theme": self.theme, "events": self.events_path });
+        let Ok(page) = templates::render(templates::DASHBOARD_TEMPLATE, &context) else {
+            return Response::new(HttpStatus::InternalServerError);
+        };
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "text/html; charset=utf-8"));
+        response.add_header(("Cache-Control", "no-store"));
+        response.add_body(page.as_bytes());
+        response
+    }
+
+    /// Event stream of the counters, until the client leaves
+    fn events(&self) -> Response {
+        let (reader, mut writer) = duplex(16 * 1024);
+        let stats = self.stats.clone();
+        let traffic = self.traffic.clone();
+
+        tokio::spawn(async move {
+            let mut ticks = interval(DASHBOARD_REFRESH);
+            let mut last = (Instant::now(), stats.snapshot().total_requests);
+
+            loop {
+                ticks.tick().await;
+
+                let snapshot = stats.snapshot();
+                let elapsed = last.0.elapsed().as_secs_f64();
+                let requests = snapshot.total_requests.saturating_sub(last.1);
+                last = (Instant::now(), snapshot.total_requests);
+
+                let event = DashboardEvent {
+                    stats: snapshot,
+                    requests_per_sec: requests as f64 / elapsed.max(f64::EPSILON),
+                    traffic: traffic.snapshot(TOP_PATHS),
+                };
+                let data = serde_json::to_string(&event).unwrap_or_default();
+
+                // The reader is dropped with the connection
+                if writer
+                    .write_all(format!("data: {data}\n\n").as_bytes())
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let mut response = Response::new(HttpStatus::Ok);
+        response.add_header(("Content-Type", "text/event-stream"));
+        response.add_header(("Cache-Control", "no-store"));
+        response.set_body(Body::unbounded(reader));
+        response
+    }
+}
+
+impl Named for DashboardReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for DashboardReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let url = request.url();
+        let path = url.path();
+
+        if path == self.path {
+            ControlFlow::Break(self.page())
+        } else if path == self.events_path {
+            ControlFlow::Break(self.events())
+        } else {
+            ControlFlow::Continue(request)
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::SystemTime,
+};
+
+use log::warn;
+use serde::Deserialize;
+use tokio::fs::{metadata, read_to_string};
+
+use super::Permission;
+
+/// Settings file of a served directory, applied to everything under it
+///
+/// Unset fields are inherited from the parent directories, the headers are merged.
+///
+/// ```toml
+/// # Index files of the directories, the first existing one is served
+/// index = ["index.html", "README.html"]
+/// # List the directories, regardless of the browsable mode
+/// listing = false
+/// # Replace the handler permissions
+/// permissions = ["read"]
+///
+/// [headers]
+/// Cache-Control = "no-cache"
+/// ```
+pub const DIR_CONFIG_FILE_NAME: &str = ".httpr.toml";
+
+/// Contents of a [DIR_CONFIG_FILE_NAME]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(in crate::static_server) struct DirConfig {
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub index: Option<Vec<String>>,
+    pub listing: Option<bool>,
+    pub permissions: Option<Vec<Permission>>,
+}
+
+impl DirConfig {
+    /// `child` settings over these
+    fn merge(&mut self, child: &DirConfig) {
+        self.headers.extend(child.headers.clone());
+        if child.index.is_some() {
+            self.index = child.index.clone();
+        }
+        if child.listing.is_some() {
+            self.listing = child.listing;
+        }
+        if child.permissions.is_some() {
+            self.permissions = child.permissions.clone();
+        }
+    }
+}
+
+struct CachedConfig {
+    modified: SystemTime,
+    config: Arc<DirConfig>,
+}
+
+/// Per-directory configs under a root, parsed again when their files change
+#[derive(Default)]
+pub(in crate::static_server) struct DirConfigs {
+    cache: RwLock<HashMap<PathBuf, CachedConfig>>,
+}
+
+impl DirConfigs {
+    /// Effective config of `dir`, a path relative to `root`
+    ///
+    /// Fails when a file on the way is invalid, serving the subtree without its
+    /// access rules would be worse.
+    pub async fn resolve(&self, root: &Path, dir: &Path) -> Result<DirConfig, ()> {
+        let mut effective = DirConfig::default();
+        let mut current = root.to_path_buf();
+
+        if let Some(config) = self.load(&current).await? {
+            effective.merge(&config);
+        }
+
+        for segment in dir.iter() {
+            current.push(segment);
+            if let Some(config) = self.load(&current).await? {
+                effective.merge(&config);
+            }
+        }
+
+        Ok(effective)
+    }
+
+    async fn load(&self, dir: &Path) -> Result<Option<Arc<DirConfig>>, ()> {
+        let path = dir.join(DIR_CONFIG_FILE_NAME);
+
+        let Some(modified) = metadata(&path).await.ok().and_then(|m| m.modified().ok()) else {
+            return Ok(None);
+        };
+
+        {
+            let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(cached) = cache.get(dir).filter(|c| c.modified == modified) {
+                return Ok(Some(cached.config.clone()));
+            }
+        }
+
+        let content = read_to_string(&path).await.map_err(|e| {
+            warn!("Can't read {path:?}: {e}");
+        })?;
+
+        let config: Arc<DirConfig> = toml::from_str(&content)
+            .map(Arc::new)
+            .map_err(|e| warn!("Invalid {path:?}: {e}"))?;
+
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        cache.insert(
+            dir.to_path_buf(),
+            CachedConfig {
+                modified,
+                config: config.clone(),
+            },
+        );
+
+        Ok(Some(config))
+    }
+}
@@ -0,0 +1,272 @@
+//! Files compiled into the binary
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    marker::PhantomData,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use rust_embed::{EmbeddedFile, RustEmbed};
+
+use super::{
+    i18n, listing_response, rejected,
+    url_path::{decode_path, encode_path},
+    utils::{hex, mime_by_path},
+    IconMap, StaticFileHandler, TemplateDirCtx, TemplateEntryCtx, Theme, INDEX_FILE_NAME,
+    INTERNAL_ROOT,
+};
+use crate::http::{
+    conditional::{http_date, none_match},
+    range::{coalesce_ranges, parse_byte_ranges, RangeError},
+    HttpHandler, HttpStatus, Method, Named, Request, Response,
+};
+
+/// Handler serving the files of a [RustEmbed] type, for sites built into the binary
+///
+/// Files get their type by extension and an `ETag` from the embedded hash.
+/// Directories are the prefixes of the embedded paths: browsable handlers list
+/// them with the UI of [StaticFileHandler], the others redirect to their
+/// `index.html`.
+///
+/// ```
+/// use httpr::{http::Server, static_server::EmbeddedDirHandler};
+/// use rust_embed::RustEmbed;
+///
+/// #[derive(RustEmbed)]
+/// #[folder = "assets/"]
+/// struct Site;
+///
+/// let handler = EmbeddedDirHandler::<Site>::new(true);
+/// let server = Server::new(String::from("127.0.0.1:4444"), handler);
+/// ```
+pub struct EmbeddedDirHandler<E> {
+    is_browsable: bool,
+    internal_root: String,
+    theme: Theme,
+    icons: IconMap,
+    files: PhantomData<fn() -> E>,
+}
+
+impl<E: RustEmbed> EmbeddedDirHandler<E> {
+    pub fn new(browsable: bool) -> Self {
+        Self {
+            is_browsable: browsable,
+            internal_root: String::new(),
+            theme: Theme::default(),
+            icons: IconMap::default(),
+            files: PhantomData,
+        }
+        .with_internal_root(INTERNAL_ROOT)
+    }
+
+    /// Route of the browsable UI assets, [INTERNAL_ROOT] by default
+    pub fn with_internal_root(mut self, route: &str) -> Self {
+        self.internal_root = format!("/{}", route.trim_matches('/'));
+        self
+    }
+
+    /// Color scheme of the browsable UI, [Theme::Auto] by default
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Icons of the browsable listing entries
+    pub fn with_icons(mut self, icons: IconMap) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    /// Whether `dir`, without slashes around, holds embedded files
+    fn is_dir(dir: &str) -> bool {
+        let prefix = format!("{dir}/");
+        dir.is_empty() || E::iter().any(|name| name.starts_with(&prefix))
+    }
+
+    fn solve_file(request: &Request, path: &Path, file: EmbeddedFile) -> Response {
+        let etag = format!("\"{}\"", hex(&file.metadata.sha256_hash()[..16]));
+        let last_modified = modified(&file);
+
+        let mut response = match request.header("if-none-match") {
+            Some(tags) if none_match(tags, &etag) => Response::new(HttpStatus::NotModified),
+            _ => {
+                let data = &file.data;
+                let size = data.len() as u64;
+                let ranges = request.header("range").map(|r| parse_byte_ranges(r, size));
+
+                let mut response = match ranges.map(|r| r.map(coalesce_ranges)) {
+                    Some(Ok(ranges)) if ranges.len() == 1 => {
+                        let range = &ranges[0];
+                        let mut response = Response::new(HttpStatus::PartialContent);
+                        response.add_header(("Content-Range", &range.content_range(size)));
+                        response.add_body(&data[range.start as usize..=range.end as usize]);
+                        response
+                    }
+                    Some(Err(RangeError::Unsatisfiable)) => {
+                        let mut response = Response::new(HttpStatus::RangeNotSatisfiable);
+                        response.add_header(("Content-Range", &format!("bytes */{size}")));
+                        return response;
+                    }
+                    // Several ranges of an in-memory file, sent whole
+                    _ => {
+                        let mut response = Response::new(HttpStatus::Ok);
+                        response.add_body(data);
+                        response
+                    }
+                };
+
+                response.add_header(("Content-Type", &mime_by_path(path)));
+                response.add_header(("Accept-Ranges", "bytes"));
+                response
+            }
+        };
+
+        response.add_header(("ETag", &etag));
+        if let Some(modified) = last_modified {
+            response.add_header(("Last-Modified", &http_date(modified)));
+        }
+
+        response
+    }
+
+    fn solve_listing(&self, request: &Request, dir: &str) -> Result<Response, &'static str> {
+        let prefix = match dir {
+            "" => String::new(),
+            dir => format!("{dir}/"),
+        };
+
+        // Entries of the directory, `true` for the subdirectories
+        let mut entries = BTreeMap::new();
+        for name in E::iter() {
+            let Some(rest) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+
+            match rest.split_once('/') {
+                Some((subdir, _)) => entries.insert(subdir.to_string(), true),
+                None => entries.insert(rest.to_string(), false),
+            };
+        }
+
+        let base = format!("/{prefix}");
+        let mut files = entries
+            .into_iter()
+            .map(|(file_name, is_dir)| {
+                let file = match is_dir {
+                    true => None,
+                    false => E::get(&format!("{prefix}{file_name}")),
+                };
+                let ext = Path::new(&file_name).extension().and_then(|e| e.to_str());
+                let mime = ext.map(|_| mime_by_path(Path::new(&file_name)));
+
+                TemplateEntryCtx {
+                    is_dir,
+                    href: encode_path(&match is_dir {
+                        true => format!("{base}{file_name}/"),
+                        false => format!("{base}{file_name}"),
+                    }),
+                    icon: match is_dir {
+                        true => self.icons.folder_markup(),
+                        false => self.icons.file_markup(ext, mime.as_deref()),
+                    },
+                    mime,
+                    thumbnail: None,
+                    size: file.as_ref().map(|f| f.data.len() as u64),
+                    modified: file.as_ref().and_then(modified),
+                    file_name: Cow::Owned(file_name),
+                }
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let locale = i18n::select_locale(None, request.header("accept-language"));
+        let request_path = Path::new(&base);
+        let context = TemplateDirCtx {
+            internal_root: &self.internal_root,
+            i18n: i18n::bundle(&locale),
+            locale,
+            theme: self.theme,
+            custom_css: None,
+            is_root: dir.is_empty(),
+            dir: Cow::Owned(encode_path(&base)),
+            parent: match request_path.parent() {
+                Some(p) if p != Path::new("/") => encode_path(&format!("{}/", p.to_string_lossy())),
+                _ => String::from("/"),
+            },
+            readme: None,
+            bread_crums: StaticFileHandler::generate_bread_crum(request_path),
+            files,
+            pagination: None,
+            upload: false,
+            archive: false,
+            manage: false,
+            new_folder: false,
+            delete: false,
+        };
+
+        listing_response(request, request_path, &context)
+    }
+}
+
+impl<E: RustEmbed> Named for EmbeddedDirHandler<E> {}
+
+#[async_trait]
+impl<E: RustEmbed + 'static> HttpHandler for EmbeddedDirHandler<E> {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return Ok(Response::method_not_allowed(&[Method::Get, Method::Head]));
+        }
+
+        let url = request.url();
+        let Some(path) = decode_path(url.path()) else {
+            return Ok(rejected((HttpStatus::BadRequest, "Invalid path")));
+        };
+
+        if self.is_browsable {
+            if let Ok(asset) = Path::new(&path).strip_prefix(&self.internal_root) {
+                return Ok(StaticFileHandler::asset_response(request, asset)
+                    .unwrap_or_else(Response::not_found));
+            }
+        }
+
+        let name = path.trim_matches('/');
+        if !path.ends_with('/') {
+            if let Some(file) = E::get(name) {
+                return Ok(Self::solve_file(request, Path::new(name), file));
+            }
+        }
+
+        if !Self::is_dir(name) {
+            return Ok(Response::not_found());
+        }
+
+        let dir_path = match name {
+            "" => String::from("/"),
+            name => format!("/{name}/"),
+        };
+        if path != dir_path {
+            let mut response = Response::new(HttpStatus::Found);
+            response.add_header(("Location", &encode_path(&dir_path)));
+            return Ok(response);
+        }
+
+        match self.is_browsable {
+            true => self.solve_listing(request, name),
+            false if E::get(&format!("{}{INDEX_FILE_NAME}", &dir_path[1..])).is_some() => {
+                let mut response = Response::new(HttpStatus::Found);
+                let location = format!("{dir_path}{INDEX_FILE_NAME}");
+                response.add_header(("Location", &encode_path(&location)));
+                Ok(response)
+            }
+            false => Ok(Response::not_found()),
+        }
+    }
+}
+
+fn modified(file: &EmbeddedFile) -> Option<SystemTime> {
+    let secs = file.metadata.last_modified()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
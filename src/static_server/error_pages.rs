@@ -0,0 +1,135 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use ::handlebars::Handlebars;
+use async_trait::async_trait;
+use log::warn;
+use serde::Serialize;
+
+use super::{
+    i18n,
+    templates::{self, NOT_FOUND_TEMPLATE},
+    utils::mime_by_path,
+};
+use crate::http::{HttpStatus, InterceptorRes, Named, Request, Response};
+
+enum ErrorPage {
+    File { content_type: String, data: Vec<u8> },
+    Template,
+}
+
+#[derive(Serialize)]
+struct TemplateErrorCtx {
+    status: u16,
+    reason: String,
+    path: String,
+    locale: String,
+    i18n: i18n::Bundle,
+}
+
+/// Give the error responses a page by status code
+///
+/// Pages are files, rendered as handlebars templates when their name ends with
+/// `.hbs`. Templates get the `status`, `reason`, request `path`, `locale` and its
+/// `i18n` messages. `404` defaults to the built-in page, other statuses without a
+/// page are left untouched.
+pub struct ErrorPageResInterceptor {
+    pages: HashMap<u16, ErrorPage>,
+    templates: Handlebars<'static>,
+}
+
+impl ErrorPageResInterceptor {
+    pub fn new() -> Self {
+        Self {
+            pages: HashMap::new(),
+            templates: Handlebars::new(),
+        }
+    }
+
+    /// Answer `status` with the page at `path`, read once here
+    pub fn with_page<P: AsRef<Path>>(
+        mut self,
+        status: HttpStatus,
+        path: P,
+    ) -> Result<Self, &'static str> {
+        let path = path.as_ref();
+        let code = status.code();
+
+        if code < 400 {
+            return Err("Error pages are only for 4xx and 5xx statuses");
+        }
+
+        if path.extension().is_some_and(|ext| ext == "hbs") {
+            let template = fs::read_to_string(path).map_err(|_| "Can't read the error page")?;
+            self.templates
+                .register_template_string(&code.to_string(), template)
+                .map_err(|_| "Invalid error page template")?;
+            self.pages.insert(code, ErrorPage::Template);
+        } else {
+            let data = fs::read(path).map_err(|_| "Can't read the error page")?;
+            let content_type = match mime_by_path(path) {
+                mime if mime.starts_with("text/") => format!("{mime}; charset=utf-8"),
+                mime => mime,
+            };
+
+            self.pages
+                .insert(code, ErrorPage::File { content_type, data });
+        }
+
+        Ok(self)
+    }
+}
+
+impl Default for ErrorPageResInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for ErrorPageResInterceptor {}
+
+#[async_trait]
+impl InterceptorRes for ErrorPageResInterceptor {
+    async fn chain_res(&self, request: &Request, mut response: Response) -> Response {
+        let status = response.status();
+        let code = status.code();
+
+        let page = self.pages.get(&code);
+        if page.is_none() && status != HttpStatus::NotFound {
+            return response;
+        }
+
+        let locale = i18n::select_locale(
+            response.header("content-language"),
+            request.header("accept-language"),
+        );
+
+        let context = TemplateErrorCtx {
+            status: code,
+            reason: response.reason().to_string(),
+            path: request.url().path().to_string(),
+            i18n: i18n::bundle(&locale),
+            locale,
+        };
+
+        let (content_type, body) = match page {
+            Some(ErrorPage::File { content_type, data }) => (content_type.as_str(), data.clone()),
+            Some(ErrorPage::Template) => match self.templates.render(&code.to_string(), &context) {
+                Ok(body) => ("text/html; charset=utf-8", body.into_bytes()),
+                Err(e) => {
+                    warn!("Failed rendering the {code} error page: {e}");
+                    return response;
+                }
+            },
+            None => match templates::render(NOT_FOUND_TEMPLATE, &context) {
+                Ok(body) => ("text/html; charset=utf-8", body.into_bytes()),
+                Err(_) => return response,
+            },
+        };
+
+        response.add_header(("Content-Type", content_type));
+        response.add_header(("Content-Language", &context.locale));
+        response.add_body(&body);
+
+        response
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use log::warn;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::OwnedSemaphorePermit,
+};
+
+use super::fs_limit::LimitedReader;
+use crate::http::{range::ByteRange, Response};
+
+/// Contents of the files loaded in memory, by full path
+#[derive(Debug, Default)]
+pub struct FileCache {
+    files: RwLock<HashMap<PathBuf, Arc<[u8]>>>,
+    /// Size limit of the last preload, `None` until then
+    max_file_size: RwLock<Option<u64>>,
+}
+
+impl FileCache {
+    /// Load every file under `root` up to `max_file_size` bytes
+    ///
+    /// Returns the files and bytes loaded.
+    pub fn preload(&self, root: &Path, max_file_size: u64) -> io::Result<(usize, u64)> {
+        *self.max_file_size.write().unwrap() = Some(max_file_size);
+        self.load_dir(root, max_file_size)
+    }
+
+    fn load_dir(&self, root: &Path, max_file_size: u64) -> io::Result<(usize, u64)> {
+        let mut loaded = (0, 0);
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+
+                if metadata.is_dir() {
+                    pending.push(entry.path());
+                } else if metadata.is_file() && metadata.len() <= max_file_size {
+                    let data = fs::read(entry.path())?;
+                    loaded.0 += 1;
+                    loaded.1 += data.len() as u64;
+                    // Same form as the paths matched on requests
+                    self.insert(entry.path().components().collect(), data.into());
+                }
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Arc<[u8]>> {
+        self.files.read().unwrap().get(path).cloned()
+    }
+
+    pub fn insert(&self, path: PathBuf, data: Arc<[u8]>) {
+        self.files.write().unwrap().insert(path, data);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.read().unwrap().is_empty()
+    }
+
+    /// Bring `path` up to date after a change on disk
+    ///
+    /// Files are reloaded while they fit the preload limit and dropped otherwise.
+    /// Directories are reloaded as a whole. Does nothing before a preload.
+    pub fn refresh(&self, path: &Path) {
+        let Some(max_file_size) = *self.max_file_size.read().unwrap() else {
+            return;
+        };
+
+        let path: PathBuf = path.components().collect();
+        self.files
+            .write()
+            .unwrap()
+            .retain(|cached, _| !cached.starts_with(&path));
+
+        let Ok(metadata) = fs::metadata(&path) else {
+            return;
+        };
+
+        let loaded = if metadata.is_dir() {
+            self.load_dir(&path, max_file_size).map(|_| ())
+        } else if metadata.is_file() && metadata.len() <= max_file_size {
+            fs::read(&path).map(|data| self.insert(path.clone(), data.into()))
+        } else {
+            Ok(())
+        };
+
+        if let Err(e) = loaded {
+            warn!("Failed to refresh {path:?}: {e}");
+        }
+    }
+}
+
+/// Where the contents of a matched file are read from
+pub(crate) enum FileSource {
+    /// Open file with its slot of the handler filesystem limit
+    Disk(File, Option<OwnedSemaphorePermit>),
+    Memory(Arc<[u8]>),
+}
+
+impl FileSource {
+    pub(crate) async fn read_range(&mut self, range: &ByteRange) -> io::Result<Vec<u8>> {
+        match self {
+            FileSource::Disk(file, _) => {
+                let mut part = vec![0; range.len() as usize];
+                file.seek(SeekFrom::Start(range.start)).await?;
+                file.read_exact(&mut part).await?;
+                Ok(part)
+            }
+            FileSource::Memory(data) => {
+                Ok(data[range.start as usize..=range.end as usize].to_vec())
+            }
+        }
+    }
+
+    /// Send `range`, or the whole file, as the body of `response`
+    pub(crate) async fn send(
+        self,
+        response: &mut Response,
+        range: Option<&ByteRange>,
+    ) -> io::Result<()> {
+        match self {
+            FileSource::Disk(mut file, permit) => {
+                let len = match range {
+                    Some(range) => {
+                        file.seek(SeekFrom::Start(range.start)).await?;
+                        range.len()
+                    }
+                    None => file.metadata().await?.len(),
+                };
+
+                response.stream_body(LimitedReader::new(file, permit), len);
+            }
+            FileSource::Memory(data) => match range {
+                Some(range) => response.add_body(&data[range.start as usize..=range.end as usize]),
+                None => response.add_body(&data),
+            },
+        }
+
+        Ok(())
+    }
+}
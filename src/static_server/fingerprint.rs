@@ -0,0 +1,90 @@
+//! Content hashes in the file names, to cache the assets forever
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock},
+    time::SystemTime,
+};
+
+use md5::{Digest, Md5};
+
+use super::utils::hex;
+
+/// Route of the fingerprinted names of the files, under the internal root
+pub const MANIFEST_ROUTE: &str = "manifest.json";
+
+/// Hexadecimal digits of the hash in the names
+const FINGERPRINT_LEN: usize = 8;
+
+/// The name changes with the contents, so the response never does
+pub(in crate::static_server) const IMMUTABLE_CACHE_CONTROL: &str =
+    "public, max-age=31536000, immutable";
+
+/// Hashes of the files, kept until they are modified
+#[derive(Debug, Default)]
+pub(in crate::static_server) struct Fingerprints {
+    hashes: RwLock<HashMap<PathBuf, (SystemTime, u64, String)>>,
+}
+
+impl Fingerprints {
+    /// Hash of the file at `path`, `None` if it isn't a readable file
+    pub(in crate::static_server) fn hash(&self, path: &Path) -> Option<String> {
+        let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+        let modified = metadata.modified().ok()?;
+
+        let hashes = self.hashes.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some((m, len, hash)) = hashes.get(path) {
+            if *m == modified && *len == metadata.len() {
+                return Some(hash.clone());
+            }
+        }
+        drop(hashes);
+
+        let mut hasher = Md5::new();
+        io::copy(&mut File::open(path).ok()?, &mut hasher).ok()?;
+        let hash = hex(&hasher.finalize())[..FINGERPRINT_LEN].to_string();
+
+        self.hashes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_path_buf(), (modified, metadata.len(), hash.clone()));
+
+        Some(hash)
+    }
+}
+
+/// `app.js` -> `app.<hash>.js`
+pub(in crate::static_server) fn fingerprinted(path: &Path, hash: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let name = match name.split_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem}.{hash}.{ext}"),
+        _ => format!("{name}.{hash}"),
+    };
+
+    path.with_file_name(name)
+}
+
+/// `app.<hash>.js` -> (`app.js`, `<hash>`)
+pub(in crate::static_server) fn split(path: &Path) -> Option<(PathBuf, String)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.split('.').collect::<Vec<_>>();
+
+    let is_hash = |part: &str| {
+        part.len() == FINGERPRINT_LEN
+            && part
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    };
+    if !parts.get(1).is_some_and(|part| is_hash(part)) {
+        return None;
+    }
+    let hash = parts.remove(1).to_string();
+
+    Some((path.with_file_name(parts.join(".")), hash))
+}
@@ -0,0 +1,58 @@
+//! Bound on the files open and the directories read at once
+
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+/// Slots of the filesystem work, unbounded by default
+#[derive(Debug, Clone, Default)]
+pub(in crate::static_server) struct FsLimit {
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl FsLimit {
+    pub(in crate::static_server) fn new(limit: usize) -> Self {
+        Self {
+            permits: Some(Arc::new(Semaphore::new(limit.max(1)))),
+        }
+    }
+
+    /// Wait for a slot, kept until the permit is dropped
+    pub(in crate::static_server) async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permits = self.permits.clone()?;
+        permits.acquire_owned().await.ok()
+    }
+}
+
+/// Reader keeping its slot until it is dropped, after the body is sent
+pub(in crate::static_server) struct LimitedReader<R> {
+    reader: R,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<R> LimitedReader<R> {
+    pub(in crate::static_server) fn new(reader: R, permit: Option<OwnedSemaphorePermit>) -> Self {
+        Self {
+            reader,
+            _permit: permit,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for LimitedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
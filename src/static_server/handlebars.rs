@@ -1,4 +1,10 @@
 use core::str;
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{PoisonError, RwLock},
+};
 
 use handlebars::{
     Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
@@ -6,10 +12,9 @@ use handlebars::{
 use once_cell::sync::Lazy;
 use rust_embed::RustEmbed;
 
-use super::INTERNAL_ROOT;
-
 pub const DIRECTORY_TEMPLATE: &str = "directory";
 pub const NOT_FOUND_TEMPLATE: &str = "not_found";
+pub const DASHBOARD_TEMPLATE: &str = "dashboard";
 
 const MIME_FALLBACK_PATH: &str = "icons/file.svg";
 
@@ -17,7 +22,61 @@ const MIME_FALLBACK_PATH: &str = "icons/file.svg";
 #[folder = "target/assets/"]
 pub struct Assets;
 
-pub static HBS: Lazy<Handlebars<'static>> = Lazy::new(|| {
+/// Folder the assets are read from in dev mode, instead of the embedded ones
+static DEV_ASSETS: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Built-in templates with their file name in the `templates` folder
+const BUILT_IN_TEMPLATES: [(&str, &str); 3] = [
+    (DIRECTORY_TEMPLATE, "directory.hbs"),
+    (NOT_FOUND_TEMPLATE, "not_found.hbs"),
+    (DASHBOARD_TEMPLATE, "dashboard.hbs"),
+];
+
+/// Read the built-in templates and the assets from `ui_dir` as they change
+///
+/// `ui_dir` holds the `templates` and `assets` folders, like the httpr sources.
+pub fn enable_dev_mode(ui_dir: &Path) -> Result<(), &'static str> {
+    let assets = ui_dir.join("assets");
+    if !assets.is_dir() {
+        return Err("No assets folder in the UI directory");
+    }
+
+    let mut hbs = HBS.write().unwrap_or_else(PoisonError::into_inner);
+    // Only the templates registered in dev mode are reloaded
+    hbs.set_dev_mode(true);
+    for (name, file_name) in BUILT_IN_TEMPLATES {
+        hbs.register_template_file(name, ui_dir.join("templates").join(file_name))
+            .map_err(|_| "Invalid or missing template in the UI directory")?;
+    }
+
+    *DEV_ASSETS.write().unwrap_or_else(PoisonError::into_inner) = Some(assets);
+    Ok(())
+}
+
+/// Whether the assets are read from disk, see [enable_dev_mode]
+pub fn is_dev_mode() -> bool {
+    DEV_ASSETS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Contents of the asset at `path`, from the dev folder when enabled
+pub fn asset(path: &str) -> Option<Cow<'static, [u8]>> {
+    let dev_assets = DEV_ASSETS.read().unwrap_or_else(PoisonError::into_inner);
+    match dev_assets.as_ref() {
+        Some(dir) => {
+            let path = Path::new(path);
+            if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+                return None;
+            }
+            fs::read(dir.join(path)).ok().map(Cow::Owned)
+        }
+        None => Assets::get(path).map(|asset| asset.data),
+    }
+}
+
+pub static HBS: Lazy<RwLock<Handlebars<'static>>> = Lazy::new(|| {
     let mut hbs = Handlebars::new();
     hbs.register_template_string(
         DIRECTORY_TEMPLATE,
@@ -31,21 +90,11 @@ pub static HBS: Lazy<Handlebars<'static>> = Lazy::new(|| {
     )
     .unwrap();
 
-    // internal path
-    hbs.register_helper(
-        "internal_path",
-        Box::new(
-            |_: &Helper,
-             _: &Handlebars,
-             _: &Context,
-             _: &mut RenderContext,
-             out: &mut dyn Output|
-             -> HelperResult {
-                let _ = out.write(INTERNAL_ROOT);
-                Ok(())
-            },
-        ),
-    );
+    hbs.register_template_string(
+        DASHBOARD_TEMPLATE,
+        include_str!("../../target/templates/dashboard.hbs"),
+    )
+    .unwrap();
 
     // assets inject
     hbs.register_helper(
@@ -61,14 +110,13 @@ pub static HBS: Lazy<Handlebars<'static>> = Lazy::new(|| {
                     .param(0)
                     .ok_or(RenderErrorReason::ParamNotFoundForIndex("asset", 0))?;
 
-                if let Some(asset) = Assets::get(
+                if let Some(data) = asset(
                     param
                         .value()
                         .as_str()
                         .ok_or(RenderErrorReason::InvalidParamType("Invalid"))?,
                 ) {
-                    let data = str::from_utf8(&asset.data).unwrap();
-                    out.write(data)?;
+                    out.write(&String::from_utf8_lossy(&data))?;
                 }
 
                 Ok(())
@@ -92,20 +140,18 @@ pub static HBS: Lazy<Handlebars<'static>> = Lazy::new(|| {
 
                 let value = param.value().as_str();
 
+                let fallback = || asset(MIME_FALLBACK_PATH).unwrap_or_default();
                 if value.is_none() {
-                    let data = Assets::get(MIME_FALLBACK_PATH).unwrap().data;
-                    let _ = out.write(str::from_utf8(&data).unwrap());
+                    let _ = out.write(&String::from_utf8_lossy(&fallback()));
                     return Ok(());
                 }
 
                 let path = format!("icons/by_mime/{}.svg", value.unwrap());
 
-                if let Some(icon) = Assets::get(&path) {
-                    let data = str::from_utf8(&icon.data).unwrap();
-                    out.write(data)?;
+                if let Some(icon) = asset(&path) {
+                    out.write(&String::from_utf8_lossy(&icon))?;
                 } else {
-                    let data = Assets::get(MIME_FALLBACK_PATH).unwrap().data;
-                    let _ = out.write(str::from_utf8(&data).unwrap());
+                    let _ = out.write(&String::from_utf8_lossy(&fallback()));
                     return Ok(());
                 }
 
@@ -114,5 +160,5 @@ pub static HBS: Lazy<Handlebars<'static>> = Lazy::new(|| {
         ),
     );
 
-    hbs
+    RwLock::new(hbs)
 });
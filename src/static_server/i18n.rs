@@ -0,0 +1,132 @@
+//! Translations of the built-in templates
+//!
+//! Every locale is a bundle of messages, keys missing in a bundle fall back to
+//! the [DEFAULT_LOCALE] one. English (`en`) and Spanish (`es`) are built in.
+
+use std::{
+    collections::HashMap,
+    sync::{PoisonError, RwLock},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::http::negotiation::negotiate_language;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Messages of a locale by key
+pub type Bundle = HashMap<String, String>;
+
+const EN: [(&str, &str); 19] = [
+    ("index_of", "Index of"),
+    ("parent_dir", "Parent directory"),
+    ("empty_dir", "This directory is empty"),
+    ("not_found_title", "Not found"),
+    ("not_found", "Not found :("),
+    ("download", "Download"),
+    ("download_archive", "Download as .tar.gz"),
+    ("page", "Page"),
+    ("previous_page", "Previous"),
+    ("next_page", "Next"),
+    ("upload", "Upload"),
+    ("drop_files", "Drop files here or pick them"),
+    ("upload_failed", "Upload failed"),
+    ("rename", "Rename"),
+    ("delete", "Delete"),
+    ("new_folder", "New folder"),
+    ("new_name", "New name"),
+    ("confirm_delete", "Delete"),
+    ("action_failed", "Action failed"),
+];
+
+const ES: [(&str, &str); 19] = [
+    ("index_of", "Índice de"),
+    ("parent_dir", "Directorio superior"),
+    ("empty_dir", "Este directorio está vacío"),
+    ("not_found_title", "No encontrado"),
+    ("not_found", "No encontrado :("),
+    ("download", "Descargar"),
+    ("download_archive", "Descargar como .tar.gz"),
+    ("page", "Página"),
+    ("previous_page", "Anterior"),
+    ("next_page", "Siguiente"),
+    ("upload", "Subir"),
+    ("drop_files", "Suelta aquí los archivos o elígelos"),
+    ("upload_failed", "Error al subir"),
+    ("rename", "Renombrar"),
+    ("delete", "Eliminar"),
+    ("new_folder", "Nueva carpeta"),
+    ("new_name", "Nuevo nombre"),
+    ("confirm_delete", "¿Eliminar"),
+    ("action_failed", "Error en la acción"),
+];
+
+static BUNDLES: Lazy<RwLock<HashMap<String, Bundle>>> = Lazy::new(|| {
+    let mut bundles = HashMap::new();
+    bundles.insert(String::from("en"), to_bundle(EN));
+    bundles.insert(String::from("es"), to_bundle(ES));
+
+    RwLock::new(bundles)
+});
+
+fn to_bundle<'a>(messages: impl IntoIterator<Item = (&'a str, &'a str)>) -> Bundle {
+    messages
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Register the messages of `locale`, merging them with the already registered ones
+///
+/// ```
+/// use httpr::static_server::i18n;
+///
+/// i18n::register_bundle("ca", [("not_found", "No s'ha trobat :(")]);
+/// assert!(i18n::locales().contains(&String::from("ca")));
+/// ```
+pub fn register_bundle<'a>(locale: &str, messages: impl IntoIterator<Item = (&'a str, &'a str)>) {
+    let mut bundles = BUNDLES.write().unwrap_or_else(PoisonError::into_inner);
+
+    bundles
+        .entry(locale.to_string())
+        .or_default()
+        .extend(to_bundle(messages));
+}
+
+/// Registered locales
+pub fn locales() -> Vec<String> {
+    let bundles = BUNDLES.read().unwrap_or_else(PoisonError::into_inner);
+    bundles.keys().cloned().collect()
+}
+
+/// Messages of `locale` completed with the default locale ones
+pub(in crate::static_server) fn bundle(locale: &str) -> Bundle {
+    let bundles = BUNDLES.read().unwrap_or_else(PoisonError::into_inner);
+
+    let mut bundle = bundles.get(DEFAULT_LOCALE).cloned().unwrap_or_default();
+    if let Some(messages) = bundles.get(locale) {
+        bundle.extend(messages.clone());
+    }
+
+    bundle
+}
+
+/// The `fixed` locale when given, otherwise the best registered one for `accept_language`
+pub(in crate::static_server) fn select_locale(
+    fixed: Option<&str>,
+    accept_language: Option<&str>,
+) -> String {
+    if let Some(locale) = fixed {
+        return locale.to_string();
+    }
+
+    let mut locales = locales();
+    // Default goes first to win with no header or ties
+    locales.sort_by_key(|l| l != DEFAULT_LOCALE);
+
+    let available = locales.iter().map(String::as_str).collect::<Vec<_>>();
+
+    negotiate_language(accept_language, &available)
+        .unwrap_or(DEFAULT_LOCALE)
+        .to_string()
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use super::handlebars::asset;
+
+const FOLDER_ICON: &str = "icons/folder.svg";
+const FILE_ICON: &str = "icons/file.svg";
+const BY_MIME_ROOT: &str = "icons/by_mime";
+
+/// Icon shown in the browsable listings
+#[derive(Debug, Clone)]
+pub enum Icon {
+    /// Path of an embedded asset, like `icons/file.svg`
+    Asset(String),
+    /// Inline SVG markup
+    Svg(String),
+}
+
+impl Icon {
+    /// Load an SVG icon from disk
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        fs::read_to_string(path)
+            .map(Icon::Svg)
+            .map_err(|_| "Can't read the icon file!")
+    }
+
+    fn markup(&self) -> Option<String> {
+        match self {
+            Icon::Asset(path) => embedded(path),
+            Icon::Svg(svg) => Some(svg.clone()),
+        }
+    }
+}
+
+/// Icons of the listing entries by extension or MIME type
+///
+/// Lookup order is extension, exact MIME type, MIME type wildcard (`image/*`),
+/// built-in icons and finally the fallback icon.
+#[derive(Debug, Clone, Default)]
+pub struct IconMap {
+    by_ext: HashMap<String, Icon>,
+    by_mime: HashMap<String, Icon>,
+    folder: Option<Icon>,
+    fallback: Option<Icon>,
+}
+
+impl IconMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the SVG icons of `dir`, following the embedded assets layout
+    ///
+    /// * `by_ext/<ext>.svg` icon for an extension
+    /// * `by_mime/<type>/<subtype>.svg` icon for a MIME type
+    /// * `by_mime/<type>.svg` icon for every subtype of a MIME type
+    /// * `folder.svg` and `file.svg` icons for directories and unknown files
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, &'static str> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err("Icons path is not a directory!");
+        }
+
+        let mut icons = Self::new();
+
+        for (path, stem) in svg_files(&dir.join("by_ext")) {
+            icons = icons.map_ext(&stem, Icon::from_file(path)?);
+        }
+
+        for (path, stem) in svg_files(&dir.join("by_mime")) {
+            icons = icons.map_mime(&format!("{stem}/*"), Icon::from_file(path)?);
+        }
+
+        if let Ok(types) = fs::read_dir(dir.join("by_mime")) {
+            for entry in types.flatten().filter(|e| e.path().is_dir()) {
+                let mime_type = entry.file_name().to_string_lossy().into_owned();
+
+                for (path, subtype) in svg_files(&entry.path()) {
+                    icons =
+                        icons.map_mime(&format!("{mime_type}/{subtype}"), Icon::from_file(path)?);
+                }
+            }
+        }
+
+        let folder = dir.join("folder.svg");
+        if folder.is_file() {
+            icons = icons.with_folder(Icon::from_file(folder)?);
+        }
+
+        let fallback = dir.join("file.svg");
+        if fallback.is_file() {
+            icons = icons.with_fallback(Icon::from_file(fallback)?);
+        }
+
+        Ok(icons)
+    }
+
+    /// Icon for the files with extension `ext`, without the dot
+    pub fn map_ext(mut self, ext: &str, icon: Icon) -> Self {
+        self.by_ext.insert(ext.to_lowercase(), icon);
+        self
+    }
+
+    /// Icon for a MIME type, `type/*` matches every subtype
+    pub fn map_mime(mut self, mime: &str, icon: Icon) -> Self {
+        self.by_mime.insert(mime.to_lowercase(), icon);
+        self
+    }
+
+    pub fn with_folder(mut self, icon: Icon) -> Self {
+        self.folder = Some(icon);
+        self
+    }
+
+    /// Icon for the files without a better match
+    pub fn with_fallback(mut self, icon: Icon) -> Self {
+        self.fallback = Some(icon);
+        self
+    }
+
+    pub(in crate::static_server) fn folder_markup(&self) -> String {
+        self.folder
+            .as_ref()
+            .and_then(Icon::markup)
+            .or_else(|| embedded(FOLDER_ICON))
+            .unwrap_or_default()
+    }
+
+    pub(in crate::static_server) fn file_markup(
+        &self,
+        ext: Option<&str>,
+        mime: Option<&str>,
+    ) -> String {
+        let ext = ext.map(str::to_lowercase);
+        let mime = mime.map(|m| m.split(';').next().unwrap_or(m).trim().to_lowercase());
+
+        let custom = ext
+            .as_ref()
+            .and_then(|e| self.by_ext.get(e))
+            .or_else(|| mime.as_ref().and_then(|m| self.by_mime.get(m)))
+            .or_else(|| {
+                let (mime_type, _) = mime.as_ref()?.split_once('/')?;
+                self.by_mime.get(&format!("{mime_type}/*"))
+            })
+            .and_then(Icon::markup);
+
+        custom
+            .or_else(|| embedded(&format!("{BY_MIME_ROOT}/{}.svg", mime.as_deref()?)))
+            .or_else(|| embedded(&format!("{BY_MIME_ROOT}/{}.svg", ext.as_deref()?)))
+            .or_else(|| self.fallback.as_ref().and_then(Icon::markup))
+            .or_else(|| embedded(FILE_ICON))
+            .unwrap_or_default()
+    }
+}
+
+fn embedded(path: &str) -> Option<String> {
+    asset(path).map(|data| String::from_utf8_lossy(&data).into_owned())
+}
+
+/// `*.svg` files of `dir` with their file stem
+fn svg_files(dir: &Path) -> Vec<(std::path::PathBuf, String)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "svg"))
+        .filter_map(|p| {
+            let stem = p.file_stem()?.to_string_lossy().to_lowercase();
+            Some((p, stem))
+        })
+        .collect()
+}
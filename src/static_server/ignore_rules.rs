@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::SystemTime,
+};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::warn;
+use tokio::fs::{metadata, read_to_string};
+
+/// Patterns of the served files to hide, with the `.gitignore` syntax
+///
+/// Read along the `.gitignore` files of each directory, its rules win over them.
+pub const IGNORE_FILE_NAME: &str = ".httprignore";
+
+/// Files with ignore rules, the later ones win
+const RULE_FILES: [&str; 2] = [".gitignore", IGNORE_FILE_NAME];
+
+struct CachedRules {
+    modified: [Option<SystemTime>; 2],
+    rules: Arc<Gitignore>,
+}
+
+/// Per-directory ignore rules under a root, parsed again when their files change
+#[derive(Default)]
+pub(in crate::static_server) struct IgnoreRules {
+    cache: RwLock<HashMap<PathBuf, CachedRules>>,
+}
+
+impl IgnoreRules {
+    /// Whether `path`, relative to `root`, is ignored like git would
+    ///
+    /// Deeper rule files win, and nothing under an ignored directory comes back.
+    pub async fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let segments = path.iter().count();
+        let mut dirs = vec![root.to_path_buf()];
+        let mut current = root.to_path_buf();
+
+        for (i, segment) in path.iter().enumerate() {
+            current.push(segment);
+            let current_is_dir = is_dir || i + 1 < segments;
+
+            let mut ignored = false;
+            for dir in dirs.iter().rev() {
+                let Some(rules) = self.load(dir).await else {
+                    continue;
+                };
+
+                let matched = rules.matched(&current, current_is_dir);
+                if !matched.is_none() {
+                    ignored = matched.is_ignore();
+                    break;
+                }
+            }
+
+            if ignored {
+                return true;
+            }
+            dirs.push(current.clone());
+        }
+
+        false
+    }
+
+    async fn load(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut modified = [None; 2];
+        for (modified, file) in modified.iter_mut().zip(RULE_FILES) {
+            *modified = metadata(dir.join(file))
+                .await
+                .ok()
+                .and_then(|m| m.modified().ok());
+        }
+
+        if modified.iter().all(Option::is_none) {
+            return None;
+        }
+
+        {
+            let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(cached) = cache.get(dir).filter(|c| c.modified == modified) {
+                return Some(cached.rules.clone());
+            }
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        for file in RULE_FILES {
+            let path = dir.join(file);
+            let Ok(content) = read_to_string(&path).await else {
+                continue;
+            };
+
+            for line in content.lines() {
+                if let Err(e) = builder.add_line(Some(path.clone()), line) {
+                    warn!("Invalid pattern in {path:?}: {e}");
+                }
+            }
+        }
+
+        let rules = match builder.build() {
+            Ok(rules) => Arc::new(rules),
+            Err(e) => {
+                warn!("Invalid ignore rules in {dir:?}: {e}");
+                return None;
+            }
+        };
+
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        cache.insert(
+            dir.to_path_buf(),
+            CachedRules {
+                modified,
+                rules: rules.clone(),
+            },
+        );
+
+        Some(rules)
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use super::{TemplateEntryCtx, TemplatePageCtx};
+
+/// Listing as served to API clients, without the fields of the UI
+#[derive(Serialize)]
+struct JsonListing<'a> {
+    dir: &'a str,
+    entries: Vec<JsonEntry<'a>>,
+    /// Only for listings of several pages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pagination: Option<&'a TemplatePageCtx>,
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    name: &'a str,
+    href: &'a str,
+    /// Files only
+    size: Option<u64>,
+    /// RFC 3339, in UTC
+    mtime: Option<String>,
+    is_dir: bool,
+}
+
+/// Listing of `dir` as a JSON object of its entries
+pub(in crate::static_server) fn render(
+    dir: &str,
+    files: &[TemplateEntryCtx],
+    pagination: Option<&TemplatePageCtx>,
+) -> Result<Vec<u8>, &'static str> {
+    let listing = JsonListing {
+        dir,
+        entries: files
+            .iter()
+            .map(|f| JsonEntry {
+                name: &f.file_name,
+                href: &f.href,
+                size: f.size,
+                mtime: f.modified.map(utc_time),
+                is_dir: f.is_dir,
+            })
+            .collect(),
+        pagination,
+    };
+
+    serde_json::to_vec(&listing).map_err(|_| "Failed serializing the listing")
+}
+
+fn utc_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
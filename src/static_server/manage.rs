@@ -0,0 +1,146 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use tokio::fs;
+use url::form_urlencoded;
+
+use super::{
+    url_path::{decode_path, to_relative_path},
+    utils::valid_file_name,
+    Rejection,
+};
+use crate::http::HttpStatus;
+
+/// Route under the internal root receiving the management form posts
+pub(in crate::static_server) const MANAGE_ROUTE: &str = "manage";
+
+/// Operation of a management form post
+#[derive(Debug)]
+pub(in crate::static_server) enum Action {
+    Delete { path: String },
+    Rename { path: String, name: String },
+    NewFolder { path: String, name: String },
+}
+
+impl Action {
+    /// `action`, `path` and, to rename or create, `name` fields of a urlencoded form
+    pub fn from_form(body: &[u8]) -> Result<Self, Rejection> {
+        let mut action = None;
+        let mut path = None;
+        let mut name = None;
+
+        for (key, value) in form_urlencoded::parse(body) {
+            match key.as_ref() {
+                "action" => action = Some(value.into_owned()),
+                "path" => path = Some(value.into_owned()),
+                "name" => name = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        let path = path.ok_or((HttpStatus::BadRequest, "Missing path"))?;
+        let name = || name.clone().ok_or((HttpStatus::BadRequest, "Missing name"));
+
+        match action.as_deref() {
+            Some("delete") => Ok(Action::Delete { path }),
+            Some("rename") => Ok(Action::Rename {
+                path,
+                name: name()?,
+            }),
+            Some("mkdir") => Ok(Action::NewFolder {
+                path,
+                name: name()?,
+            }),
+            _ => Err((HttpStatus::BadRequest, "Unknown action")),
+        }
+    }
+
+    /// Request path the action works on
+    pub fn path(&self) -> &str {
+        match self {
+            Action::Delete { path }
+            | Action::Rename { path, .. }
+            | Action::NewFolder { path, .. } => path,
+        }
+    }
+}
+
+/// Full path of the request path `path` under `root`, never outside of it
+pub(in crate::static_server) fn resolve(root: &Path, path: &str) -> Result<PathBuf, Rejection> {
+    decode_path(path)
+        .and_then(|path| to_relative_path(&path))
+        .map(|relative| root.join(relative))
+        .ok_or((HttpStatus::BadRequest, "Invalid path"))
+}
+
+/// Plain names only, the client path components accepted on uploads are rejected
+fn valid_name(name: &str) -> Result<&str, Rejection> {
+    valid_file_name(name)
+        .filter(|valid| *valid == name)
+        .ok_or((HttpStatus::BadRequest, "Invalid name"))
+}
+
+/// Remove a file or an empty directory
+pub(in crate::static_server) async fn delete(path: &Path) -> Result<(), Rejection> {
+    let metadata = fs::symlink_metadata(path)
+        .await
+        .map_err(|_| (HttpStatus::NotFound, "Not found"))?;
+
+    let removed = match metadata.is_dir() {
+        true => fs::remove_dir(path).await,
+        false => fs::remove_file(path).await,
+    };
+
+    removed.map_err(|e| match e.kind() {
+        ErrorKind::DirectoryNotEmpty => (HttpStatus::Conflict, "Directory not empty"),
+        _ => (HttpStatus::InternalServerError, "Failed to delete"),
+    })
+}
+
+/// Give `path` the new `name` in the same directory, returns the new path
+pub(in crate::static_server) async fn rename(
+    path: &Path,
+    name: &str,
+) -> Result<PathBuf, Rejection> {
+    let name = valid_name(name)?;
+
+    if fs::symlink_metadata(path).await.is_err() {
+        return Err((HttpStatus::NotFound, "Not found"));
+    }
+
+    let target = path.with_file_name(name);
+    if fs::symlink_metadata(&target).await.is_ok() {
+        return Err((HttpStatus::Conflict, "Name already in use"));
+    }
+
+    fs::rename(path, &target)
+        .await
+        .map_err(|_| (HttpStatus::InternalServerError, "Failed to rename"))?;
+
+    Ok(target)
+}
+
+/// Create the directory `name` inside `dir`, returns its path
+pub(in crate::static_server) async fn new_folder(
+    dir: &Path,
+    name: &str,
+) -> Result<PathBuf, Rejection> {
+    let name = valid_name(name)?;
+
+    if !dir.is_dir() {
+        return Err((HttpStatus::NotFound, "Not found"));
+    }
+
+    let folder = dir.join(name);
+    fs::create_dir(&folder).await.map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => (HttpStatus::Conflict, "Name already in use"),
+        _ => (
+            HttpStatus::InternalServerError,
+            "Failed to create the folder",
+        ),
+    })?;
+
+    Ok(folder)
+}
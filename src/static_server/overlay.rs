@@ -0,0 +1,66 @@
+//! Several roots served as one, the upper ones shadowing the lower ones
+
+use async_trait::async_trait;
+
+use super::{
+    url_path::{decode_path, to_relative_path},
+    StaticFileHandler,
+};
+use crate::http::{HttpHandler, Method, Named, Request, Response};
+
+/// Serve each path from the first layer whose root holds it
+///
+/// Layers are searched in the order added, the first one answers the paths
+/// missing everywhere, the writes and its generated pages like the sitemap.
+/// Directories are listed from the first root holding them, their entries
+/// aren't merged.
+///
+/// ```no_run
+/// use httpr::static_server::{OverlayHandler, StaticFileHandler};
+///
+/// # fn main() -> Result<(), &'static str> {
+/// let site = OverlayHandler::new(StaticFileHandler::new("theme-overrides", false)?)
+///     .with_layer(StaticFileHandler::new("theme", false)?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct OverlayHandler {
+    layers: Vec<StaticFileHandler>,
+}
+
+impl OverlayHandler {
+    pub fn new(top: StaticFileHandler) -> Self {
+        Self { layers: vec![top] }
+    }
+
+    /// Serve from `layer` the paths missing in the layers above
+    pub fn with_layer(mut self, layer: StaticFileHandler) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    fn layer(&self, request: &Request) -> &StaticFileHandler {
+        let relative = match request.method() {
+            Method::Get | Method::Head => {
+                decode_path(request.url().path()).and_then(|path| to_relative_path(&path))
+            }
+            _ => None,
+        };
+
+        relative
+            .and_then(|relative| {
+                let mut layers = self.layers.iter();
+                layers.find(|layer| layer.root.join(&relative).exists())
+            })
+            .unwrap_or(&self.layers[0])
+    }
+}
+
+impl Named for OverlayHandler {}
+
+#[async_trait]
+impl HttpHandler for OverlayHandler {
+    async fn solve_request(&self, request: &Request) -> Result<Response, &'static str> {
+        self.layer(request).solve_request(request).await
+    }
+}
@@ -0,0 +1,128 @@
+use std::{collections::HashSet, str::FromStr};
+
+use serde::Deserialize;
+use strum_macros::{Display, EnumString};
+
+use super::url_path::normalize_path;
+
+/// Operation on the served files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, Deserialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Get files and listings
+    Read,
+    /// Add new files and folders
+    Upload,
+    /// Replace existing files with uploads
+    Overwrite,
+    /// Delete and rename files and folders
+    Delete,
+}
+
+/// Permissions granted under a request path prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRule {
+    prefix: String,
+    granted: HashSet<Permission>,
+}
+
+impl PermissionRule {
+    pub fn new(prefix: &str, granted: &[Permission]) -> Self {
+        Self {
+            prefix: format!("/{}", prefix.trim_matches('/')),
+            granted: granted.iter().copied().collect(),
+        }
+    }
+
+    /// Whether `path` is the prefix or below it, whole segments only
+    fn matches(&self, path: &str) -> bool {
+        self.prefix == "/"
+            || path
+                .strip_prefix(&self.prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+/// `PREFIX=PERMISSION,...`, an empty list grants nothing
+///
+/// ```
+/// use httpr::static_server::{Permission, PermissionRule};
+///
+/// let rule: PermissionRule = "/incoming=read,upload".parse().unwrap();
+/// assert_eq!(rule, PermissionRule::new("/incoming", &[Permission::Read, Permission::Upload]));
+/// ```
+impl FromStr for PermissionRule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, granted) = s.split_once('=').ok_or("Expected PREFIX=PERMISSIONS")?;
+
+        let granted = granted
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().map_err(|_| "Unknown permission"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(prefix, &granted))
+    }
+}
+
+/// What can be done with the served files, by request path
+///
+/// The longest rule prefix matching a path decides its permissions. Write features
+/// still have to be enabled in the handler to be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    rules: Vec<PermissionRule>,
+}
+
+impl Permissions {
+    /// `default` permissions for the whole tree
+    pub fn new(default: &[Permission]) -> Self {
+        Self {
+            rules: vec![PermissionRule::new("/", default)],
+        }
+    }
+
+    /// Replace the permissions of the paths under the rule prefix
+    pub fn with_rule(mut self, rule: PermissionRule) -> Self {
+        self.rules.retain(|r| r.prefix != rule.prefix);
+        self.rules.push(rule);
+        self
+    }
+
+    /// ```
+    /// use httpr::static_server::{Permission, PermissionRule, Permissions};
+    ///
+    /// let permissions = Permissions::new(&[Permission::Read])
+    ///     .with_rule("/incoming=read,upload".parse().unwrap());
+    ///
+    /// assert!(permissions.allows("/incoming/a.txt", Permission::Upload));
+    /// assert!(!permissions.allows("/incomingx", Permission::Upload));
+    /// assert!(!permissions.allows("/docs", Permission::Delete));
+    /// assert!(permissions.allows("//incoming/./a.txt", Permission::Upload));
+    /// ```
+    ///
+    /// `path` is decoded, it is normalized like the served files are looked up and
+    /// the invalid ones are denied.
+    pub fn allows(&self, path: &str, permission: Permission) -> bool {
+        let Some(path) = normalize_path(path) else {
+            return false;
+        };
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(&path))
+            .max_by_key(|rule| rule.prefix.len())
+            .is_some_and(|rule| rule.granted.contains(&permission))
+    }
+}
+
+/// Everything but overwriting, the write features enabled decide what's done
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::new(&[Permission::Read, Permission::Upload, Permission::Delete])
+    }
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use log::warn;
+use pulldown_cmark::{html, Event, Options, Parser};
+use serde::Serialize;
+use tokio::fs::{metadata, read_to_string};
+
+/// Files rendered on top of the listings, by preference
+const README_FILES: [&str; 3] = ["README.md", "README.txt", "index.txt"];
+
+/// Bigger readmes aren't rendered
+const README_MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind", content = "content")]
+pub(in crate::static_server) enum Readme {
+    /// Markdown rendered as HTML
+    Html(String),
+    Text(String),
+}
+
+/// Readme of `dir`, if any
+pub(in crate::static_server) async fn find_readme(dir: &Path) -> Option<Readme> {
+    for name in README_FILES {
+        let path = dir.join(name);
+
+        match metadata(&path).await {
+            Ok(m) if m.is_file() && m.len() <= README_MAX_SIZE => {}
+            _ => continue,
+        }
+
+        let content = match read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Can't read {path:?}: {e}");
+                continue;
+            }
+        };
+
+        if name.ends_with(".md") {
+            return Some(Readme::Html(render_markdown(&content)));
+        }
+
+        return Some(Readme::Text(content));
+    }
+
+    None
+}
+
+fn render_markdown(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+
+    // Raw HTML is shown as text, the served files aren't trusted
+    let parser = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        event => event,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+
+    output
+}
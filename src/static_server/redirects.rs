@@ -0,0 +1,166 @@
+use std::{ops::ControlFlow, path::Path};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use regex::Regex;
+
+use crate::http::{HttpStatus, InterceptorReq, Named, Request, Response};
+
+/// Name of the redirect rules file looked up in the served root
+pub const REDIRECTS_FILE_NAME: &str = "_redirects";
+
+const SPLAT: &str = ":splat";
+
+enum RedirectMatch {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+/// Single `from -> to` redirection
+pub struct RedirectRule {
+    matcher: RedirectMatch,
+    target: String,
+    status: HttpStatus,
+}
+
+impl RedirectRule {
+    /// Redirect only the exact `path`
+    pub fn exact(path: &str, target: &str, status: HttpStatus) -> Result<Self, &'static str> {
+        Self::new(RedirectMatch::Exact(path.to_string()), target, status)
+    }
+
+    /// Redirect every path starting with `prefix`, the rest of the path replaces `:splat` in `target`
+    pub fn prefix(prefix: &str, target: &str, status: HttpStatus) -> Result<Self, &'static str> {
+        Self::new(RedirectMatch::Prefix(prefix.to_string()), target, status)
+    }
+
+    /// Redirect paths matching `pattern`, captures are expanded in `target` (`$1`, `$name`)
+    pub fn regex(pattern: &str, target: &str, status: HttpStatus) -> Result<Self, &'static str> {
+        let regex = Regex::new(pattern).map_err(|_| "Invalid redirect pattern!")?;
+        Self::new(RedirectMatch::Regex(regex), target, status)
+    }
+
+    fn new(matcher: RedirectMatch, target: &str, status: HttpStatus) -> Result<Self, &'static str> {
+        if !status.is_redirect() {
+            return Err("Invalid redirect status!");
+        }
+
+        Ok(Self {
+            matcher,
+            target: target.to_string(),
+            status,
+        })
+    }
+
+    /// Location for `path` when the rule matches
+    fn location(&self, path: &str) -> Option<String> {
+        match &self.matcher {
+            RedirectMatch::Exact(p) => (p == path).then(|| self.target.clone()),
+            RedirectMatch::Prefix(p) => path
+                .strip_prefix(p.as_str())
+                .map(|rest| self.target.replace(SPLAT, rest)),
+            RedirectMatch::Regex(r) => r.captures(path).map(|captures| {
+                let mut location = String::new();
+                captures.expand(&self.target, &mut location);
+                location
+            }),
+        }
+    }
+}
+
+/// Answer with a redirect when the request path matches one of the rules
+///
+/// Rules are checked in order and the first match wins.
+pub struct RedirectReqInterceptor {
+    rules: Vec<RedirectRule>,
+}
+
+impl RedirectReqInterceptor {
+    pub fn new(rules: Vec<RedirectRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Load rules from a `_redirects`-style file
+    ///
+    /// Each line is `from to [status]`, blank lines and lines starting with `#` are ignored.
+    /// `from` is an exact path, a prefix when ending with `*` (the rest goes to `:splat`)
+    /// or a regex when starting with `~`. Status defaults to `301`.
+    ///
+    /// ```text
+    /// /old             /new
+    /// /blog/*          /posts/:splat    302
+    /// ~^/item/(\d+)$   /items/$1        307
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let content =
+            std::fs::read_to_string(path).map_err(|_| "Can't read the redirects file!")?;
+
+        let mut rules = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let (from, to) = match (parts.next(), parts.next()) {
+                (Some(from), Some(to)) => (from, to),
+                _ => return Err("Invalid redirect rule!"),
+            };
+
+            let status = match parts.next() {
+                Some(code) => code
+                    .parse::<u16>()
+                    .ok()
+                    .and_then(HttpStatus::from_code)
+                    .ok_or("Invalid redirect status!")?,
+                None => HttpStatus::MovedPermanently,
+            };
+
+            let rule = if let Some(pattern) = from.strip_prefix('~') {
+                RedirectRule::regex(pattern, to, status)?
+            } else if let Some(prefix) = from.strip_suffix('*') {
+                RedirectRule::prefix(prefix, to, status)?
+            } else {
+                RedirectRule::exact(from, to, status)?
+            };
+
+            rules.push(rule);
+        }
+
+        Ok(Self::new(rules))
+    }
+
+    /// Load the `_redirects` file from `root`, `None` when there isn't any
+    pub fn from_root<P: AsRef<Path>>(root: P) -> Option<Result<Self, &'static str>> {
+        let path = root.as_ref().join(REDIRECTS_FILE_NAME);
+        path.is_file().then(|| Self::from_file(path))
+    }
+}
+
+impl Named for RedirectReqInterceptor {}
+
+#[async_trait]
+impl InterceptorReq for RedirectReqInterceptor {
+    async fn chain_req(&self, request: Request) -> ControlFlow<Response, Request> {
+        let url = request.url();
+
+        for rule in &self.rules {
+            if let Some(location) = rule.location(url.path()) {
+                debug!("Redirect {} -> {location}", url.path());
+
+                if location.is_empty() {
+                    warn!("Empty redirect location for {}", url.path());
+                    continue;
+                }
+
+                let mut response = Response::new(rule.status);
+                response.add_header(("Location", &location));
+
+                return ControlFlow::Break(response);
+            }
+        }
+
+        ControlFlow::Continue(request)
+    }
+}
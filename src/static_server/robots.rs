@@ -0,0 +1,30 @@
+/// Path of the synthesized robots file, a file with that name on disk is served instead
+pub const ROBOTS_PATH: &str = "/robots.txt";
+
+/// What `/robots.txt` tells crawlers when the served directory has none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RobotsPolicy {
+    /// Crawl everything, pointing to the sitemap when one is generated
+    AllowAll,
+    /// Crawl nothing, keeps ad-hoc shares out of search engines
+    DenyAll,
+    /// Serve this body as it is
+    Custom(String),
+}
+
+impl RobotsPolicy {
+    /// Body of `/robots.txt`, `sitemap` is the URL of the generated sitemap
+    pub(in crate::static_server) fn body(&self, sitemap: Option<&str>) -> String {
+        match self {
+            RobotsPolicy::AllowAll => {
+                let mut body = String::from("User-agent: *\nDisallow:\n");
+                if let Some(sitemap) = sitemap {
+                    body.push_str(&format!("\nSitemap: {sitemap}\n"));
+                }
+                body
+            }
+            RobotsPolicy::DenyAll => String::from("User-agent: *\nDisallow: /\n"),
+            RobotsPolicy::Custom(body) => body.clone(),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use log::{debug, warn};
+
+use super::StaticFileHandler;
+use crate::http::{HttpHandler, HttpStatus, InterceptorRes, Named, Request, Response};
+
+/// Response header asking to serve a file instead, see [Response::sendfile]
+pub const SENDFILE_HEADER: &str = "X-Sendfile";
+
+/// Same as [SENDFILE_HEADER], as named by nginx
+const ACCEL_REDIRECT_HEADER: &str = "X-Accel-Redirect";
+
+/// Serve the file named by the `X-Sendfile` or `X-Accel-Redirect` header of a response
+///
+/// The application handler checks the access and answers with the path of the file,
+/// relative to the root of `handler`, which serves it with ranges, conditional
+/// requests and streaming. Headers of the application response missing in the file
+/// response are kept, like `Content-Disposition` or `Cache-Control`.
+pub struct SendfileResInterceptor {
+    handler: StaticFileHandler,
+}
+
+impl SendfileResInterceptor {
+    pub fn new(handler: StaticFileHandler) -> Self {
+        Self { handler }
+    }
+}
+
+impl Named for SendfileResInterceptor {}
+
+#[async_trait]
+impl InterceptorRes for SendfileResInterceptor {
+    async fn chain_res(&self, request: &Request, mut response: Response) -> Response {
+        let Some(path) = response
+            .remove_header(SENDFILE_HEADER)
+            .or_else(|| response.remove_header(ACCEL_REDIRECT_HEADER))
+        else {
+            return response;
+        };
+
+        if !path.starts_with('/') {
+            warn!("Sendfile path must be absolute: {path}");
+            return Response::new(HttpStatus::InternalServerError);
+        }
+
+        debug!("Sending {path} for {}", request.url().path());
+        let mut file_response = match self.handler.solve_request(&request.internal(&path)).await {
+            Ok(file_response) => file_response,
+            Err(e) => {
+                warn!("Sendfile of {path} failed: {e}");
+                return Response::new(HttpStatus::InternalServerError);
+            }
+        };
+
+        for (name, value) in response.headers() {
+            if name != "content-length" && file_response.header(name).is_none() {
+                file_response.add_header((name, value));
+            }
+        }
+
+        file_response
+    }
+}
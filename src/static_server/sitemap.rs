@@ -0,0 +1,98 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+/// Path of the generated sitemap, a file with that name on disk is served instead
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+
+/// How long a generated sitemap is served before walking the root again
+pub const SITEMAP_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// URLs of a sitemap at most, by the protocol
+pub(in crate::static_server) const MAX_SITEMAP_URLS: usize = 50_000;
+
+/// Served file of the sitemap
+pub(in crate::static_server) struct SitemapEntry {
+    /// Encoded URL path
+    pub path: String,
+    pub modified: Option<SystemTime>,
+}
+
+/// Generated `sitemap.xml` of the served files
+pub(in crate::static_server) struct Sitemap {
+    base_url: Url,
+    cached: RwLock<Option<(Instant, Arc<str>)>>,
+}
+
+impl Sitemap {
+    /// `base_url` is the public URL of the root, like `https://example.com/docs/`
+    pub fn new(base_url: &str) -> Result<Self, &'static str> {
+        let mut base_url = Url::parse(base_url).map_err(|_| "Invalid sitemap base URL!")?;
+        if !matches!(base_url.scheme(), "http" | "https") || base_url.cannot_be_a_base() {
+            return Err("Sitemap base URL must be http or https!");
+        }
+
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        base_url.set_query(None);
+        base_url.set_fragment(None);
+
+        Ok(Self {
+            base_url,
+            cached: RwLock::new(None),
+        })
+    }
+
+    /// Public URL of the sitemap
+    pub fn url(&self) -> String {
+        format!("{}{}", self.base_url.as_str(), &SITEMAP_PATH[1..])
+    }
+
+    /// Last sitemap generated, while younger than [SITEMAP_MAX_AGE]
+    pub fn cached(&self) -> Option<Arc<str>> {
+        match &*self.cached.read().unwrap() {
+            Some((generated, xml)) if generated.elapsed() < SITEMAP_MAX_AGE => Some(xml.clone()),
+            _ => None,
+        }
+    }
+
+    /// Render `entries` and keep the result for the next requests
+    pub fn generate(&self, entries: &[SitemapEntry]) -> Arc<str> {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+        ));
+
+        for entry in entries {
+            let loc = format!(
+                "{}{}",
+                self.base_url.as_str().trim_end_matches('/'),
+                entry.path
+            );
+            xml.push_str(&format!("  <url>\n    <loc>{}</loc>\n", xml_escape(&loc)));
+            if let Some(modified) = entry.modified {
+                let modified = DateTime::<Utc>::from(modified).format("%Y-%m-%d");
+                xml.push_str(&format!("    <lastmod>{modified}</lastmod>\n"));
+            }
+            xml.push_str("  </url>\n");
+        }
+        xml.push_str("</urlset>\n");
+
+        let xml: Arc<str> = xml.into();
+        *self.cached.write().unwrap() = Some((Instant::now(), xml.clone()));
+        xml
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
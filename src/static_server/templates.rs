@@ -0,0 +1,71 @@
+//! Handlebars templates shared with the built-in pages
+//!
+//! Templates registered here can use the partials and helpers of the others,
+//! like `asset` to inline a bundled asset. The built-in templates are registered
+//! as [DIRECTORY_TEMPLATE], [NOT_FOUND_TEMPLATE] and [DASHBOARD_TEMPLATE],
+//! registering one of these names replaces the built-in page.
+//!
+//! In dev mode, see [enable_dev_mode], the built-in templates and the assets are
+//! read from disk as they are edited, without rebuilding httpr.
+
+use std::sync::PoisonError;
+
+use log::warn;
+use serde::Serialize;
+
+pub use super::handlebars::{
+    enable_dev_mode, is_dev_mode, DASHBOARD_TEMPLATE, DIRECTORY_TEMPLATE, NOT_FOUND_TEMPLATE,
+};
+pub use ::handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+};
+
+use super::handlebars::HBS;
+
+/// Register the template `name`, replacing any other with that name
+///
+/// ```
+/// use httpr::static_server::templates;
+///
+/// templates::register_template("hello", "Hello, {{name}}!").unwrap();
+/// let hello = templates::render("hello", &serde_json::json!({ "name": "world" }));
+/// assert_eq!(hello.unwrap(), "Hello, world!");
+/// ```
+pub fn register_template(name: &str, source: &str) -> Result<(), &'static str> {
+    HBS.write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register_template_string(name, source)
+        .map_err(|e| {
+            warn!("Invalid template {name}: {e}");
+            "Invalid template"
+        })
+}
+
+/// Register a partial, included in the templates with `{{> name}}`
+pub fn register_partial(name: &str, source: &str) -> Result<(), &'static str> {
+    HBS.write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register_partial(name, source)
+        .map_err(|e| {
+            warn!("Invalid partial {name}: {e}");
+            "Invalid partial"
+        })
+}
+
+/// Register a helper, a [HelperDef] or a closure with its signature
+pub fn register_helper<H: HelperDef + Send + Sync + 'static>(name: &str, helper: H) {
+    HBS.write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register_helper(name, Box::new(helper));
+}
+
+/// Render the template `name` with `context`
+pub fn render<T: Serialize>(name: &str, context: &T) -> Result<String, &'static str> {
+    HBS.read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .render(name, context)
+        .map_err(|e| {
+            warn!("Failed rendering {name}: {e}");
+            "Failed rendering the template"
+        })
+}
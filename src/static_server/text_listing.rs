@@ -0,0 +1,84 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+
+use super::{TemplateEntryCtx, TemplatePageCtx};
+use crate::http::Request;
+
+/// Command line clients shown the text listing when they accept anything
+const TEXT_CLIENTS: [&str; 2] = ["curl/", "wget/"];
+
+/// Whether `request` comes from a terminal client not asking for a type
+pub(in crate::static_server) fn is_text_client(request: &Request) -> bool {
+    let accepts_any = request
+        .header("accept")
+        .is_none_or(|accept| accept.trim() == "*/*");
+    let user_agent = request
+        .header("user-agent")
+        .unwrap_or_default()
+        .to_lowercase();
+
+    accepts_any && TEXT_CLIENTS.iter().any(|c| user_agent.starts_with(c))
+}
+
+/// Listing of `dir` as aligned name, size and modification time columns
+pub(in crate::static_server) fn render(
+    dir: &str,
+    files: &[TemplateEntryCtx],
+    pagination: Option<&TemplatePageCtx>,
+) -> String {
+    let names = files
+        .iter()
+        .map(|f| match f.is_dir {
+            true => format!("{}/", f.file_name),
+            false => f.file_name.to_string(),
+        })
+        .collect::<Vec<_>>();
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+
+    let mut listing = format!("Index of {dir}\n\n");
+    for (file, name) in files.iter().zip(&names) {
+        let size = file.size.map_or(String::from("-"), human_size);
+        let modified = file.modified.map_or(String::from("-"), local_time);
+
+        let padding = width - name.chars().count();
+        listing.push_str(&format!("{name}{:padding$}  {size:>6}  {modified}\n", ""));
+    }
+
+    if let Some(page) = pagination {
+        listing.push_str(&format!("\nPage {} of {}", page.page, page.pages));
+        if let Some(next) = &page.next {
+            listing.push_str(&format!(", next: {next}"));
+        }
+        listing.push('\n');
+    }
+
+    listing
+}
+
+/// `ls -h` style size, `512`, `1.5K`, `20M`
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match value < 10.0 {
+        true => format!("{value:.1}{}", UNITS[unit]),
+        false => format!("{value:.0}{}", UNITS[unit]),
+    }
+}
+
+fn local_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use image::ImageFormat;
+use log::warn;
+use tokio::{fs::metadata, task::spawn_blocking};
+
+/// Route under the internal root serving the thumbnails
+pub const THUMBNAIL_ROUTE: &str = "thumbnail";
+
+/// Thumbnails kept in memory before flushing the cache
+const CACHE_CAPACITY: usize = 1024;
+
+struct CachedThumbnail {
+    modified: SystemTime,
+    data: Arc<Vec<u8>>,
+}
+
+/// Downscaled PNG previews of the images, cached until the file changes
+pub(in crate::static_server) struct Thumbnails {
+    size: u32,
+    cache: Mutex<HashMap<PathBuf, CachedThumbnail>>,
+}
+
+impl Thumbnails {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, path: &Path) -> Option<Arc<Vec<u8>>> {
+        let modified = metadata(path).await.ok()?.modified().ok()?;
+
+        {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(cached) = cache.get(path).filter(|c| c.modified == modified) {
+                return Some(cached.data.clone());
+            }
+        }
+
+        let size = self.size;
+        let source = path.to_path_buf();
+        let data = match spawn_blocking(move || render(&source, size)).await {
+            Ok(Some(data)) => Arc::new(data),
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Thumbnail task failed: {e}");
+                return None;
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+
+        cache.insert(
+            path.to_path_buf(),
+            CachedThumbnail {
+                modified,
+                data: data.clone(),
+            },
+        );
+
+        Some(data)
+    }
+}
+
+fn render(path: &Path, size: u32) -> Option<Vec<u8>> {
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Can't decode {path:?}: {e}");
+            return None;
+        }
+    };
+
+    let mut data = Cursor::new(Vec::new());
+    image
+        .thumbnail(size, size)
+        .write_to(&mut data, ImageFormat::Png)
+        .ok()?;
+
+    Some(data.into_inner())
+}
@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use super::{
+    utils::{valid_file_name, validators},
+    Rejection,
+};
+use crate::http::{
+    conditional::write_allowed,
+    multipart::{form_boundary, parse_form_data},
+    HttpStatus, Request,
+};
+
+/// Default limit of an upload request body, 100 MiB
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Save the files of a `multipart/form-data` request into `dir`
+///
+/// Every file is validated before writing any, existing files are only replaced
+/// with `overwrite` and when they pass the request preconditions (`If-Match`,
+/// `If-None-Match: *`, `If-Unmodified-Since`). Returns the names of the saved files.
+pub(in crate::static_server) async fn save_files(
+    request: &Request,
+    dir: &Path,
+    max_size: u64,
+    overwrite: bool,
+) -> Result<Vec<String>, Rejection> {
+    if request.body().len() as u64 > max_size {
+        return Err((HttpStatus::PayloadTooLarge, "Upload too large"));
+    }
+
+    let boundary = request
+        .header("content-type")
+        .and_then(form_boundary)
+        .ok_or((
+            HttpStatus::UnsupportedMediaType,
+            "Expected multipart/form-data",
+        ))?;
+
+    let parts = parse_form_data(request.body(), boundary)
+        .map_err(|reason| (HttpStatus::BadRequest, reason))?;
+
+    let mut files = Vec::new();
+    for part in &parts {
+        let Some(file_name) = &part.file_name else {
+            continue;
+        };
+
+        // Empty file inputs are sent with an empty name
+        if file_name.is_empty() && part.data.is_empty() {
+            continue;
+        }
+
+        let name =
+            valid_file_name(file_name).ok_or((HttpStatus::BadRequest, "Invalid file name"))?;
+        let path = dir.join(name);
+
+        let is_taken = match overwrite {
+            true => path.exists() && !path.is_file(),
+            false => path.exists(),
+        };
+
+        if is_taken || files.iter().any(|(p, _): &(PathBuf, _)| *p == path) {
+            return Err((HttpStatus::Conflict, "File already exists"));
+        }
+
+        if !write_allowed(request, validators(&path).as_ref()) {
+            return Err((HttpStatus::PreconditionFailed, "Changed since it was read"));
+        }
+
+        files.push((path, part.data));
+    }
+
+    if files.is_empty() {
+        return Err((HttpStatus::BadRequest, "No files to upload"));
+    }
+
+    let mut saved = Vec::with_capacity(files.len());
+    for (path, data) in files {
+        let write = async {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(overwrite)
+                .create_new(!overwrite)
+                .open(&path)
+                .await?;
+            file.write_all(data).await?;
+            file.flush().await
+        };
+
+        match write.await {
+            Ok(()) => saved.push(path.file_name().unwrap().to_string_lossy().into_owned()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err((HttpStatus::Conflict, "File already exists"))
+            }
+            Err(_) => return Err((HttpStatus::InternalServerError, "Failed to save the file")),
+        }
+    }
+
+    Ok(saved)
+}
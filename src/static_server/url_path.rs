@@ -0,0 +1,254 @@
+//! Conversions between URL paths and the names of the served files
+
+use std::path::{Component, Path, PathBuf};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+/// Device names of Windows, whatever the extension
+const WINDOWS_RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters of a file name breaking a path segment, non-ASCII is always encoded
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Percent-encode a path of file names to use it in a link or a `Location`
+///
+/// ```
+/// use httpr::static_server::url_path::encode_path;
+///
+/// assert_eq!(encode_path("/docs/café #1?.txt"), "/docs/caf%C3%A9%20%231%3F.txt");
+/// assert_eq!(encode_path("/100%/"), "/100%25/");
+/// ```
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// File names path of a request path
+///
+/// `None` when it isn't UTF-8 or, once decoded, has `..` segments.
+///
+/// ```
+/// use httpr::static_server::url_path::{decode_path, encode_path};
+///
+/// let name = "/docs/naïve 100% #1?.txt";
+/// assert_eq!(decode_path(&encode_path(name)).as_deref(), Some(name));
+/// assert_eq!(decode_path("/a/%2E%2E%2Fsecret"), None);
+/// assert_eq!(decode_path("/%FF"), None);
+/// ```
+pub fn decode_path(path: &str) -> Option<String> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+
+    if decoded.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+
+    Some(decoded.into_owned())
+}
+
+/// Relative path of the OS for a decoded request path, to join it to a root
+///
+/// Every segment has to be a plain file name on this platform, so `..`, drive
+/// letters, `\` separators or device names on Windows give `None`.
+///
+/// ```
+/// use std::path::PathBuf;
+///
+/// use httpr::static_server::url_path::to_relative_path;
+///
+/// assert_eq!(to_relative_path("/docs/./a b.txt"), Some(PathBuf::from("docs").join("a b.txt")));
+/// assert_eq!(to_relative_path("/"), Some(PathBuf::new()));
+/// assert_eq!(to_relative_path("/docs/../../secret"), None);
+///
+/// if cfg!(windows) {
+///     assert_eq!(to_relative_path("/C:/Windows/win.ini"), None);
+///     assert_eq!(to_relative_path("/docs/..\\..\\secret"), None);
+///     assert_eq!(to_relative_path("/nul.txt"), None);
+/// }
+/// ```
+pub fn to_relative_path(path: &str) -> Option<PathBuf> {
+    relative_path(path, cfg!(windows))
+}
+
+/// Decoded request path `path` of the file it is looked up as, `/` separated
+///
+/// Empty and `.` segments are dropped, so access rules match what is served.
+/// `None` when [to_relative_path] refuses it.
+///
+/// ```
+/// use httpr::static_server::url_path::normalize_path;
+///
+/// assert_eq!(normalize_path("//private/./a b.txt").as_deref(), Some("/private/a b.txt"));
+/// assert_eq!(normalize_path("").as_deref(), Some("/"));
+/// assert_eq!(normalize_path("/docs/../secret"), None);
+/// ```
+pub fn normalize_path(path: &str) -> Option<String> {
+    let segments = to_relative_path(path)?
+        .iter()
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    Some(format!("/{}", segments.join("/")))
+}
+
+/// [to_relative_path] with the Windows rules applied when `windows`
+fn relative_path(path: &str, windows: bool) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+
+    for segment in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if !is_file_name(segment, windows) {
+            return None;
+        }
+
+        relative.push(segment);
+    }
+
+    Some(relative)
+}
+
+/// Whether `segment` is a single plain file name, also on Windows when `windows`
+fn is_file_name(segment: &str, windows: bool) -> bool {
+    let mut components = Path::new(segment).components();
+    let is_name = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(name)), None) if name == segment
+    );
+
+    // Separators, drive letters and streams, and the device names
+    is_name && !(windows && (segment.contains(['\\', ':']) || is_windows_reserved(segment)))
+}
+
+/// Encoded URL path of the file names in `path`, `/` separated on every platform
+///
+/// ```
+/// use std::path::Path;
+///
+/// use httpr::static_server::url_path::to_url_path;
+///
+/// assert_eq!(to_url_path(&Path::new("/docs").join("café.txt")), "/docs/caf%C3%A9.txt");
+/// assert_eq!(to_url_path(Path::new("")), "/");
+/// ```
+pub fn to_url_path(path: &Path) -> String {
+    let segments = path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    encode_path(&format!("/{}", segments.join("/")))
+}
+
+fn is_windows_reserved(segment: &str) -> bool {
+    let stem = segment.split('.').next().unwrap_or_default().trim_end();
+    WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        http::{HttpHandler, HttpStatus, Request, DEFAULT_MAX_URI_LENGTH},
+        static_server::StaticFileHandler,
+    };
+
+    async fn get(handler: &StaticFileHandler, uri: &str, accept: &str) -> (HttpStatus, Vec<u8>) {
+        let raw = format!("GET {uri} HTTP/1.1\r\nHost: localhost\r\nAccept: {accept}\r\n\r\n");
+        let request = Request::read_head(&mut raw.as_bytes(), DEFAULT_MAX_URI_LENGTH)
+            .await
+            .unwrap();
+        let mut response = handler.solve_request(&request).await.unwrap();
+
+        let mut message = Vec::new();
+        response.write_to(&mut message).await.unwrap();
+        let body_start = message.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+
+        (response.status(), message.split_off(body_start))
+    }
+
+    #[tokio::test]
+    async fn listing_hrefs_lead_back_to_their_files() {
+        let root = std::env::temp_dir().join(format!("httpr-url-path-{}", std::process::id()));
+        let mut names = vec!["a b.txt", "#1.txt", "100%.txt", "café.txt", "日本語.txt"];
+        if !cfg!(windows) {
+            names.push("what?.txt");
+        }
+
+        fs::create_dir_all(root.join("dir #2?%")).unwrap();
+        for name in &names {
+            fs::write(root.join(name), name).unwrap();
+            fs::write(root.join("dir #2?%").join(name), name).unwrap();
+        }
+
+        let handler = StaticFileHandler::new(&root, true).unwrap();
+        for dir in ["/", "/dir%20%232%3F%25/"] {
+            let (status, listing) = get(&handler, dir, "application/json").await;
+            assert_eq!(status, HttpStatus::Ok);
+
+            let listing: serde_json::Value = serde_json::from_slice(&listing).unwrap();
+            let entries = listing["entries"].as_array().unwrap();
+            for entry in entries.iter().filter(|e| e["is_dir"] == false) {
+                let href = entry["href"].as_str().unwrap();
+                let (status, body) = get(&handler, href, "*/*").await;
+                assert_eq!(status, HttpStatus::Ok, "{href}");
+                assert_eq!(body, entry["name"].as_str().unwrap().as_bytes(), "{href}");
+            }
+            assert_eq!(entries.len(), names.len() + usize::from(dir == "/"));
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn windows_names_are_refused_on_every_platform() {
+        for path in [
+            "/C:/Windows/win.ini",
+            "/docs/..\\..\\secret",
+            "/docs\\a.txt",
+            "/file.txt:stream",
+            "/nul.txt",
+            "/COM1",
+            "/lpt9.tar.gz",
+            "/con /a.txt",
+        ] {
+            assert_eq!(relative_path(path, true), None, "{path}");
+        }
+
+        assert_eq!(
+            relative_path("/docs/console.txt", true),
+            Some(PathBuf::from("docs").join("console.txt"))
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn windows_names_are_files_elsewhere() {
+        for path in ["/nul.txt", "/a:b", "/a\\b"] {
+            assert_eq!(relative_path(path, false), Some(PathBuf::from(&path[1..])));
+        }
+    }
+}
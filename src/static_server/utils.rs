@@ -1,4 +1,13 @@
-use std::path::Path;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::http::{
+    conditional::{http_date, Validators},
+    Response,
+};
 
 pub fn mime_by_ext(ext: &str) -> String {
     mime_guess::from_ext(ext).first_or_text_plain().to_string()
@@ -9,3 +18,52 @@ pub fn mime_by_path(path: &Path) -> String {
         .first_or_text_plain()
         .to_string()
 }
+
+/// `page.html` -> `page.html.<lang>`
+pub fn variant_path(file_path: &Path, lang: &str) -> PathBuf {
+    let mut variant = file_path.as_os_str().to_owned();
+    variant.push(".");
+    variant.push(lang);
+
+    PathBuf::from(variant)
+}
+
+/// Last component of a client file name, hidden and special names are rejected
+pub fn valid_file_name(file_name: &str) -> Option<&str> {
+    // Some clients send the full path
+    let name = file_name.rsplit(['/', '\\']).next()?.trim();
+
+    let is_valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.chars().any(char::is_control)
+        && name.len() <= 255;
+
+    is_valid.then_some(name)
+}
+
+/// Current validators of the file or directory at `path`, `None` if it doesn't exist
+pub fn validators(path: &Path) -> Option<Validators> {
+    fs::metadata(path)
+        .ok()
+        .and_then(|metadata| Validators::from_metadata(&metadata))
+}
+
+pub fn add_validators(response: &mut Response, validators: &Validators) {
+    response.add_header(("ETag", &validators.etag));
+    response.add_header(("Last-Modified", &http_date(validators.last_modified)));
+}
+
+/// Lowercase hexadecimal representation of `bytes`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Delimiter of the `multipart/byteranges` parts, unlikely to show up in the files
+pub fn multipart_boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    format!("httpr-{}", hex(&nanos.to_be_bytes()))
+}
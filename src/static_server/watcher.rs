@@ -0,0 +1,26 @@
+use std::{path::Path, sync::Arc};
+
+use log::warn;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::FileCache;
+
+/// Refresh the entries of `cache` as the files under `root` change
+///
+/// The changes are seen while the returned watcher is alive.
+pub(in crate::static_server) fn watch(
+    root: &Path,
+    cache: Arc<FileCache>,
+) -> notify::Result<RecommendedWatcher> {
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if !event.kind.is_access() => {
+                event.paths.iter().for_each(|path| cache.refresh(path));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("File watcher error: {e}"),
+        })?;
+
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
@@ -0,0 +1,255 @@
+//! Load generation over loopback and allocation counting, for the benches
+//!
+//! Behind the `testing` feature.
+//!
+//! Spawn a server with [LoopbackServer::start], hammer it with a [Load] and
+//! read its [LoadReport]. Allocations are counted once [CountingAllocator] is
+//! the global allocator of the binary:
+//!
+//! ```
+//! use httpr::testing::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator;
+//! ```
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{self, Error, ErrorKind},
+    net::{SocketAddr, TcpListener as StdTcpListener},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    task::{JoinHandle, JoinSet},
+    time::sleep,
+};
+
+use crate::http::{HttpHandler, Request, Server, DEFAULT_MAX_URI_LENGTH};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// System allocator counting the allocations, read with [allocations]
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Allocations since the start, always 0 without [CountingAllocator]
+pub fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Request read from `raw` as the server does, head and body
+pub async fn parse_request(raw: &[u8]) -> io::Result<Request> {
+    let mut reader = BufReader::new(raw);
+
+    let mut request = Request::read_head(&mut reader, DEFAULT_MAX_URI_LENGTH).await?;
+    request.read_body(&mut reader).await?;
+
+    Ok(request)
+}
+
+/// [Server] running on a free loopback port, stopped when dropped
+pub struct LoopbackServer {
+    addr: SocketAddr,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl LoopbackServer {
+    /// Run `handler` with the default server settings
+    pub async fn start<H: HttpHandler + 'static>(handler: H) -> io::Result<Self> {
+        Self::start_with(handler, |_| {}).await
+    }
+
+    /// Run `handler` with the server settings applied by `setup`
+    pub async fn start_with<H, F>(handler: H, setup: F) -> io::Result<Self>
+    where
+        H: HttpHandler + 'static,
+        F: FnOnce(&mut Server<H>),
+    {
+        let addr = StdTcpListener::bind("127.0.0.1:0")?.local_addr()?;
+
+        let mut server = Server::new(addr.to_string(), handler);
+        setup(&mut server);
+        let task = tokio::spawn(async move { server.run().await });
+
+        // Ready once it accepts connections
+        for _ in 0..100 {
+            if TcpStream::connect(addr).await.is_ok() {
+                return Ok(Self { addr, task });
+            }
+            if task.is_finished() {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        task.abort();
+        Err(Error::new(
+            ErrorKind::AddrNotAvailable,
+            "Server didn't start",
+        ))
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for LoopbackServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Same raw request sent by concurrent clients, one connection each time
+///
+/// Every request opens a new connection and its response is read until closed,
+/// the raw requests must ask for it with `Connection: close`.
+///
+/// ```no_run
+/// use httpr::{testing::{Load, LoopbackServer}, static_server::StaticFileHandler};
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let handler = StaticFileHandler::new(".", true).unwrap();
+/// let server = LoopbackServer::start(handler).await?;
+///
+/// let report = Load::get("/")
+///     .with_clients(16)
+///     .with_requests(100)
+///     .run(server.addr())
+///     .await?;
+/// println!("{:.0} req/s", report.requests_per_sec());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Load {
+    request: Vec<u8>,
+    clients: usize,
+    requests: usize,
+}
+
+impl Load {
+    /// Send the `raw` request, as is
+    pub fn new(raw: &[u8]) -> Self {
+        Self {
+            request: raw.to_vec(),
+            clients: 1,
+            requests: 1,
+        }
+    }
+
+    /// Send a `GET` of `uri`
+    pub fn get(uri: &str) -> Self {
+        let request = format!("GET {uri} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        Self::new(request.as_bytes())
+    }
+
+    /// Clients sending at the same time, 1 unless set
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients.max(1);
+        self
+    }
+
+    /// Requests sent by each client one after another, 1 unless set
+    pub fn with_requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Send every request to `addr` and wait for the responses
+    pub async fn run(&self, addr: SocketAddr) -> io::Result<LoadReport> {
+        let allocations_before = allocations();
+        let start = Instant::now();
+
+        let mut clients = JoinSet::new();
+        for _ in 0..self.clients {
+            let request = self.request.clone();
+            let requests = self.requests;
+            clients.spawn(async move {
+                let mut report = LoadReport::default();
+                let mut response = Vec::new();
+
+                for _ in 0..requests {
+                    let mut stream = TcpStream::connect(addr).await?;
+                    stream.write_all(&request).await?;
+
+                    response.clear();
+                    stream.read_to_end(&mut response).await?;
+
+                    report.requests += 1;
+                    report.bytes += response.len() as u64;
+                    if !is_success(&response) {
+                        report.failures += 1;
+                    }
+                }
+
+                Ok::<_, Error>(report)
+            });
+        }
+
+        let mut report = LoadReport::default();
+        while let Some(client) = clients.join_next().await {
+            let client = client.map_err(Error::other)??;
+            report.requests += client.requests;
+            report.failures += client.failures;
+            report.bytes += client.bytes;
+        }
+
+        report.elapsed = start.elapsed();
+        report.allocations = allocations() - allocations_before;
+
+        Ok(report)
+    }
+}
+
+/// Whether the status line of `response` is a `2xx` or `3xx` one
+fn is_success(response: &[u8]) -> bool {
+    matches!(response.get(9), Some(b'2' | b'3')) && response.starts_with(b"HTTP/")
+}
+
+/// Outcome of a [Load]
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub requests: u64,
+    /// Responses other than `2xx` and `3xx`
+    pub failures: u64,
+    /// Bytes of the responses, heads included
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Allocations of the clients and the server meanwhile, see [CountingAllocator]
+    pub allocations: u64,
+}
+
+impl LoadReport {
+    pub fn requests_per_sec(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn allocations_per_request(&self) -> f64 {
+        self.allocations as f64 / self.requests.max(1) as f64
+    }
+}
@@ -0,0 +1,22 @@
+//! HTTP/1.1 conformance of the server, see [Conformance]
+
+use std::fs;
+
+use httpr::{conformance::Conformance, static_server::StaticFileHandler, testing::LoopbackServer};
+
+#[tokio::test]
+async fn static_files_conform() {
+    let root = std::env::temp_dir().join(format!("httpr-conformance-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("hello.txt"), "Hello, world :)").unwrap();
+
+    let handler = StaticFileHandler::new(&root, true).unwrap();
+    let server = LoopbackServer::start(handler).await.unwrap();
+
+    for path in ["/", "/hello.txt"] {
+        let report = Conformance::new(server.addr()).with_path(path).run().await;
+        assert!(report.passed(), "{path}: {report}");
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
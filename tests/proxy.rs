@@ -0,0 +1,133 @@
+//! Upgraded connections and cached responses through the [ProxyHandler]
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use httpr::{http::proxy::ProxyHandler, testing::LoopbackServer};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+/// Head up to the empty line, lowercased
+async fn read_head<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> String {
+    let mut head = String::new();
+    while !head.ends_with("\r\n\r\n") {
+        assert_ne!(reader.read_line(&mut head).await.unwrap(), 0, "{head}");
+    }
+    head.to_lowercase()
+}
+
+/// Whole response to a `GET` of `path`
+async fn get(addr: SocketAddr, path: &str) -> String {
+    let mut client = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    client.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn upgraded_connections_are_relayed() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    // Echoes the bytes back once switched to `echo`
+    let echo = tokio::spawn(async move {
+        let (socket, _) = upstream.accept().await.unwrap();
+        let mut socket = BufReader::new(socket);
+        let head = read_head(&mut socket).await;
+
+        socket
+            .get_mut()
+            .write_all(
+                b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let (mut reader, mut writer) = tokio::io::split(socket);
+        tokio::io::copy(&mut reader, &mut writer).await.unwrap();
+        head
+    });
+
+    let proxy = ProxyHandler::new(&format!("http://{upstream_addr}")).unwrap();
+    let server = LoopbackServer::start(proxy).await.unwrap();
+
+    let client = TcpStream::connect(server.addr()).await.unwrap();
+    let mut client = BufReader::new(client);
+    client
+        .get_mut()
+        .write_all(b"GET /chat HTTP/1.1\r\nHost: a\r\nConnection: keep-alive, Upgrade\r\nUpgrade: echo\r\n\r\n")
+        .await
+        .unwrap();
+
+    let head = read_head(&mut client).await;
+    assert!(head.starts_with("http/1.1 101"), "{head}");
+    assert!(head.contains("upgrade: echo\r\n"), "{head}");
+    assert!(head.contains("connection: upgrade\r\n"), "{head}");
+
+    client.get_mut().write_all(b"ping").await.unwrap();
+    let mut echoed = [0; 4];
+    client.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+
+    client.get_mut().shutdown().await.unwrap();
+    let upstream_head = echo.await.unwrap();
+    assert!(
+        upstream_head.starts_with("get /chat http/1.1\r\n"),
+        "{upstream_head}"
+    );
+    assert!(
+        upstream_head.contains("upgrade: echo\r\n"),
+        "{upstream_head}"
+    );
+}
+
+#[tokio::test]
+async fn fresh_responses_are_served_from_the_cache() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    let served = Arc::new(AtomicUsize::new(0));
+
+    let counter = served.clone();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = upstream.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            let head = read_head(&mut socket).await;
+            let body = match head.starts_with("get /fresh ") {
+                true => {
+                    "HTTP/1.0 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 5\r\n\r\nfresh"
+                }
+                false => "HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nnever",
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+            socket.get_mut().write_all(body.as_bytes()).await.unwrap();
+        }
+    });
+
+    let proxy = ProxyHandler::new(&format!("http://{upstream_addr}"))
+        .unwrap()
+        .with_cache(1024);
+    let server = LoopbackServer::start(proxy).await.unwrap();
+
+    for _ in 0..3 {
+        assert!(get(server.addr(), "/fresh")
+            .await
+            .ends_with("\r\n\r\nfresh"));
+    }
+    assert_eq!(served.load(Ordering::Relaxed), 1);
+
+    for _ in 0..2 {
+        assert!(get(server.addr(), "/never")
+            .await
+            .ends_with("\r\n\r\nnever"));
+    }
+    assert_eq!(served.load(Ordering::Relaxed), 3);
+}